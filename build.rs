@@ -0,0 +1,14 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // the gRPC server stubs are generated from proto/merkle.proto with a vendored protoc, so
+    // building with the `grpc` feature does not need protoc installed
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/merkle.proto"], &["proto"])
+            .expect("proto/merkle.proto compiles");
+    }
+}
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use merkle_trees_th::merkle_tree::fuzz::run_tree_ops;
+
+fuzz_target!(|data: &[u8]| run_tree_ops(data));
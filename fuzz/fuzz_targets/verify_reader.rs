@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use merkle_trees_th::merkle_tree::fuzz::run_verify_reader;
+
+fuzz_target!(|data: &[u8]| run_verify_reader(data));
@@ -0,0 +1,78 @@
+/*
+ * C ABI for merkle_trees_th, built with the `ffi` cargo feature. See src/ffi.rs. Build the shared
+ * library with `cargo rustc --release --features ffi --crate-type cdylib`.
+ *
+ * Trees and proofs are opaque handles owned by the caller. Strings are NUL-terminated UTF-8,
+ * and `0x` prefixed nodes need MERKLE_NODE_HEX_LEN bytes of output buffer.
+ */
+#ifndef MERKLE_TREES_TH_H
+#define MERKLE_TREES_TH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* return codes, one positive code per MerkleError variant */
+#define MERKLE_OK 0
+#define MERKLE_ERR_ENCODE 1
+#define MERKLE_ERR_INVALID_BYTES 2
+#define MERKLE_ERR_MAX_DEPTH_EXCEEDED 3
+#define MERKLE_ERR_INVALID_INDEX 4
+#define MERKLE_ERR_TREE_FULL 5
+#define MERKLE_ERR_PROOF_LENGTH_MISMATCH 6
+#define MERKLE_ERR_UNSORTED_LEAVES 7
+#define MERKLE_ERR_DUPLICATE_LEAF 8
+#define MERKLE_ERR_NOT_SORTED 9
+#define MERKLE_ERR_LEAF_PRESENT 10
+#define MERKLE_ERR_VALUE_OUT_OF_RANGE 11
+#define MERKLE_ERR_SERIALIZATION 12
+#define MERKLE_ERR_INTEGRITY 13
+#define MERKLE_ERR_NOTHING_TO_UNDO 14
+#define MERKLE_ERR_STORAGE 15
+#define MERKLE_ERR_STORE_MISMATCH 16
+#define MERKLE_ERR_ROOT_MISMATCH 17
+#define MERKLE_ERR_HASH_FUNCTION_MISMATCH 18
+#define MERKLE_ERR_INVALID_FORMAT 19
+#define MERKLE_ERR_UNSUPPORTED_SNAPSHOT_VERSION 20
+/* a pointer argument was null or a string argument was not UTF-8 */
+#define MERKLE_ERR_INVALID_ARGUMENT -1
+/* the output buffer cannot hold the string and its NUL terminator */
+#define MERKLE_ERR_BUFFER_TOO_SMALL -2
+/* the library panicked; the handles passed in should not be used again */
+#define MERKLE_ERR_PANIC -3
+
+/* output buffer size for a `0x` prefixed 32 byte node, including the NUL terminator */
+#define MERKLE_NODE_HEX_LEN 67
+
+typedef struct MerkleTreeHandle MerkleTreeHandle;
+typedef struct MerkleProofHandle MerkleProofHandle;
+
+/* returns NULL on any error */
+MerkleTreeHandle *merkle_tree_new(size_t depth, const char *initial_leaf_hex);
+void merkle_tree_free(MerkleTreeHandle *handle);
+
+int32_t merkle_tree_set(MerkleTreeHandle *handle, size_t index, const char *value_hex);
+int32_t merkle_tree_root(const MerkleTreeHandle *handle, char *out_buf, size_t out_len);
+
+/* stores a new proof handle in *out_proof, to be released with merkle_proof_free */
+int32_t merkle_tree_proof(const MerkleTreeHandle *handle, size_t index,
+                          MerkleProofHandle **out_proof);
+size_t merkle_proof_len(const MerkleProofHandle *proof);
+/* direction is 0 if the proven node is the left child at this step, 1 if it is the right */
+int32_t merkle_proof_step(const MerkleProofHandle *proof, size_t step, int32_t *out_direction,
+                          char *out_buf, size_t out_len);
+void merkle_proof_free(MerkleProofHandle *proof);
+
+/* stores 1 in *out_valid if the proof leads from leaf_hex to root_hex, 0 otherwise; the roots
+   are compared ignoring case and surrounding whitespace */
+int32_t merkle_verify(const MerkleProofHandle *proof, const char *leaf_hex, const char *root_hex,
+                      int32_t *out_valid);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MERKLE_TREES_TH_H */
@@ -0,0 +1,31 @@
+import pytest
+
+import merkle_trees
+
+LEAF = "0x" + "ab" * 32
+
+
+def test_set_proof_verify():
+    value = "0x" + "00" * 31 + "07"
+    tree = merkle_trees.MerkleTree(3, LEAF)
+    tree.set(1, value)
+
+    proof = tree.proof(1)
+    assert len(proof) == 2
+    assert proof[0][0] == "right"
+    assert merkle_trees.MerkleTree.verify(proof, value, tree.root())
+    shouted = " 0x%s " % tree.root()[2:].upper()
+    assert merkle_trees.MerkleTree.verify(proof, value, shouted)
+    assert not merkle_trees.MerkleTree.verify(proof, LEAF, tree.root())
+
+
+def test_errors_are_raised_as_distinct_exceptions():
+    with pytest.raises(merkle_trees.MaxDepthExceededError):
+        merkle_trees.MerkleTree(31, LEAF)
+    tree = merkle_trees.MerkleTree(2, LEAF)
+    with pytest.raises(merkle_trees.InvalidIndexError):
+        tree.set(2, LEAF)
+    with pytest.raises(merkle_trees.MerkleTreeError):
+        tree.proof(2)
+    with pytest.raises(merkle_trees.SerializationError):
+        merkle_trees.MerkleTree.verify([("up", LEAF)], LEAF, tree.root())
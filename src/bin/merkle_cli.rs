@@ -0,0 +1,190 @@
+//! merkle-cli
+//!
+//! Command line access to `MerkleTree` for ops runbooks, built with the `cli` feature:
+//!
+//! ```text
+//! merkle-cli build --depth <depth> --leaf <hex> [--out <snapshot>]
+//! merkle-cli set --snapshot <snapshot> --index <index> --value <hex>
+//! merkle-cli proof --snapshot <snapshot> --index <index> [--format json|text]
+//! merkle-cli verify --root <hex> --leaf <hex> --proof <proof file>
+//! ```
+//!
+//! Snapshot files hold a tree in the binary `MerkleTree::to_snapshot_bytes` container; files in
+//! the older `MerkleTree::to_json` format are still read, and rewritten as containers by `set`.
+//! Proofs are printed as `MerkleTree::proof_to_json` JSON, or as text with one `L:<sibling>` /
+//! `R:<sibling>` line per step, where `L` means the proven node is the left child at that step.
+//! `verify` reads either format, and exits 0 if the proof is valid and 1 if it is not. Usage and
+//! tree errors exit 2.
+use merkle_trees_th::errors::errors::MerkleError;
+use merkle_trees_th::merkle_tree::merkle_tree::{Direction, MerkleTree, ProofStep};
+use merkle_trees_th::merkle_tree::snapshot_file::SNAPSHOT_MAGIC;
+use std::collections::HashMap;
+use std::fs;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+  merkle-cli build --depth <depth> --leaf <hex> [--out <snapshot>]
+  merkle-cli set --snapshot <snapshot> --index <index> --value <hex>
+  merkle-cli proof --snapshot <snapshot> --index <index> [--format json|text]
+  merkle-cli verify --root <hex> --leaf <hex> --proof <proof file>";
+
+// the `--name value` options of a subcommand
+struct Options(HashMap<String, String>);
+
+impl Options {
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Self, String> {
+        let mut options = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .filter(|name| allowed.contains(name))
+                .ok_or_else(|| format!("unexpected argument {:?}", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("--{} needs a value", name))?;
+            options.insert(name.to_string(), value.clone());
+        }
+        Ok(Options(options))
+    }
+
+    fn optional(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.optional(name)
+            .ok_or_else(|| format!("missing --{}", name))
+    }
+
+    fn number(&self, name: &str) -> Result<usize, String> {
+        let value = self.required(name)?;
+        value
+            .parse()
+            .map_err(|_| format!("--{} must be a non-negative integer, got {:?}", name, value))
+    }
+}
+
+fn tree_error(error: MerkleError) -> String {
+    error.to_string()
+}
+
+fn read_file(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))
+}
+
+fn load_snapshot(path: &str) -> Result<MerkleTree, String> {
+    let contents = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    if contents.starts_with(&SNAPSHOT_MAGIC) {
+        return MerkleTree::from_snapshot_bytes(&contents).map_err(tree_error);
+    }
+    let json = String::from_utf8(contents).map_err(|_| format!("{} is not a snapshot", path))?;
+    MerkleTree::try_from_json(&json).map_err(tree_error)
+}
+
+fn save_snapshot(path: &str, tree: &MerkleTree) -> Result<(), String> {
+    let contents = tree.to_snapshot_bytes().map_err(tree_error)?;
+    fs::write(path, contents).map_err(|e| format!("cannot write {}: {}", path, e))
+}
+
+fn proof_to_text(proof: &[ProofStep]) -> String {
+    proof
+        .iter()
+        .map(|step| {
+            let direction = match step.direction() {
+                Direction::Left => 'L',
+                Direction::Right => 'R',
+            };
+            format!("{}:{}\n", direction, step.sibling())
+        })
+        .collect()
+}
+
+fn proof_from_text(text: &str) -> Result<Vec<ProofStep>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (direction, sibling) = match line.trim().split_once(':') {
+                Some(("L", sibling)) => (Direction::Left, sibling),
+                Some(("R", sibling)) => (Direction::Right, sibling),
+                _ => return Err(format!("malformed proof line {:?}", line)),
+            };
+            Ok(ProofStep::new(direction, sibling.to_string()))
+        })
+        .collect()
+}
+
+// returns the text to print on success
+fn run(command: &str, args: &[String]) -> Result<String, String> {
+    match command {
+        "build" => {
+            let options = Options::parse(args, &["depth", "leaf", "out"])?;
+            let tree = MerkleTree::new(options.number("depth")?, options.required("leaf")?)
+                .map_err(tree_error)?;
+            if let Some(path) = options.optional("out") {
+                save_snapshot(path, &tree)?;
+            }
+            Ok(tree.root())
+        }
+        "set" => {
+            let options = Options::parse(args, &["snapshot", "index", "value"])?;
+            let path = options.required("snapshot")?;
+            let mut tree = load_snapshot(path)?;
+            tree.set(options.number("index")?, options.required("value")?)
+                .map_err(tree_error)?;
+            save_snapshot(path, &tree)?;
+            Ok(tree.root())
+        }
+        "proof" => {
+            let options = Options::parse(args, &["snapshot", "index", "format"])?;
+            let tree = load_snapshot(options.required("snapshot")?)?;
+            let proof = tree.proof(options.number("index")?).map_err(tree_error)?;
+            match options.optional("format").unwrap_or("json") {
+                "json" => Ok(MerkleTree::proof_to_json(&proof)),
+                "text" => Ok(proof_to_text(&proof).trim_end().to_string()),
+                other => Err(format!("unknown proof format {:?}", other)),
+            }
+        }
+        _ => Err(format!("unknown command {:?}", command)),
+    }
+}
+
+// returns whether the proof is valid
+fn verify(args: &[String]) -> Result<bool, String> {
+    let options = Options::parse(args, &["root", "leaf", "proof"])?;
+    let contents = read_file(options.required("proof")?)?;
+    let proof = if contents.trim_start().starts_with('[') {
+        MerkleTree::proof_from_json(&contents).map_err(tree_error)?
+    } else {
+        proof_from_text(&contents)?
+    };
+    let leaf = options.required("leaf")?.to_string();
+    let root = MerkleTree::verify(&proof, leaf).map_err(tree_error)?;
+    // hex digits may come in either case, e.g. pasted from another tool
+    Ok(root.eq_ignore_ascii_case(options.required("root")?.trim()))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let result = match command.as_str() {
+        "verify" => verify(args).map(|valid| {
+            println!("{}", if valid { "valid" } else { "invalid" });
+            u8::from(!valid)
+        }),
+        _ => run(command, args).map(|output| {
+            println!("{}", output);
+            0
+        }),
+    };
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(message) => {
+            eprintln!("merkle-cli: {}\n{}", message, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
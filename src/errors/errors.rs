@@ -4,23 +4,64 @@ use hex::FromHexError;
 
 #[derive(Debug, PartialEq)]
 pub enum ValidationError {
-    BelowZero,
     Invalid,
+    Overflow,
+}
+
+/// An internal node that is not the hash of its two children.
+#[derive(Debug, PartialEq)]
+pub struct NodeMismatch {
+    /// array index of the node in the breadth-first layout
+    pub index: usize,
+    /// hash of the node's stored children, `None` if a child is not a valid node
+    pub expected: Option<String>,
+    /// the value stored at `index`
+    pub found: String,
+}
+
+/// Inconsistent internal nodes found by `MerkleTree::validate`.
+#[derive(Debug, PartialEq)]
+pub struct IntegrityError {
+    /// the first mismatching nodes in breadth-first order, at most `MAX_REPORTED_MISMATCHES`
+    pub mismatches: Vec<NodeMismatch>,
+    /// total number of mismatching nodes, which may exceed `mismatches.len()`
+    pub total: usize,
+}
+
+impl IntegrityError {
+    /// the most mismatches an `IntegrityError` lists individually
+    pub const MAX_REPORTED_MISMATCHES: usize = 16;
 }
 
 #[derive(Debug, PartialEq)]
 pub enum MerkleError {
     EncodeError(FromHexError),
     InvalidBytes,
-    MaxDepthExceeded,
+    MaxDepthExceeded { requested: usize, permitted: usize },
     InvalidIndex,
+    TreeFull,
+    ProofLengthMismatch { expected: usize, actual: usize },
+    UnsortedLeaves { index: usize },
+    DuplicateLeaf { index: usize },
+    NotSorted,
+    LeafPresent { index: usize },
+    ValueOutOfRange,
+    SerializationError(String),
+    Integrity(IntegrityError),
+    NothingToUndo,
+    Storage(String),
+    StoreMismatch,
+    RootMismatch { expected: String, actual: String },
+    HashFunctionMismatch,
+    InvalidFormat,
+    UnsupportedSnapshotVersion { found: u16, supported: u16 },
 }
 
 impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ValidationError::BelowZero => write!(f, "Input can only accept positive values"),
             ValidationError::Invalid => write!(f, "Input is invalid"),
+            ValidationError::Overflow => write!(f, "Result does not fit in a usize"),
         }
     }
 }
@@ -29,13 +70,93 @@ impl fmt::Display for MerkleError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             MerkleError::EncodeError(e) => write!(f, "{}", e),
-            MerkleError::InvalidBytes => write!(f, "leaf must be 32 byte hex string"),
-            MerkleError::MaxDepthExceeded => write!(f, "depth must be less than 30"),
+            MerkleError::InvalidBytes => {
+                write!(f, "value must be 0x followed by one hex digit pair per digest byte (64 digits for 32 bytes)")
+            }
+            MerkleError::MaxDepthExceeded {
+                requested,
+                permitted,
+            } => write!(
+                f,
+                "depth {} exceeds the maximum of {}",
+                requested, permitted
+            ),
             MerkleError::InvalidIndex => write!(f, "index is out of bounds"),
+            MerkleError::TreeFull => write!(f, "every leaf of the tree has been appended to"),
+            MerkleError::ProofLengthMismatch { expected, actual } => {
+                write!(f, "proof has {} steps, expected {}", actual, expected)
+            }
+            MerkleError::UnsortedLeaves { index } => {
+                write!(f, "leaf {} is smaller than the leaf before it", index)
+            }
+            MerkleError::DuplicateLeaf { index } => {
+                write!(f, "leaf {} is equal to the leaf before it", index)
+            }
+            MerkleError::NotSorted => write!(f, "tree was not built from sorted leaves"),
+            MerkleError::LeafPresent { index } => write!(f, "value is present at leaf {}", index),
+            MerkleError::ValueOutOfRange => {
+                write!(f, "value is not between two leaves of the tree")
+            }
+            MerkleError::SerializationError(ref message) => {
+                write!(f, "serialization failed: {}", message)
+            }
+            MerkleError::Integrity(ref e) => write!(f, "{}", e),
+            MerkleError::NothingToUndo => write!(f, "no recorded mutation left to undo"),
+            MerkleError::Storage(ref message) => write!(f, "node store failed: {}", message),
+            MerkleError::StoreMismatch => {
+                write!(
+                    f,
+                    "node store holds a tree of another depth or initial leaf"
+                )
+            }
+            MerkleError::RootMismatch {
+                ref expected,
+                ref actual,
+            } => write!(f, "root is {}, expected {}", actual, expected),
+            MerkleError::HashFunctionMismatch => {
+                write!(f, "hash output is wider than the field it is mapped into")
+            }
+            MerkleError::InvalidFormat => {
+                write!(
+                    f,
+                    "input ends partway through a proof step or has an invalid direction"
+                )
+            }
+            MerkleError::UnsupportedSnapshotVersion { found, supported } => write!(
+                f,
+                "snapshot format version {} is not supported, this build reads version {}",
+                found, supported
+            ),
         }
     }
 }
 
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mismatches.first() {
+            Some(first) => write!(
+                f,
+                "{} node(s) are not the hash of their children, first at index {}",
+                self.total, first.index
+            ),
+            None => write!(f, "tree is consistent"),
+        }
+    }
+}
+
+impl From<IntegrityError> for MerkleError {
+    fn from(err: IntegrityError) -> MerkleError {
+        MerkleError::Integrity(err)
+    }
+}
+
+// index arithmetic only fails for coordinates that do not name a node of any tree
+impl From<ValidationError> for MerkleError {
+    fn from(_: ValidationError) -> MerkleError {
+        MerkleError::InvalidIndex
+    }
+}
+
 impl From<FromHexError> for MerkleError {
     fn from(err: FromHexError) -> MerkleError {
         MerkleError::EncodeError(err)
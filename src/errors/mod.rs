@@ -1,2 +1,3 @@
 //! Validation Errors
+#[allow(clippy::module_inception)]
 pub mod errors;
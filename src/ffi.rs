@@ -0,0 +1,414 @@
+//! C FFI
+//!
+//! A C ABI over the default `MerkleTree`, declared in `include/merkle_trees_th.h`. Trees and
+//! proofs are opaque heap handles owned by the caller and released with `merkle_tree_free` and
+//! `merkle_proof_free`. Strings passed in and written out are NUL-terminated UTF-8; `0x`
+//! prefixed nodes need `MERKLE_NODE_HEX_LEN` bytes of output buffer including the NUL.
+//!
+//! Functions returning `int32_t` return `MERKLE_OK` or an error code: one positive code per
+//! `MerkleError` variant, or a negative code for errors specific to the boundary. No panic
+//! crosses the boundary; one is reported as `MERKLE_ERR_PANIC` (or a null handle).
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{
+    normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES, DIGEST_HEX,
+};
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+pub const MERKLE_OK: i32 = 0;
+pub const MERKLE_ERR_ENCODE: i32 = 1;
+pub const MERKLE_ERR_INVALID_BYTES: i32 = 2;
+pub const MERKLE_ERR_MAX_DEPTH_EXCEEDED: i32 = 3;
+pub const MERKLE_ERR_INVALID_INDEX: i32 = 4;
+pub const MERKLE_ERR_TREE_FULL: i32 = 5;
+pub const MERKLE_ERR_PROOF_LENGTH_MISMATCH: i32 = 6;
+pub const MERKLE_ERR_UNSORTED_LEAVES: i32 = 7;
+pub const MERKLE_ERR_DUPLICATE_LEAF: i32 = 8;
+pub const MERKLE_ERR_NOT_SORTED: i32 = 9;
+pub const MERKLE_ERR_LEAF_PRESENT: i32 = 10;
+pub const MERKLE_ERR_VALUE_OUT_OF_RANGE: i32 = 11;
+pub const MERKLE_ERR_SERIALIZATION: i32 = 12;
+pub const MERKLE_ERR_INTEGRITY: i32 = 13;
+pub const MERKLE_ERR_NOTHING_TO_UNDO: i32 = 14;
+pub const MERKLE_ERR_STORAGE: i32 = 15;
+pub const MERKLE_ERR_STORE_MISMATCH: i32 = 16;
+pub const MERKLE_ERR_ROOT_MISMATCH: i32 = 17;
+pub const MERKLE_ERR_HASH_FUNCTION_MISMATCH: i32 = 18;
+pub const MERKLE_ERR_INVALID_FORMAT: i32 = 19;
+pub const MERKLE_ERR_UNSUPPORTED_SNAPSHOT_VERSION: i32 = 20;
+/// a pointer argument was null or a string argument was not UTF-8
+pub const MERKLE_ERR_INVALID_ARGUMENT: i32 = -1;
+/// the output buffer cannot hold the string and its NUL terminator
+pub const MERKLE_ERR_BUFFER_TOO_SMALL: i32 = -2;
+/// the library panicked; the handles passed in should not be used again
+pub const MERKLE_ERR_PANIC: i32 = -3;
+
+/// output buffer size needed for a `0x` prefixed node, including the NUL terminator
+pub const MERKLE_NODE_HEX_LEN: usize = DIGEST_HEX + 3;
+
+/// Opaque tree handle.
+pub struct MerkleTreeHandle(MerkleTree);
+
+/// Opaque proof handle.
+pub struct MerkleProofHandle(Vec<ProofStep>);
+
+/// returns the C error code for `error`
+pub fn error_code(error: &MerkleError) -> i32 {
+    match error {
+        MerkleError::EncodeError(_) => MERKLE_ERR_ENCODE,
+        MerkleError::InvalidBytes => MERKLE_ERR_INVALID_BYTES,
+        MerkleError::MaxDepthExceeded { .. } => MERKLE_ERR_MAX_DEPTH_EXCEEDED,
+        MerkleError::InvalidIndex => MERKLE_ERR_INVALID_INDEX,
+        MerkleError::TreeFull => MERKLE_ERR_TREE_FULL,
+        MerkleError::ProofLengthMismatch { .. } => MERKLE_ERR_PROOF_LENGTH_MISMATCH,
+        MerkleError::UnsortedLeaves { .. } => MERKLE_ERR_UNSORTED_LEAVES,
+        MerkleError::DuplicateLeaf { .. } => MERKLE_ERR_DUPLICATE_LEAF,
+        MerkleError::NotSorted => MERKLE_ERR_NOT_SORTED,
+        MerkleError::LeafPresent { .. } => MERKLE_ERR_LEAF_PRESENT,
+        MerkleError::ValueOutOfRange => MERKLE_ERR_VALUE_OUT_OF_RANGE,
+        MerkleError::SerializationError(_) => MERKLE_ERR_SERIALIZATION,
+        MerkleError::Integrity(_) => MERKLE_ERR_INTEGRITY,
+        MerkleError::NothingToUndo => MERKLE_ERR_NOTHING_TO_UNDO,
+        MerkleError::Storage(_) => MERKLE_ERR_STORAGE,
+        MerkleError::StoreMismatch => MERKLE_ERR_STORE_MISMATCH,
+        MerkleError::RootMismatch { .. } => MERKLE_ERR_ROOT_MISMATCH,
+        MerkleError::HashFunctionMismatch => MERKLE_ERR_HASH_FUNCTION_MISMATCH,
+        MerkleError::InvalidFormat => MERKLE_ERR_INVALID_FORMAT,
+        MerkleError::UnsupportedSnapshotVersion { .. } => MERKLE_ERR_UNSUPPORTED_SNAPSHOT_VERSION,
+    }
+}
+
+// runs an entry point, turning errors and panics into codes
+fn guard(body: impl FnOnce() -> Result<(), i32>) -> i32 {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => MERKLE_OK,
+        Ok(Err(code)) => code,
+        Err(_) => MERKLE_ERR_PANIC,
+    }
+}
+
+fn code(error: MerkleError) -> i32 {
+    error_code(&error)
+}
+
+// borrows a NUL-terminated UTF-8 argument
+unsafe fn read_str<'a>(value: *const c_char) -> Result<&'a str, i32> {
+    if value.is_null() {
+        return Err(MERKLE_ERR_INVALID_ARGUMENT);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| MERKLE_ERR_INVALID_ARGUMENT)
+}
+
+// copies `value` and a NUL terminator into the caller's buffer
+unsafe fn write_str(value: &str, out: *mut c_char, out_len: usize) -> Result<(), i32> {
+    if out.is_null() {
+        return Err(MERKLE_ERR_INVALID_ARGUMENT);
+    }
+    if value.len() >= out_len {
+        return Err(MERKLE_ERR_BUFFER_TOO_SMALL);
+    }
+    ptr::copy_nonoverlapping(value.as_ptr(), out.cast::<u8>(), value.len());
+    *out.add(value.len()) = 0;
+    Ok(())
+}
+
+unsafe fn tree_ref<'a>(handle: *const MerkleTreeHandle) -> Result<&'a MerkleTree, i32> {
+    handle
+        .as_ref()
+        .map(|handle| &handle.0)
+        .ok_or(MERKLE_ERR_INVALID_ARGUMENT)
+}
+
+unsafe fn proof_ref<'a>(proof: *const MerkleProofHandle) -> Result<&'a [ProofStep], i32> {
+    proof
+        .as_ref()
+        .map(|proof| proof.0.as_slice())
+        .ok_or(MERKLE_ERR_INVALID_ARGUMENT)
+}
+
+/// Creates a tree of `depth` whose leaves are all `initial_leaf_hex`, see `MerkleTree::new`.
+/// Returns null on any error.
+///
+/// # Safety
+///
+/// `initial_leaf_hex` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_new(
+    depth: usize,
+    initial_leaf_hex: *const c_char,
+) -> *mut MerkleTreeHandle {
+    let tree = catch_unwind(AssertUnwindSafe(|| {
+        let leaf = read_str(initial_leaf_hex).ok()?;
+        MerkleTree::new(depth, leaf).ok()
+    }));
+    match tree {
+        Ok(Some(tree)) => Box::into_raw(Box::new(MerkleTreeHandle(tree))),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases a tree. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by `merkle_tree_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_free(handle: *mut MerkleTreeHandle) {
+    if !handle.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(handle))));
+    }
+}
+
+/// Sets the leaf at `index` to `value_hex`, see `MerkleTree::set`.
+///
+/// # Safety
+///
+/// `handle` must be a live tree handle and `value_hex` a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_set(
+    handle: *mut MerkleTreeHandle,
+    index: usize,
+    value_hex: *const c_char,
+) -> i32 {
+    guard(|| {
+        let tree = handle
+            .as_mut()
+            .map(|handle| &mut handle.0)
+            .ok_or(MERKLE_ERR_INVALID_ARGUMENT)?;
+        tree.set(index, read_str(value_hex)?).map_err(code)
+    })
+}
+
+/// Writes the `0x` prefixed root into `out_buf`.
+///
+/// # Safety
+///
+/// `handle` must be a live tree handle and `out_buf` valid for `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_root(
+    handle: *const MerkleTreeHandle,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    guard(|| write_str(&tree_ref(handle)?.root(), out_buf, out_len))
+}
+
+/// Generates the proof for the leaf at `index` and stores a new proof handle in `out_proof`.
+///
+/// # Safety
+///
+/// `handle` must be a live tree handle and `out_proof` valid for a pointer write.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_proof(
+    handle: *const MerkleTreeHandle,
+    index: usize,
+    out_proof: *mut *mut MerkleProofHandle,
+) -> i32 {
+    guard(|| {
+        if out_proof.is_null() {
+            return Err(MERKLE_ERR_INVALID_ARGUMENT);
+        }
+        let proof = tree_ref(handle)?.proof(index).map_err(code)?;
+        *out_proof = Box::into_raw(Box::new(MerkleProofHandle(proof)));
+        Ok(())
+    })
+}
+
+/// Returns the number of steps in a proof, or 0 for null.
+///
+/// # Safety
+///
+/// `proof` must be null or a live proof handle.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_len(proof: *const MerkleProofHandle) -> usize {
+    catch_unwind(AssertUnwindSafe(|| {
+        proof_ref(proof).map_or(0, <[ProofStep]>::len)
+    }))
+    .unwrap_or(0)
+}
+
+/// Reads step `step` of a proof: its direction (`0` if the proven node is the left child, `1`
+/// if it is the right child) into `out_direction` and the `0x` prefixed sibling into `out_buf`.
+///
+/// # Safety
+///
+/// `proof` must be a live proof handle, `out_direction` valid for a write and `out_buf` valid
+/// for `out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_step(
+    proof: *const MerkleProofHandle,
+    step: usize,
+    out_direction: *mut i32,
+    out_buf: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    guard(|| {
+        let step = proof_ref(proof)?
+            .get(step)
+            .ok_or(MERKLE_ERR_INVALID_INDEX)?;
+        if out_direction.is_null() {
+            return Err(MERKLE_ERR_INVALID_ARGUMENT);
+        }
+        write_str(step.sibling(), out_buf, out_len)?;
+        *out_direction = match step.direction() {
+            Direction::Left => 0,
+            Direction::Right => 1,
+        };
+        Ok(())
+    })
+}
+
+/// Releases a proof. Null is ignored.
+///
+/// # Safety
+///
+/// `proof` must be null or a handle returned by `merkle_tree_proof` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_free(proof: *mut MerkleProofHandle) {
+    if !proof.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(proof))));
+    }
+}
+
+/// Checks that `proof` leads from `leaf_hex` to `root_hex`, storing `1` in `out_valid` if it
+/// does and `0` otherwise. The roots are compared ignoring case and surrounding whitespace.
+///
+/// # Safety
+///
+/// `proof` must be a live proof handle, `leaf_hex` and `root_hex` valid NUL-terminated strings
+/// and `out_valid` valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_verify(
+    proof: *const MerkleProofHandle,
+    leaf_hex: *const c_char,
+    root_hex: *const c_char,
+    out_valid: *mut i32,
+) -> i32 {
+    guard(|| {
+        let proof = proof_ref(proof)?;
+        let (leaf, root) = (read_str(leaf_hex)?, read_str(root_hex)?);
+        if out_valid.is_null() {
+            return Err(MERKLE_ERR_INVALID_ARGUMENT);
+        }
+        let root = normalize_node(root, DIGEST_BYTES).map_err(code)?;
+        let computed = MerkleTree::verify(proof, leaf.to_string()).map_err(code)?;
+        *out_valid = i32::from(computed.eq_ignore_ascii_case(root));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    fn read_buf(buf: &[c_char]) -> String {
+        unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let leaf = CString::new(LEAF).unwrap();
+        let value = CString::new(format!("0x{:064x}", 7)).unwrap();
+        let mut buf = [0 as c_char; MERKLE_NODE_HEX_LEN];
+        unsafe {
+            let tree = merkle_tree_new(3, leaf.as_ptr());
+            assert!(!tree.is_null());
+            assert_eq!(merkle_tree_set(tree, 2, value.as_ptr()), MERKLE_OK);
+            assert_eq!(
+                merkle_tree_root(tree, buf.as_mut_ptr(), buf.len()),
+                MERKLE_OK
+            );
+            let root = read_buf(&buf);
+
+            let mut expected = MerkleTree::new(3, LEAF).unwrap();
+            expected.set(2, &format!("0x{:064x}", 7)).unwrap();
+            assert_eq!(root, expected.root());
+
+            let mut proof = ptr::null_mut();
+            assert_eq!(merkle_tree_proof(tree, 2, &mut proof), MERKLE_OK);
+            assert_eq!(merkle_proof_len(proof), 2);
+            let mut direction = -1;
+            assert_eq!(
+                merkle_proof_step(proof, 0, &mut direction, buf.as_mut_ptr(), buf.len()),
+                MERKLE_OK
+            );
+            assert_eq!(direction, 0);
+            assert_eq!(read_buf(&buf), expected.proof(2).unwrap()[0].sibling());
+
+            let root = CString::new(root).unwrap();
+            let mut valid = -1;
+            assert_eq!(
+                merkle_verify(proof, value.as_ptr(), root.as_ptr(), &mut valid),
+                MERKLE_OK
+            );
+            assert_eq!(valid, 1);
+            // the root is compared ignoring case and surrounding whitespace
+            let digits = root.to_str().unwrap()[2..].to_uppercase();
+            let shouted = CString::new(format!(" 0x{} ", digits)).unwrap();
+            valid = -1;
+            assert_eq!(
+                merkle_verify(proof, value.as_ptr(), shouted.as_ptr(), &mut valid),
+                MERKLE_OK
+            );
+            assert_eq!(valid, 1);
+            assert_eq!(
+                merkle_verify(proof, leaf.as_ptr(), root.as_ptr(), &mut valid),
+                MERKLE_OK
+            );
+            assert_eq!(valid, 0);
+
+            merkle_proof_free(proof);
+            merkle_tree_free(tree);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors() {
+        let leaf = CString::new(LEAF).unwrap();
+        let short = CString::new("0x00").unwrap();
+        let mut buf = [0 as c_char; 8];
+        unsafe {
+            assert!(merkle_tree_new(31, leaf.as_ptr()).is_null());
+            assert!(merkle_tree_new(3, ptr::null()).is_null());
+
+            let tree = merkle_tree_new(3, leaf.as_ptr());
+            assert_eq!(
+                merkle_tree_set(tree, 4, leaf.as_ptr()),
+                MERKLE_ERR_INVALID_INDEX
+            );
+            assert_eq!(
+                merkle_tree_set(tree, 0, short.as_ptr()),
+                MERKLE_ERR_INVALID_BYTES
+            );
+            assert_eq!(
+                merkle_tree_set(tree, 0, ptr::null()),
+                MERKLE_ERR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                merkle_tree_root(tree, buf.as_mut_ptr(), buf.len()),
+                MERKLE_ERR_BUFFER_TOO_SMALL
+            );
+            let mut proof = ptr::null_mut();
+            assert_eq!(
+                merkle_tree_proof(tree, 9, &mut proof),
+                MERKLE_ERR_INVALID_INDEX
+            );
+            assert!(proof.is_null());
+            merkle_tree_free(tree);
+        }
+        assert_eq!(
+            error_code(&MerkleError::NothingToUndo),
+            MERKLE_ERR_NOTHING_TO_UNDO
+        );
+        assert_eq!(guard(|| panic!("boom")), MERKLE_ERR_PANIC);
+    }
+}
@@ -0,0 +1,267 @@
+//! gRPC Service
+//!
+//! `MerkleTreeService` serves one shared `MerkleTree` over gRPC with `tonic`, implementing the
+//! `MerkleTreeService` of `proto/merkle.proto`. The generated messages and server stubs live in
+//! `proto`. Direction values are those of `Direction`, and `MerkleError`s are returned as
+//! `tonic::Status` codes, see `to_status`.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{Direction, MerkleTree, ProofStep};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tonic::{Code, Request, Response, Status};
+
+/// Messages and server stubs generated from `proto/merkle.proto`.
+pub mod proto {
+    // the generated trait has a `New` RPC, i.e. a `new` method taking `&self`
+    #![allow(clippy::new_ret_no_self, clippy::wrong_self_convention)]
+    tonic::include_proto!("merkle");
+}
+
+use proto::merkle_tree_service_server::{self, MerkleTreeServiceServer};
+
+/// Converts `error` into a `Status` whose code says what kind of failure it was.
+pub fn to_status(error: MerkleError) -> Status {
+    let code = match error {
+        MerkleError::EncodeError(_)
+        | MerkleError::InvalidBytes
+        | MerkleError::MaxDepthExceeded { .. }
+        | MerkleError::ProofLengthMismatch { .. }
+        | MerkleError::UnsortedLeaves { .. }
+        | MerkleError::DuplicateLeaf { .. }
+        | MerkleError::ValueOutOfRange
+        | MerkleError::SerializationError(_)
+        | MerkleError::InvalidFormat
+        | MerkleError::UnsupportedSnapshotVersion { .. } => Code::InvalidArgument,
+        MerkleError::InvalidIndex => Code::OutOfRange,
+        MerkleError::TreeFull => Code::ResourceExhausted,
+        MerkleError::NotSorted
+        | MerkleError::LeafPresent { .. }
+        | MerkleError::NothingToUndo
+        | MerkleError::StoreMismatch
+        | MerkleError::RootMismatch { .. }
+        | MerkleError::HashFunctionMismatch => Code::FailedPrecondition,
+        MerkleError::Integrity(_) => Code::DataLoss,
+        MerkleError::Storage(_) => Code::Unavailable,
+    };
+    Status::new(code, error.to_string())
+}
+
+// converts a proof step into its proto message
+fn step_message(step: &ProofStep) -> proto::ProofStep {
+    let direction = match step.direction() {
+        Direction::Left => proto::Direction::Left,
+        Direction::Right => proto::Direction::Right,
+    };
+    proto::ProofStep {
+        direction: direction.into(),
+        sibling: step.sibling().to_string(),
+    }
+}
+
+// converts a proto proof step back into a proof step
+fn step_from_message(step: proto::ProofStep) -> Result<ProofStep, MerkleError> {
+    let direction = match proto::Direction::try_from(step.direction) {
+        Ok(proto::Direction::Left) => Direction::Left,
+        Ok(proto::Direction::Right) => Direction::Right,
+        Err(_) => {
+            return Err(MerkleError::SerializationError(format!(
+                "unknown proof direction {}",
+                step.direction
+            )))
+        }
+    };
+    Ok(ProofStep::new(direction, step.sibling))
+}
+
+// converts a proto index, which does not fit in a usize on every platform
+fn leaf_index(index: u64) -> Result<usize, MerkleError> {
+    usize::try_from(index).map_err(|_| MerkleError::InvalidIndex)
+}
+
+/// gRPC service over a `MerkleTree` shared with the rest of the application.
+#[derive(Clone)]
+pub struct MerkleTreeService {
+    tree: Arc<Mutex<MerkleTree>>,
+}
+
+impl MerkleTreeService {
+    /// Constructs a service for `tree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to serve, shared with whoever else holds it.
+    ///
+    /// # Returns
+    ///
+    /// * The new service.
+    pub fn new(tree: Arc<Mutex<MerkleTree>>) -> Self {
+        MerkleTreeService { tree }
+    }
+
+    /// returns the served tree
+    pub fn tree(&self) -> Arc<Mutex<MerkleTree>> {
+        self.tree.clone()
+    }
+
+    /// returns the tonic server for this service, ready to be added to a `tonic` router
+    pub fn into_server(self) -> MerkleTreeServiceServer<Self> {
+        MerkleTreeServiceServer::new(self)
+    }
+
+    // `Status` is what every RPC fails with, however large
+    #[allow(clippy::result_large_err)]
+    fn lock(&self) -> Result<MutexGuard<'_, MerkleTree>, Status> {
+        self.tree
+            .lock()
+            .map_err(|_| Status::internal("a request panicked while holding the tree"))
+    }
+}
+
+#[tonic::async_trait]
+impl merkle_tree_service_server::MerkleTreeService for MerkleTreeService {
+    async fn new(
+        &self,
+        request: Request<proto::NewRequest>,
+    ) -> Result<Response<proto::RootResponse>, Status> {
+        let request = request.into_inner();
+        // a depth beyond usize is beyond the maximum depth too
+        let depth = usize::try_from(request.depth).unwrap_or(usize::MAX);
+        let tree = MerkleTree::new(depth, &request.initial_leaf).map_err(to_status)?;
+        let root = tree.root();
+        *self.lock()? = tree;
+        Ok(Response::new(proto::RootResponse { root }))
+    }
+
+    async fn set(
+        &self,
+        request: Request<proto::SetRequest>,
+    ) -> Result<Response<proto::RootResponse>, Status> {
+        let request = request.into_inner();
+        let mut tree = self.lock()?;
+        let index = leaf_index(request.index).map_err(to_status)?;
+        tree.set(index, &request.value).map_err(to_status)?;
+        Ok(Response::new(proto::RootResponse { root: tree.root() }))
+    }
+
+    async fn root(
+        &self,
+        _request: Request<proto::RootRequest>,
+    ) -> Result<Response<proto::RootResponse>, Status> {
+        let root = self.lock()?.root();
+        Ok(Response::new(proto::RootResponse { root }))
+    }
+
+    async fn proof(
+        &self,
+        request: Request<proto::ProofRequest>,
+    ) -> Result<Response<proto::ProofResponse>, Status> {
+        let index = leaf_index(request.into_inner().index).map_err(to_status)?;
+        let proof = self.lock()?.proof(index).map_err(to_status)?;
+        Ok(Response::new(proto::ProofResponse {
+            steps: proof.iter().map(step_message).collect(),
+        }))
+    }
+
+    async fn verify(
+        &self,
+        request: Request<proto::VerifyRequest>,
+    ) -> Result<Response<proto::VerifyResponse>, Status> {
+        let request = request.into_inner();
+        let proof = request
+            .steps
+            .into_iter()
+            .map(step_from_message)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(to_status)?;
+        let root = MerkleTree::verify(&proof, request.leaf).map_err(to_status)?;
+        Ok(Response::new(proto::VerifyResponse {
+            valid: root == request.root,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merkle_tree_service_server::MerkleTreeService as _;
+    use super::*;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_service_round_trip() {
+        let shared = Arc::new(Mutex::new(MerkleTree::new(2, LEAF).unwrap()));
+        let service = MerkleTreeService::new(shared.clone());
+        let value = format!("0x{:064x}", 7);
+        block_on(async {
+            let new = proto::NewRequest {
+                depth: 3,
+                initial_leaf: LEAF.to_string(),
+            };
+            service.new(Request::new(new)).await.unwrap();
+            let set = proto::SetRequest {
+                index: 1,
+                value: value.clone(),
+            };
+            let root = service
+                .set(Request::new(set))
+                .await
+                .unwrap()
+                .into_inner()
+                .root;
+            assert_eq!(root, shared.lock().unwrap().root());
+            let served = service.root(Request::new(proto::RootRequest {})).await;
+            assert_eq!(served.unwrap().into_inner().root, root);
+
+            let proof = service.proof(Request::new(proto::ProofRequest { index: 1 }));
+            let steps = proof.await.unwrap().into_inner().steps;
+            assert_eq!(steps.len(), 2);
+            assert_eq!(steps[0].direction, proto::Direction::Right as i32);
+
+            for (leaf, valid) in [(value.clone(), true), (LEAF.to_string(), false)] {
+                let verify = proto::VerifyRequest {
+                    steps: steps.clone(),
+                    leaf,
+                    root: root.clone(),
+                };
+                let response = service.verify(Request::new(verify)).await.unwrap();
+                assert_eq!(response.into_inner().valid, valid);
+            }
+        });
+    }
+
+    #[test]
+    fn test_errors_map_to_status_codes() {
+        let service =
+            MerkleTreeService::new(Arc::new(Mutex::new(MerkleTree::new(2, LEAF).unwrap())));
+        block_on(async {
+            let proof = service.proof(Request::new(proto::ProofRequest { index: 2 }));
+            assert_eq!(proof.await.unwrap_err().code(), Code::OutOfRange);
+            let new = proto::NewRequest {
+                depth: 31,
+                initial_leaf: LEAF.to_string(),
+            };
+            let new = service.new(Request::new(new)).await;
+            assert_eq!(new.unwrap_err().code(), Code::InvalidArgument);
+            let verify = proto::VerifyRequest {
+                steps: vec![proto::ProofStep {
+                    direction: 7,
+                    sibling: LEAF.to_string(),
+                }],
+                leaf: LEAF.to_string(),
+                root: LEAF.to_string(),
+            };
+            let verify = service.verify(Request::new(verify)).await;
+            assert_eq!(verify.unwrap_err().code(), Code::InvalidArgument);
+        });
+        assert_eq!(
+            to_status(MerkleError::TreeFull).code(),
+            Code::ResourceExhausted
+        );
+    }
+}
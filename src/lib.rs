@@ -10,6 +10,17 @@
 //!
 //! - **utils**: Functions and helpers for Merkle tree operations.
 //!
+//! - **test_vectors** (`test_vectors` feature): deterministic trees, roots and proofs for testing
+//!   other implementations against this crate.
+//!
+//! - **ffi** (`ffi` feature): C ABI over `MerkleTree`, declared in `include/merkle_trees_th.h`.
+//!
+//! - **grpc** (`grpc` feature): `tonic` service for the gRPC interface in `proto/merkle.proto`.
+//!
+//! - **python** (`python` feature): PyO3 extension module `merkle_trees` for Python clients.
+//!
+//! - **wasm** (`wasm` feature): `wasm-bindgen` exports for JavaScript clients.
+//!
 //! ## Usage
 //!
 //! Use this library for precise error handling and utilities in Merkle tree operations.
@@ -19,5 +30,15 @@
 //! **Note**: This library depends on external crates, including `hex`, for error handling.
 
 pub mod errors;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod merkle_tree;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,184 @@
+//! Arena Allocated Trees
+//!
+//! `ArenaMerkleTree` stores every node string in a caller supplied `bumpalo::Bump` instead of
+//! one heap allocation per node. Building a tree reuses a few scratch buffers and only allocates
+//! from the arena, and all nodes are freed at once when the arena is dropped. Meant for building
+//! very large, read-only trees; use `MerkleTree` for trees that are mutated.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{
+    check_depth, validate_leaf, zero_leaf, Direction, ProofStep, MAX_DEPTH,
+};
+use crate::utils::index::{left_child_index, parent_index};
+use bumpalo::Bump;
+
+/// Read-only merkle tree whose nodes live in a `Bump` arena, see the module documentation.
+pub struct ArenaMerkleTree<'bump, H = Sha3_256Hasher> {
+    nodes: Vec<&'bump str>,
+    hasher: H,
+}
+
+impl<'bump> ArenaMerkleTree<'bump> {
+    /// Constructs a tree in `bump` whose leaves are `leaves`, padded like `MerkleTree::from_leaves`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bump` - The arena holding the node strings.
+    /// * `leaves` - The leaf values. Each must be 32 byte hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new tree or Error.
+    pub fn from_leaves_in(bump: &'bump Bump, leaves: &[&str]) -> Result<Self, MerkleError> {
+        ArenaMerkleTree::from_leaves_with_hasher_in(bump, leaves, Sha3_256Hasher)
+    }
+}
+
+impl<'bump, H: MerkleHasher> ArenaMerkleTree<'bump, H> {
+    /// Constructs a tree in `bump` whose leaves are `leaves`, combining nodes with `hasher`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bump` - The arena holding the node strings.
+    /// * `leaves` - The leaf values. Each must be `H::DIGEST_BYTES` hex string starting with `0x`
+    /// * `hasher` - hash function the tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new tree or Error.
+    pub fn from_leaves_with_hasher_in(
+        bump: &'bump Bump,
+        leaves: &[&str],
+        hasher: H,
+    ) -> Result<Self, MerkleError> {
+        if leaves.is_empty() {
+            return Ok(ArenaMerkleTree {
+                nodes: Vec::new(),
+                hasher,
+            });
+        }
+        let leaf_count = leaves.len().next_power_of_two();
+        check_depth(leaf_count.trailing_zeros() as usize + 1, MAX_DEPTH)?;
+
+        let mut nodes = vec![""; leaf_count - 1];
+        for leaf in leaves {
+            validate_leaf(leaf, H::DIGEST_BYTES)?;
+            nodes.push(bump.alloc_str(leaf));
+        }
+        // every padding leaf shares a single arena string
+        nodes.resize(
+            2 * leaf_count - 1,
+            bump.alloc_str(&zero_leaf(H::DIGEST_BYTES)),
+        );
+
+        let width = H::DIGEST_BYTES;
+        let mut children = vec![0u8; 2 * width];
+        let mut node = vec![b'0'; 2 + 2 * width];
+        node[1] = b'x';
+        for index in (0..leaf_count - 1).rev() {
+            let left = left_child_index(index)?;
+            hex::decode_to_slice(&nodes[left][2..], &mut children[..width])?;
+            hex::decode_to_slice(&nodes[left + 1][2..], &mut children[width..])?;
+            let digest = hasher.hash_pair(&children[..width], &children[width..]);
+            hex::encode_to_slice(&digest, &mut node[2..])?;
+            let node = std::str::from_utf8(&node).expect("hex encoding is ascii");
+            nodes[index] = bump.alloc_str(node);
+        }
+        Ok(ArenaMerkleTree { nodes, hasher })
+    }
+
+    /// returns the root of the tree, or the hash of the empty string if the tree has no leaves
+    pub fn root(&self) -> String {
+        match self.nodes.first() {
+            Some(root) => root.to_string(),
+            None => format!("0x{}", hex::encode(self.hasher.digest(&[]))),
+        }
+    }
+
+    /// returns the number of leaves in the tree
+    pub fn num_leaves(&self) -> usize {
+        self.nodes.len().div_ceil(2)
+    }
+
+    /// Returns the value stored at `leaf_index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to read.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf or `MerkleError::InvalidIndex`.
+    pub fn get_leaf(&self, leaf_index: usize) -> Result<&'bump str, MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Ok(self.nodes[self.nodes.len() - leaf_count + leaf_index])
+    }
+
+    /// Constructs the proof for `leaf_index`, see `MerkleTree::proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps or `MerkleError::InvalidIndex`.
+    pub fn proof(&self, leaf_index: usize) -> Result<Vec<ProofStep>, MerkleError> {
+        self.get_leaf(leaf_index)?;
+        let mut proof = Vec::new();
+        let mut index = self.nodes.len() - self.num_leaves() + leaf_index;
+        while let Some(parent) = parent_index(index) {
+            proof.push(if left_child_index(parent) == Ok(index) {
+                ProofStep::new(Direction::Left, self.nodes[index + 1].to_string())
+            } else {
+                ProofStep::new(Direction::Right, self.nodes[index - 1].to_string())
+            });
+            index = parent;
+        }
+        Ok(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_arena_tree_matches_default_allocator() {
+        let leaves: Vec<String> = (0..1 << 15).map(|i| format!("0x{:064x}", i)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let tree = MerkleTree::from_leaves(&leaves).unwrap();
+
+        let bump = Bump::new();
+        let arena = ArenaMerkleTree::from_leaves_in(&bump, &leaves).unwrap();
+        assert_eq!(tree.depth(), 16);
+        assert_eq!(arena.root(), tree.root());
+        assert_eq!(arena.proof(1234).unwrap(), tree.proof(1234).unwrap());
+        assert_eq!(arena.get_leaf(7).unwrap(), leaves[7]);
+    }
+
+    #[test]
+    fn test_arena_tree_padding_and_errors() {
+        let bump = Bump::new();
+        let leaf = format!("0x{:064x}", 1);
+        let leaves = [leaf.as_str(); 3];
+        let arena = ArenaMerkleTree::from_leaves_in(&bump, &leaves).unwrap();
+        assert_eq!(
+            arena.root(),
+            MerkleTree::from_leaves(&leaves).unwrap().root()
+        );
+        assert_eq!(arena.num_leaves(), 4);
+        assert_eq!(arena.get_leaf(4), Err(MerkleError::InvalidIndex));
+
+        assert_eq!(
+            ArenaMerkleTree::from_leaves_in(&bump, &["0x00"]).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+        let empty = ArenaMerkleTree::from_leaves_in(&bump, &[]).unwrap();
+        assert_eq!(empty.root(), MerkleTree::empty().root());
+    }
+}
@@ -0,0 +1,102 @@
+//! Async Construction
+//!
+//! `MerkleTree::new_async` builds the same tree as `MerkleTree::new`, but yields to the tokio
+//! runtime every `YIELD_INTERVAL` node writes so that constructing a very large tree does not
+//! starve the other tasks on its worker thread.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{tree_size, validate_leaf, MerkleTree, MAX_DEPTH};
+use tokio::task::yield_now;
+
+/// the number of nodes written between two yields to the runtime
+pub const YIELD_INTERVAL: usize = 1 << 14;
+
+// fills `nodes` with `value`, yielding to the runtime every `YIELD_INTERVAL` nodes
+async fn fill_level(nodes: &mut [String], value: &str) {
+    for chunk in nodes.chunks_mut(YIELD_INTERVAL) {
+        for node in chunk {
+            *node = value.to_string();
+        }
+        yield_now().await;
+    }
+}
+
+impl MerkleTree {
+    /// Given `depth` (one indexed) and `initial_leaf`, asynchronously constructs the tree
+    /// `MerkleTree::new(depth, initial_leaf)` would, yielding to the runtime between chunks of
+    /// each level.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * A future resolving to the new MerkleTree or Error.
+    pub async fn new_async(depth: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
+        let total_nodes = tree_size(depth, MAX_DEPTH)?;
+        let hasher: Sha3_256Hasher = Sha3_256Hasher;
+        validate_leaf(initial_leaf, <Sha3_256Hasher>::DIGEST_BYTES)?;
+        if total_nodes == 0 {
+            return Ok(MerkleTree::empty().with_initial_leaf(initial_leaf));
+        }
+        let depth = depth - 1;
+
+        let leaf_count = total_nodes.div_ceil(2);
+        let mut nodes = vec![String::new(); total_nodes];
+        fill_level(&mut nodes[leaf_count - 1..], initial_leaf).await;
+
+        let mut current_hash = hex::decode(&initial_leaf[2..])?;
+        for d in (0..depth).rev() {
+            current_hash = hasher.hash_pair(&current_hash, &current_hash);
+            let current_hash_string = format!("0x{}", hex::encode(&current_hash));
+            fill_level(
+                &mut nodes[(1 << d) - 1..(1 << (d + 1)) - 1],
+                &current_hash_string,
+            )
+            .await;
+        }
+
+        Ok(MerkleTree::with_nodes(nodes, hasher).with_initial_leaf(initial_leaf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_new_async_matches_new_and_yields() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            // on a current thread runtime the spawned task only runs if the build yields
+            let ran = Arc::new(AtomicBool::new(false));
+            let flag = ran.clone();
+            tokio::spawn(async move { flag.store(true, Ordering::SeqCst) });
+
+            let tree = MerkleTree::new_async(16, LEAF).await.unwrap();
+            assert!(ran.load(Ordering::SeqCst));
+            assert_eq!(tree.root(), MerkleTree::new(16, LEAF).unwrap().root());
+            assert_eq!(tree.num_leaves(), 1 << 15);
+
+            assert_eq!(
+                MerkleTree::new_async(MAX_DEPTH + 1, LEAF).await.err(),
+                Some(MerkleError::MaxDepthExceeded {
+                    requested: MAX_DEPTH + 1,
+                    permitted: MAX_DEPTH
+                })
+            );
+            assert_eq!(
+                MerkleTree::new_async(4, "0x00").await.err(),
+                Some(MerkleError::InvalidBytes)
+            );
+        });
+    }
+}
@@ -0,0 +1,181 @@
+//! Stateless Batch Verification
+//!
+//! `MerkleTree::verify_batch_updates` lets a light client holding only a root check that a set
+//! of leaf updates leads to a claimed new root. Each update carries the leaf's old value and its
+//! proof against the old root. The proofs are checked first, which authenticates every sibling
+//! they contain, and together they cover every node the updates can change. The updates are then
+//! replayed on that partial tree, each one's path refreshing the siblings later proofs share with
+//! it, and the root that comes out is compared with the claimed one.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::Sha3_256Hasher;
+use crate::merkle_tree::merkle_tree::{
+    hash_nodes, normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES,
+};
+use std::collections::HashMap;
+
+/// A leaf update with what a verifier without the tree needs to check it.
+#[derive(Clone, Copy, Debug)]
+pub struct ProvenUpdate<'a> {
+    /// 0 indexed leaf being updated
+    pub leaf_index: usize,
+    /// value of the leaf under the old root
+    pub old_value: &'a str,
+    /// value the leaf is set to
+    pub new_value: &'a str,
+    /// proof of `old_value` under the old root
+    pub proof: &'a [ProofStep],
+}
+
+// hashes a node with its sibling in the order the proof step gives
+fn hash_step(hasher: &Sha3_256Hasher, step: &ProofStep, node: &str) -> Result<String, MerkleError> {
+    match step.direction() {
+        Direction::Right => hash_nodes(hasher, step.sibling(), node),
+        Direction::Left => hash_nodes(hasher, node, step.sibling()),
+    }
+}
+
+impl MerkleTree {
+    /// Checks that applying `updates` in order to the tree with root `old_root` produces
+    /// `new_root`, without the tree. See the module documentation. As with `set_batch`, the last
+    /// of several updates to the same leaf wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_root` - The root before the updates.
+    /// * `updates` - The updates, each with the leaf's old value and its proof under `old_root`.
+    /// * `new_root` - The claimed root after the updates.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing whether every proof holds under `old_root` and the updates lead to
+    ///   `new_root`, `MerkleError::ProofLengthMismatch` if the proofs are for different depths,
+    ///   `MerkleError::InvalidIndex` if a leaf index is beyond the proofs' tree, or Error.
+    pub fn verify_batch_updates(
+        old_root: &str,
+        updates: &[ProvenUpdate],
+        new_root: &str,
+    ) -> Result<bool, MerkleError> {
+        let old_root = normalize_node(old_root, DIGEST_BYTES)?;
+        let new_root = normalize_node(new_root, DIGEST_BYTES)?;
+        let steps = match updates.first() {
+            Some(update) => update.proof.len(),
+            None => return Ok(old_root.eq_ignore_ascii_case(new_root)),
+        };
+        let hasher = Sha3_256Hasher::<DIGEST_BYTES>;
+
+        for update in updates {
+            if update.proof.len() != steps {
+                return Err(MerkleError::ProofLengthMismatch {
+                    expected: steps,
+                    actual: update.proof.len(),
+                });
+            }
+            if steps < usize::BITS as usize && update.leaf_index >> steps != 0 {
+                return Err(MerkleError::InvalidIndex);
+            }
+            normalize_node(update.new_value, DIGEST_BYTES)?;
+            let directions_match = update.proof.iter().enumerate().all(|(level, step)| {
+                (*step.direction() == Direction::Right) == ((update.leaf_index >> level) & 1 == 1)
+            });
+            let old_value = update.old_value.to_string();
+            if !directions_match
+                || !MerkleTree::verify(update.proof, old_value)?.eq_ignore_ascii_case(old_root)
+            {
+                return Ok(false);
+            }
+        }
+
+        // the current value of every node the replay has rewritten, by `(level, position)`
+        let mut written: HashMap<(usize, usize), String> = HashMap::new();
+        let mut root = old_root.to_string();
+        for update in updates {
+            let mut position = update.leaf_index;
+            let mut node = normalize_node(update.new_value, DIGEST_BYTES)?.to_string();
+            for (level, step) in update.proof.iter().enumerate() {
+                written.insert((level, position), node.clone());
+                let step = match written.get(&(level, position ^ 1)) {
+                    Some(sibling) => ProofStep::new(*step.direction(), sibling.clone()),
+                    None => step.clone(),
+                };
+                node = hash_step(&hasher, &step, &node)?;
+                position >>= 1;
+            }
+            root = node;
+        }
+        Ok(root.eq_ignore_ascii_case(new_root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    #[test]
+    fn test_verify_batch_updates() {
+        let leaves: Vec<String> = (0..8).map(|i| format!("0x{:064x}", i)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let mut tree = MerkleTree::from_leaves(&leaves).unwrap();
+        let old_root = tree.root();
+        let (a, b) = (format!("0x{:064x}", 100), format!("0x{:064x}", 200));
+        // siblings, a shared subtree, and a second update to the first leaf
+        let changes = [
+            (2, a.as_str()),
+            (3, b.as_str()),
+            (6, a.as_str()),
+            (2, b.as_str()),
+        ];
+        let proofs: Vec<Vec<ProofStep>> = changes
+            .iter()
+            .map(|&(leaf_index, _)| tree.proof(leaf_index).unwrap())
+            .collect();
+        let updates: Vec<ProvenUpdate> = changes
+            .iter()
+            .zip(&proofs)
+            .map(|(&(leaf_index, new_value), proof)| ProvenUpdate {
+                leaf_index,
+                old_value: leaves[leaf_index],
+                new_value,
+                proof,
+            })
+            .collect();
+        tree.set_batch(&changes).unwrap();
+        let new_root = tree.root();
+
+        assert!(MerkleTree::verify_batch_updates(&old_root, &updates, &new_root).unwrap());
+        assert!(!MerkleTree::verify_batch_updates(&old_root, &updates, &old_root).unwrap());
+        assert!(!MerkleTree::verify_batch_updates(&new_root, &updates, &new_root).unwrap());
+        let upper = format!("0x{}", old_root[2..].to_uppercase());
+        assert!(MerkleTree::verify_batch_updates(&old_root, &[], &upper).unwrap());
+
+        // a wrong old value, or a proof for another leaf, fails under the old root
+        let mut forged = updates.clone();
+        forged[1].old_value = ZERO_LEAF;
+        assert!(!MerkleTree::verify_batch_updates(&old_root, &forged, &new_root).unwrap());
+        let mut moved = updates.clone();
+        moved[1].leaf_index = 1;
+        assert!(!MerkleTree::verify_batch_updates(&old_root, &moved, &new_root).unwrap());
+
+        let mut short = updates.clone();
+        short[2].proof = &proofs[2][1..];
+        assert_eq!(
+            MerkleTree::verify_batch_updates(&old_root, &short, &new_root),
+            Err(MerkleError::ProofLengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        let mut outside = updates.clone();
+        outside[0].leaf_index = 8;
+        assert_eq!(
+            MerkleTree::verify_batch_updates(&old_root, &outside, &new_root),
+            Err(MerkleError::InvalidIndex)
+        );
+        let mut malformed = updates;
+        malformed[3].new_value = "0x00";
+        assert_eq!(
+            MerkleTree::verify_batch_updates(&old_root, &malformed, &new_root),
+            Err(MerkleError::InvalidBytes)
+        );
+    }
+}
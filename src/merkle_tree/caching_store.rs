@@ -0,0 +1,220 @@
+//! Node Caching
+//!
+//! `CachingStore` wraps another `NodeStore` with a bounded least recently used cache of nodes,
+//! so the upper levels that every proof reads are served from memory instead of a disk or
+//! network backed store. Writes go to the wrapped store first and then into the cache, so the
+//! cache never holds a stale node. Lookups of nodes that were never written are cached too.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::store::NodeStore;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[derive(Default)]
+struct LruEntries {
+    // cached node, or `None` for an unwritten node, and the tick it was last used at
+    nodes: HashMap<usize, (Option<String>, u64)>,
+    // node indices by the tick they were last used at, least recently used first
+    order: BTreeMap<u64, usize>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl LruEntries {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn insert(&mut self, capacity: usize, index: usize, node: Option<String>) {
+        if capacity == 0 {
+            return;
+        }
+        let tick = self.next_tick();
+        match self.nodes.insert(index, (node, tick)) {
+            Some((_, used)) => {
+                self.order.remove(&used);
+            }
+            None if self.nodes.len() > capacity => {
+                if let Some((_, oldest)) = self.order.pop_first() {
+                    self.nodes.remove(&oldest);
+                }
+            }
+            None => {}
+        }
+        self.order.insert(tick, index);
+    }
+}
+
+/// `NodeStore` serving recently used nodes of another store from memory, see the module
+/// documentation.
+pub struct CachingStore<S> {
+    inner: S,
+    capacity: usize,
+    // `NodeStore::get` fills the cache through `&self`
+    entries: Mutex<LruEntries>,
+}
+
+impl<S: NodeStore> CachingStore<S> {
+    /// Wraps `inner` with a cache of up to `capacity` nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The store holding the nodes.
+    /// * `capacity` - The most nodes kept in memory. 0 disables caching.
+    ///
+    /// # Returns
+    ///
+    /// * The caching store.
+    pub fn with_capacity(inner: S, capacity: usize) -> Self {
+        CachingStore {
+            inner,
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, LruEntries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// returns the most nodes kept in memory
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// returns the number of nodes currently cached
+    pub fn len(&self) -> usize {
+        self.entries().nodes.len()
+    }
+
+    /// returns true if no node is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns the number of reads served from the cache
+    pub fn hits(&self) -> u64 {
+        self.entries().hits
+    }
+
+    /// returns the number of reads passed on to the wrapped store
+    pub fn misses(&self) -> u64 {
+        self.entries().misses
+    }
+
+    /// returns the wrapped store
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// consumes the cache, returning the wrapped store
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: NodeStore> NodeStore for CachingStore<S> {
+    fn get(&self, index: usize) -> Result<Option<String>, MerkleError> {
+        {
+            let mut guard = self.entries();
+            let entries = &mut *guard;
+            let tick = entries.next_tick();
+            if let Some((node, used)) = entries.nodes.get_mut(&index) {
+                let node = node.clone();
+                let used = std::mem::replace(used, tick);
+                entries.order.remove(&used);
+                entries.order.insert(tick, index);
+                entries.hits += 1;
+                return Ok(node);
+            }
+            entries.misses += 1;
+        }
+        let node = self.inner.get(index)?;
+        self.entries().insert(self.capacity, index, node.clone());
+        Ok(node)
+    }
+
+    fn write_batch(&mut self, nodes: &[(usize, String)]) -> Result<(), MerkleError> {
+        let capacity = self.capacity;
+        let result = self.inner.write_batch(nodes);
+        let entries = self
+            .entries
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        for (index, node) in nodes {
+            match result {
+                Ok(()) => entries.insert(capacity, *index, Some(node.clone())),
+                // the wrapped store may hold either value now, so forget it
+                Err(_) => {
+                    if let Some((_, used)) = entries.nodes.remove(index) {
+                        entries.order.remove(&used);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn metadata(&self) -> Result<Option<(usize, String)>, MerkleError> {
+        self.inner.metadata()
+    }
+
+    fn set_metadata(&mut self, depth: usize, initial_leaf: &str) -> Result<(), MerkleError> {
+        self.inner.set_metadata(depth, initial_leaf)
+    }
+
+    fn flush(&mut self) -> Result<(), MerkleError> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::store::{MemoryStore, StoredMerkleTree};
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_upper_levels_served_from_cache() {
+        let store = CachingStore::with_capacity(MemoryStore::new(), 16);
+        let tree = StoredMerkleTree::open(store, 5, LEAF).unwrap();
+
+        tree.proof(0).unwrap();
+        assert_eq!((tree.store().hits(), tree.store().misses()), (0, 4));
+        // leaf 3 shares the two upper siblings of leaf 0's proof
+        tree.proof(3).unwrap();
+        assert_eq!((tree.store().hits(), tree.store().misses()), (2, 6));
+        tree.proof(3).unwrap();
+        assert_eq!((tree.store().hits(), tree.store().misses()), (6, 6));
+    }
+
+    #[test]
+    fn test_cached_tree_matches_uncached() {
+        let mut cached =
+            StoredMerkleTree::open(CachingStore::with_capacity(MemoryStore::new(), 3), 5, LEAF)
+                .unwrap();
+        let mut uncached = StoredMerkleTree::open(MemoryStore::new(), 5, LEAF).unwrap();
+        for (leaf_index, value) in [(3, 1), (12, 2), (3, 3), (15, 4)] {
+            let value = format!("0x{:064x}", value);
+            cached.set(leaf_index, &value).unwrap();
+            uncached.set(leaf_index, &value).unwrap();
+        }
+        assert_eq!(cached.root().unwrap(), uncached.root().unwrap());
+        for leaf_index in 0..16 {
+            assert_eq!(
+                cached.proof(leaf_index).unwrap(),
+                uncached.proof(leaf_index).unwrap()
+            );
+        }
+        assert_eq!(cached.store().len(), 3);
+        assert_eq!(cached.store().inner().len(), uncached.store().len());
+
+        let disabled = CachingStore::with_capacity(MemoryStore::new(), 0);
+        let tree = StoredMerkleTree::open(disabled, 3, LEAF).unwrap();
+        tree.proof(0).unwrap();
+        assert!(tree.store().is_empty());
+        assert_eq!(tree.store().hits(), 0);
+    }
+}
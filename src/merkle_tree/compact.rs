@@ -0,0 +1,199 @@
+//! Compact Trees
+//!
+//! A partially populated tree is mostly subtrees whose leaves all hold the same value, and the
+//! nodes of such a subtree are fixed by that value and its number of levels. `CompactTree` lists
+//! the nodes of a `MerkleTree` depth first, root first, and replaces each maximal uniform
+//! subtree of more than one level with a single `CompactNode::Uniform`, so its size grows with
+//! the number of distinct regions of leaves rather than with the number of leaves.
+//!
+//! `CompactTree::expand` rebuilds the full tree, hashing only one node per level of each uniform
+//! subtree. Like a snapshot, the compact form keeps the nodes but not the append position, proof
+//! cache, undo history or observers of the tree.
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{hash_nodes, MerkleTree};
+use crate::utils::index::left_child_index;
+
+/// A node of a `CompactTree`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompactNode {
+    /// a node kept as is, followed by its children unless it is a leaf
+    Hash(String),
+    /// a subtree of the given number of levels whose leaves all hold the given value
+    Uniform(String, usize),
+}
+
+/// A `MerkleTree` with its uniform subtrees collapsed, see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompactTree {
+    compact_nodes: Vec<CompactNode>,
+    depth: usize,
+}
+
+impl CompactTree {
+    /// returns the nodes in depth-first order, root first
+    pub fn compact_nodes(&self) -> &[CompactNode] {
+        &self.compact_nodes
+    }
+
+    /// returns the depth (one indexed) of the tree
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Rebuilds the tree the compact form was made from.
+    ///
+    /// # Returns
+    ///
+    /// * The MerkleTree, with the same nodes as the tree passed to `to_compact_form`.
+    pub fn expand(&self) -> MerkleTree {
+        let hasher = Sha3_256Hasher;
+        let mut nodes = vec![String::new(); (1 << self.depth) - 1];
+        let mut compact_nodes = self.compact_nodes.iter();
+        if self.depth > 0 {
+            expand_subtree(&hasher, &mut compact_nodes, &mut nodes, 0, self.depth);
+        }
+        MerkleTree::with_nodes(nodes, hasher)
+    }
+}
+
+// fills the `levels` deep subtree at `index` from the front of `compact_nodes`
+fn expand_subtree<'a, H: MerkleHasher>(
+    hasher: &H,
+    compact_nodes: &mut impl Iterator<Item = &'a CompactNode>,
+    nodes: &mut [String],
+    index: usize,
+    levels: usize,
+) {
+    match compact_nodes.next() {
+        Some(CompactNode::Hash(node)) => {
+            nodes[index] = node.clone();
+            if levels > 1 {
+                let left = left_child_index(index).expect("subtree lies within the tree");
+                expand_subtree(hasher, compact_nodes, nodes, left, levels - 1);
+                expand_subtree(hasher, compact_nodes, nodes, left + 1, levels - 1);
+            }
+        }
+        Some(CompactNode::Uniform(leaf, _)) => {
+            // the subtree's nodes from the leaves up, one value per level
+            let mut level_values = vec![leaf.clone()];
+            for _ in 1..levels {
+                let below = &level_values[level_values.len() - 1];
+                let above = hash_nodes(hasher, below, below).expect("leaves are validated nodes");
+                level_values.push(above);
+            }
+            let mut first = index;
+            for (width, value) in (0..levels)
+                .map(|level| 1 << level)
+                .zip(level_values.iter().rev())
+            {
+                for node in &mut nodes[first..first + width] {
+                    node.clone_from(value);
+                }
+                first = 2 * first + 1;
+            }
+        }
+        None => unreachable!("compact trees are only built by to_compact_form"),
+    }
+}
+
+impl MerkleTree {
+    /// Collapses the tree's uniform subtrees, see the module documentation.
+    ///
+    /// # Returns
+    ///
+    /// * The CompactTree, which `CompactTree::expand` turns back into this tree.
+    pub fn to_compact_form(&self) -> CompactTree {
+        let nodes = self.state().0;
+        let mut compact_nodes = Vec::new();
+        if !nodes.is_empty() {
+            // the value shared by every leaf under each node, if there is one
+            let num_leaves = self.num_leaves();
+            let mut uniform: Vec<Option<&str>> = vec![None; nodes.len()];
+            for (index, leaf) in nodes.iter().enumerate().skip(num_leaves - 1) {
+                uniform[index] = Some(leaf);
+            }
+            for index in (0..num_leaves - 1).rev() {
+                let (left, right) = (uniform[2 * index + 1], uniform[2 * index + 2]);
+                uniform[index] = left.filter(|_| left == right);
+            }
+            compact_subtree(nodes, &uniform, &mut compact_nodes, 0, self.depth());
+        }
+        CompactTree {
+            compact_nodes,
+            depth: self.depth(),
+        }
+    }
+}
+
+// appends the `levels` deep subtree at `index` to `compact_nodes`
+fn compact_subtree(
+    nodes: &[String],
+    uniform: &[Option<&str>],
+    compact_nodes: &mut Vec<CompactNode>,
+    index: usize,
+    levels: usize,
+) {
+    match uniform[index] {
+        Some(leaf) if levels > 1 => {
+            compact_nodes.push(CompactNode::Uniform(leaf.to_string(), levels));
+        }
+        _ => {
+            compact_nodes.push(CompactNode::Hash(nodes[index].clone()));
+            if levels > 1 {
+                compact_subtree(nodes, uniform, compact_nodes, 2 * index + 1, levels - 1);
+                compact_subtree(nodes, uniform, compact_nodes, 2 * index + 2, levels - 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    // round trips `tree` through its compact form, returning the number of compact nodes
+    fn round_trip(tree: &MerkleTree) -> usize {
+        let compact = tree.to_compact_form();
+        assert_eq!(compact.depth(), tree.depth());
+        let expanded = compact.expand();
+        assert_eq!(expanded.state().0, tree.state().0);
+        assert!(expanded.validate().is_ok());
+        compact.compact_nodes().len()
+    }
+
+    #[test]
+    fn test_compact_form_round_trip() {
+        let mut tree = MerkleTree::new(8, ZERO_LEAF).unwrap();
+        assert_eq!(
+            tree.to_compact_form().compact_nodes(),
+            [CompactNode::Uniform(ZERO_LEAF.to_string(), 8)]
+        );
+        assert_eq!(round_trip(&tree), 1);
+
+        // a few populated leaves, and a run of equal leaves filling a whole subtree
+        let value = format!("0x{:064x}", 7);
+        for leaf_index in [0, 5, 127] {
+            tree.set(leaf_index, &format!("0x{:064x}", leaf_index + 1))
+                .unwrap();
+        }
+        for leaf_index in 64..96 {
+            tree.set(leaf_index, &value).unwrap();
+        }
+        let compact = tree.to_compact_form();
+        assert!(compact
+            .compact_nodes()
+            .contains(&CompactNode::Uniform(value.clone(), 6)));
+        assert!(round_trip(&tree) < tree.state().0.len() / 4);
+
+        for depth in 0..3 {
+            round_trip(&MerkleTree::new(depth, ZERO_LEAF).unwrap());
+        }
+        let mut single = MerkleTree::new(1, ZERO_LEAF).unwrap();
+        single.set(0, &value).unwrap();
+        assert_eq!(
+            single.to_compact_form().compact_nodes(),
+            [CompactNode::Hash(value)]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Tree Configuration
+//!
+//! `TreeConfig` carries the options of `MerkleTree::with_config` and
+//! `StoredMerkleTree::open_with_config`. Its depth limit defaults to `MAX_DEPTH`; a store backed
+//! tree that only holds the nodes written to it can be configured to go deeper, and a memory
+//! constrained consumer can forbid trees it cannot hold.
+//!
+//! By default the leaf values given to `MerkleTree::from_leaves_with_config` are nodes and are
+//! stored as they are. With a leaf size set, they are values of that many bytes, e.g. 64 byte
+//! records, each hashed with the tree's hasher into its bottom node. Writes after construction,
+//! and the constructors taking an initial leaf, still take nodes.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::MAX_DEPTH;
+use crate::utils::index::node_count;
+
+/// Options for constructing a tree, see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreeConfig {
+    max_depth: usize,
+    leaf_bytes: Option<usize>,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            max_depth: MAX_DEPTH,
+            leaf_bytes: None,
+        }
+    }
+}
+
+impl TreeConfig {
+    /// Sets the largest depth (one indexed) a tree can be constructed or grown to.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_depth` - The depth limit.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the updated config, or `MerkleError::MaxDepthExceeded` if a tree of
+    ///   `max_depth` has more nodes than a usize can index on this platform.
+    pub fn max_depth(mut self, max_depth: usize) -> Result<Self, MerkleError> {
+        if node_count(max_depth).is_err() {
+            return Err(MerkleError::MaxDepthExceeded {
+                requested: max_depth,
+                permitted: usize::BITS as usize - 1,
+            });
+        }
+        self.max_depth = max_depth;
+        Ok(self)
+    }
+
+    /// returns the largest depth (one indexed) a tree can be constructed or grown to
+    pub fn depth_limit(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Sets the byte length of leaf values, which are then hashed into the bottom nodes, see the
+    /// module documentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_bytes` - The length of every leaf value in bytes.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the updated config, or `MerkleError::InvalidBytes` for a length of 0
+    ///   or one whose hex digits cannot be counted in a usize.
+    pub fn leaf_bytes(mut self, leaf_bytes: usize) -> Result<Self, MerkleError> {
+        if leaf_bytes == 0 || leaf_bytes.checked_mul(2).is_none() {
+            return Err(MerkleError::InvalidBytes);
+        }
+        self.leaf_bytes = Some(leaf_bytes);
+        Ok(self)
+    }
+
+    /// returns the byte length of leaf values, or `None` if leaf values are nodes
+    pub fn leaf_size(&self) -> Option<usize> {
+        self.leaf_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_depth_fits_platform() {
+        assert_eq!(TreeConfig::default().depth_limit(), MAX_DEPTH);
+        // `2^depth` must fit in a usize
+        let widest = usize::BITS as usize - 1;
+        assert_eq!(
+            TreeConfig::default()
+                .max_depth(widest)
+                .unwrap()
+                .depth_limit(),
+            widest
+        );
+        assert_eq!(
+            TreeConfig::default().max_depth(widest + 1),
+            Err(MerkleError::MaxDepthExceeded {
+                requested: widest + 1,
+                permitted: widest
+            })
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Tree cursors
+//!
+//! `Cursor` points at one node of a borrowed `MerkleTree` and steps to its parent, children or
+//! sibling, for exploring a tree node by node instead of through array indices.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use crate::utils::index::{left_child_index, parent_index};
+
+/// Position of a node in a borrowed `MerkleTree`.
+pub struct Cursor<'a, L = String, H = Sha3_256Hasher> {
+    tree: &'a MerkleTree<L, H>,
+    index: usize,
+}
+
+impl<L, H> Clone for Cursor<'_, L, H> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<L, H> Copy for Cursor<'_, L, H> {}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// returns a cursor at the root, or `None` if the tree has no nodes
+    pub fn root_cursor(&self) -> Option<Cursor<'_, L, H>> {
+        Cursor::at(self, 0)
+    }
+
+    /// Returns a cursor at a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to start at.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the cursor, or `MerkleError::InvalidIndex`.
+    pub fn leaf_cursor(&self, leaf_index: usize) -> Result<Cursor<'_, L, H>, MerkleError> {
+        if leaf_index >= self.num_leaves() {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Cursor::at(self, self.num_leaves() - 1 + leaf_index).ok_or(MerkleError::InvalidIndex)
+    }
+}
+
+impl<'a, L, H: MerkleHasher> Cursor<'a, L, H> {
+    // returns a cursor at `index` if the tree has a node there
+    fn at(tree: &'a MerkleTree<L, H>, index: usize) -> Option<Self> {
+        (index < tree.state().0.len()).then_some(Cursor { tree, index })
+    }
+
+    /// returns the array index of the node in the breadth-first layout
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// returns the value of the node
+    pub fn value(&self) -> &'a str {
+        &self.tree.state().0[self.index]
+    }
+
+    /// returns true if the node is a leaf
+    pub fn is_leaf(&self) -> bool {
+        self.left_child().is_none()
+    }
+
+    /// returns a cursor at the node's parent, or `None` at the root
+    pub fn parent(&self) -> Option<Self> {
+        Cursor::at(self.tree, parent_index(self.index)?)
+    }
+
+    /// returns a cursor at the node's left child, or `None` at a leaf
+    pub fn left_child(&self) -> Option<Self> {
+        Cursor::at(self.tree, left_child_index(self.index).ok()?)
+    }
+
+    /// returns a cursor at the node's right child, or `None` at a leaf
+    pub fn right_child(&self) -> Option<Self> {
+        Cursor::at(self.tree, left_child_index(self.index).ok()? + 1)
+    }
+
+    /// returns a cursor at the other child of the node's parent, or `None` at the root
+    pub fn sibling(&self) -> Option<Self> {
+        match self.index {
+            0 => None,
+            index if index % 2 == 1 => Cursor::at(self.tree, index + 1),
+            index => Cursor::at(self.tree, index - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::{hash_nodes, ZERO_LEAF};
+
+    #[test]
+    fn test_navigate_up_and_down() {
+        let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        for i in 0..tree.num_leaves() {
+            tree.set(i, &format!("0x{:064x}", i)).unwrap();
+        }
+
+        // climb from leaf 1 to the root, checking each parent hashes its children
+        let hasher: Sha3_256Hasher = Sha3_256Hasher;
+        let mut cursor = tree.leaf_cursor(1).unwrap();
+        assert!(cursor.is_leaf());
+        assert_eq!(cursor.value(), tree.get_leaf(1).unwrap());
+        assert_eq!(cursor.sibling().unwrap().value(), tree.get_leaf(0).unwrap());
+        while let Some(parent) = cursor.parent() {
+            let (left, right) = (parent.left_child().unwrap(), parent.right_child().unwrap());
+            assert!(cursor.index() == left.index() || cursor.index() == right.index());
+            let hash = hash_nodes(&hasher, left.value(), right.value()).unwrap();
+            assert_eq!(parent.value(), hash);
+            cursor = parent;
+        }
+        assert_eq!(cursor.index(), 0);
+        assert_eq!(cursor.value(), tree.root());
+        assert!(cursor.sibling().is_none());
+
+        // and down the right edge to the last leaf
+        while let Some(right) = cursor.right_child() {
+            assert_eq!(
+                right.sibling().unwrap().index(),
+                cursor.left_child().unwrap().index()
+            );
+            cursor = right;
+        }
+        assert!(cursor.is_leaf());
+        assert_eq!(cursor.value(), tree.get_leaf(7).unwrap());
+        assert_eq!(cursor.index(), tree.leaf_cursor(7).unwrap().index());
+
+        assert!(tree.leaf_cursor(8).is_err());
+        assert!(MerkleTree::empty().root_cursor().is_none());
+    }
+}
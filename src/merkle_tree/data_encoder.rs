@@ -0,0 +1,187 @@
+//! Raw Data Leaves
+//!
+//! Leaves of a `MerkleTree` are node sized hashes. `DataEncoder` maps raw leaf data of any length
+//! onto such a node, so callers can commit to data without hashing it themselves. Unlike
+//! `LeafEncoder`, which a record type implements for itself, a `DataEncoder` is chosen per tree
+//! operation and works on plain bytes.
+//!
+//! Every `MerkleHasher` is a `DataEncoder` that hashes the data, which is what the `*_data`
+//! methods use unless given another encoder.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{MerkleTree, ProofStep, DIGEST_BYTES};
+
+/// Maps raw leaf data onto a leaf node.
+pub trait DataEncoder {
+    /// returns the node for `data`, which must be as wide as the tree's `MerkleHasher::DIGEST_BYTES`
+    fn encode(&self, data: &[u8]) -> Vec<u8>;
+}
+
+/// Hashers encode data by hashing it.
+impl<H: MerkleHasher> DataEncoder for H {
+    fn encode(&self, data: &[u8]) -> Vec<u8> {
+        self.digest(data)
+    }
+}
+
+// returns the `0x` prefixed hex leaf `encoder` maps `data` to
+fn data_leaf<E: DataEncoder>(encoder: &E, data: &[u8]) -> String {
+    format!("0x{}", hex::encode(encoder.encode(data)))
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Sets the leaf at `leaf_index` to the hash of `data` and re-calculates the merkle root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `data` - The raw leaf data.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn set_data(&mut self, leaf_index: usize, data: &[u8]) -> Result<(), MerkleError> {
+        let leaf = data_leaf(self.hasher(), data);
+        self.set(leaf_index, &leaf)
+    }
+
+    /// Sets the leaf at `leaf_index` to `encoder`'s node for `data` and re-calculates the merkle
+    /// root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `data` - The raw leaf data.
+    /// * `encoder` - maps `data` onto the leaf node.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn set_data_with<E: DataEncoder>(
+        &mut self,
+        leaf_index: usize,
+        data: &[u8],
+        encoder: &E,
+    ) -> Result<(), MerkleError> {
+        self.set(leaf_index, &data_leaf(encoder, data))
+    }
+}
+
+impl MerkleTree {
+    /// Constructs a merkle tree whose leaves are the SHA3-256 hashes of `data`, in order, padding
+    /// with `ZERO_LEAF` up to the next power of two.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The raw leaf data.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_data(data: &[&[u8]]) -> Result<Self, MerkleError> {
+        MerkleTree::from_data_with(data, &Sha3_256Hasher::<DIGEST_BYTES>)
+    }
+
+    /// Constructs a merkle tree whose leaves are `encoder`'s nodes for `data`, in order, padding
+    /// with `ZERO_LEAF` up to the next power of two.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The raw leaf data.
+    /// * `encoder` - maps each item of `data` onto its leaf node.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_data_with<E: DataEncoder>(
+        data: &[&[u8]],
+        encoder: &E,
+    ) -> Result<Self, MerkleError> {
+        let leaves: Vec<String> = data.iter().map(|item| data_leaf(encoder, item)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        MerkleTree::from_leaves(&leaves)
+    }
+
+    /// Given a `proof` and the raw data of the proven leaf, calculates and returns the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - `Vec<ProofStep>` containing the proof steps to be verified.
+    /// * `data` - The raw data of the leaf you want to verify proof for.
+    /// * `encoder` - the encoder the tree's leaves were built with.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root of the tree or Error.
+    pub fn verify_data_with<E: DataEncoder>(
+        proof: &[ProofStep],
+        data: &[u8],
+        encoder: &E,
+    ) -> Result<String, MerkleError> {
+        MerkleTree::verify(proof, data_leaf(encoder, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stores short records verbatim, zero padded on the right to 32 bytes
+    struct PadEncoder;
+
+    impl DataEncoder for PadEncoder {
+        fn encode(&self, data: &[u8]) -> Vec<u8> {
+            let mut node = data[..data.len().min(32)].to_vec();
+            node.resize(32, 0);
+            node
+        }
+    }
+
+    #[test]
+    fn test_default_encoder_hashes() {
+        let data: [&[u8]; 2] = [b"alice", b"bob"];
+        let tree = MerkleTree::from_data(&data).unwrap();
+        let hasher = Sha3_256Hasher::<32>;
+        assert_eq!(
+            tree.get_leaf(0).unwrap(),
+            format!("0x{}", hex::encode(hasher.digest(b"alice")))
+        );
+
+        let mut updated = MerkleTree::from_data(&data).unwrap();
+        updated.set_data(1, b"carol").unwrap();
+        let proof = updated.proof(1).unwrap();
+        assert_eq!(
+            MerkleTree::verify_data_with(&proof, b"carol", &hasher).unwrap(),
+            updated.root()
+        );
+        assert_ne!(updated.root(), tree.root());
+    }
+
+    #[test]
+    fn test_custom_encoder() {
+        let long = [7u8; 40];
+        let data: [&[u8]; 3] = [b"\x01\x02", &long, b""];
+        let tree = MerkleTree::from_data_with(&data, &PadEncoder).unwrap();
+
+        let mut padded = [0u8; 32];
+        padded[..2].copy_from_slice(&[1, 2]);
+        let leaves = [
+            format!("0x{}", hex::encode(padded)),
+            format!("0x{}", hex::encode([7u8; 32])),
+            format!("0x{}", hex::encode([0u8; 32])),
+        ];
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        assert_eq!(
+            tree.root(),
+            MerkleTree::from_leaves(&leaves).unwrap().root()
+        );
+
+        let mut tree = tree;
+        tree.set_data_with(2, b"\xff", &PadEncoder).unwrap();
+        let proof = tree.proof(2).unwrap();
+        assert_eq!(
+            MerkleTree::verify_data_with(&proof, b"\xff", &PadEncoder).unwrap(),
+            tree.root()
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Dirty Leaf Tracking
+//!
+//! Once `MerkleTree::enable_dirty_tracking` is called, every leaf written by `set`, `set_batch`,
+//! `append` and the methods built on them is recorded, so a persistence layer can write back only
+//! the leaves that changed since its last checkpoint and then call `clear_dirty`. Tracking is off
+//! by default and costs one bit per leaf.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Bitset of written leaf indices.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DirtyLeaves {
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl DirtyLeaves {
+    // an empty bitset with room for `leaf_count` leaves
+    pub(crate) fn with_leaves(leaf_count: usize) -> Self {
+        DirtyLeaves {
+            words: vec![0; leaf_count.div_ceil(WORD_BITS)],
+            count: 0,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, leaf_index: usize) {
+        let (word, bit) = (leaf_index / WORD_BITS, leaf_index % WORD_BITS);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        if self.words[word] & (1 << bit) == 0 {
+            self.words[word] |= 1 << bit;
+            self.count += 1;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(word, bits)| {
+            (0..WORD_BITS)
+                .filter(move |bit| bits & (1 << bit) != 0)
+                .map(move |bit| word * WORD_BITS + bit)
+        })
+    }
+
+    fn clear(&mut self) {
+        self.words.fill(0);
+        self.count = 0;
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Starts recording written leaves, see the module documentation. Does nothing if tracking
+    /// is already enabled.
+    pub fn enable_dirty_tracking(&mut self) {
+        let leaf_count = self.num_leaves();
+        self.dirty_leaves_mut()
+            .get_or_insert_with(|| DirtyLeaves::with_leaves(leaf_count));
+    }
+
+    /// Iterates over the leaves written since tracking was enabled or `clear_dirty` was last
+    /// called, in ascending order and without duplicates. Empty while tracking is disabled.
+    pub fn dirty_leaves(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_leaves_ref()
+            .into_iter()
+            .flat_map(DirtyLeaves::iter)
+    }
+
+    /// returns the number of distinct leaves `dirty_leaves` yields
+    pub fn dirty_count(&self) -> usize {
+        self.dirty_leaves_ref().map_or(0, |dirty| dirty.count)
+    }
+
+    /// Forgets every recorded leaf, typically after a successful checkpoint. Tracking stays
+    /// enabled.
+    pub fn clear_dirty(&mut self) {
+        if let Some(dirty) = self.dirty_leaves_mut() {
+            dirty.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_dirty_leaves_accumulate() {
+        let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        tree.enable_dirty_tracking();
+        tree.set(5, LEAF).unwrap();
+        tree.set_batch(&[(2, LEAF), (5, ZERO_LEAF), (7, LEAF)])
+            .unwrap();
+        tree.append(LEAF).unwrap();
+        tree.set(5, LEAF).unwrap();
+
+        assert_eq!(tree.dirty_leaves().collect::<Vec<_>>(), vec![0, 2, 5, 7]);
+        assert_eq!(tree.dirty_count(), 4);
+
+        tree.clear_dirty();
+        assert_eq!(tree.dirty_count(), 0);
+        assert_eq!(tree.dirty_leaves().count(), 0);
+        tree.set(1, LEAF).unwrap();
+        assert_eq!(tree.dirty_leaves().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_dirty_tracking_grows_with_tree() {
+        let mut tree = MerkleTree::empty();
+        tree.enable_dirty_tracking();
+        for _ in 0..70 {
+            tree.append_growing(LEAF).unwrap();
+        }
+        assert_eq!(tree.dirty_count(), 70);
+        assert_eq!(tree.dirty_leaves().last(), Some(69));
+    }
+
+    #[test]
+    fn test_dirty_tracking_disabled() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        tree.set(1, LEAF).unwrap();
+        tree.clear_dirty();
+        assert_eq!(tree.dirty_count(), 0);
+        assert_eq!(tree.dirty_leaves().count(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Ethereum ABI encoding for proofs.
+//!
+//! Solidity verifiers usually take a proof as `bytes32[]`. The helpers here produce
+//! exactly the bytes `abi.encode(proof)` would, so proofs can be forwarded to a contract
+//! without a separate ABI encoding crate.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+
+/// Size of a single ABI word.
+const WORD_BYTES: usize = 32;
+
+/// Decodes a `0x` prefixed hex value into an ABI word, left-padding it with zeros.
+fn abi_word(value: &str) -> Result<[u8; WORD_BYTES], MerkleError> {
+    let bytes = hex::decode(&value[2..])?;
+    if bytes.len() > WORD_BYTES {
+        return Err(MerkleError::InvalidBytes);
+    }
+    let mut word = [0u8; WORD_BYTES];
+    word[WORD_BYTES - bytes.len()..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+/// Encodes a `usize` as a big-endian ABI `uint256` word.
+fn abi_uint(value: usize) -> [u8; WORD_BYTES] {
+    let mut word = [0u8; WORD_BYTES];
+    word[WORD_BYTES - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Generates the proof for `leaf_index` and ABI-encodes it as `abi.encode(bytes32[] proof)`.
+    ///
+    /// The output is the offset word (`0x20`), the array length, then one 32 byte word per
+    /// sibling ordered from the leaf up to the root. Directions are not encoded; contracts
+    /// derive them from the leaf index.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to encode a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the ABI encoded proof or `MerkleError::InvalidIndex`.
+    pub fn encode_as_ethereum_calldata(&self, leaf_index: usize) -> Result<Vec<u8>, MerkleError> {
+        let proof = self.proof(leaf_index)?;
+
+        let mut encoded = Vec::with_capacity((proof.len() + 2) * WORD_BYTES);
+        encoded.extend_from_slice(&abi_uint(WORD_BYTES));
+        encoded.extend_from_slice(&abi_uint(proof.len()));
+        for step in proof.iter() {
+            encoded.extend_from_slice(&abi_word(step.sibling())?);
+        }
+        Ok(encoded)
+    }
+
+    /// Encodes the value of `leaf_index` as a standalone ABI `bytes32`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf to encode.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf word or `MerkleError::InvalidIndex`.
+    pub fn encode_leaf_as_bytes32(&self, leaf_index: usize) -> Result<[u8; 32], MerkleError> {
+        abi_word(self.get_leaf(leaf_index)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_encode_as_ethereum_calldata() {
+        let tree = MerkleTree::new(3, LEAF).unwrap();
+        let encoded = tree.encode_as_ethereum_calldata(1).unwrap();
+        let proof = tree.proof(1).unwrap();
+
+        assert_eq!(encoded.len(), (proof.len() + 2) * 32);
+        assert_eq!(encoded[..32], abi_uint(0x20));
+        assert_eq!(encoded[32..64], abi_uint(2));
+        for (i, step) in proof.iter().enumerate() {
+            let word = &encoded[64 + i * 32..96 + i * 32];
+            assert_eq!(format!("0x{}", hex::encode(word)), step.sibling());
+        }
+    }
+
+    #[test]
+    fn test_encode_as_ethereum_calldata_invalid_index() {
+        let tree = MerkleTree::new(3, LEAF).unwrap();
+        assert_eq!(
+            tree.encode_as_ethereum_calldata(4),
+            Err(MerkleError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn test_encode_leaf_as_bytes32() {
+        let tree = MerkleTree::new(2, LEAF).unwrap();
+        assert_eq!(tree.encode_leaf_as_bytes32(1).unwrap(), [0xab; 32]);
+        assert_eq!(
+            tree.encode_leaf_as_bytes32(2),
+            Err(MerkleError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn test_abi_word_left_pads() {
+        let word = abi_word("0xabcd").unwrap();
+        assert_eq!(word[..30], [0u8; 30]);
+        assert_eq!(word[30..], [0xab, 0xcd]);
+    }
+}
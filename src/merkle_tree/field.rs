@@ -0,0 +1,85 @@
+//! Field Elements
+//!
+//! ZK-SNARK circuits verify Merkle proofs over prime field elements rather than hex strings.
+//! `MerkleTree::root_as_field_element` reads the root as a big-endian integer and reduces it
+//! modulo the field's prime. A tree only maps into fields at least as wide as its digests, so
+//! two roots never reduce to the same element by more than one subtraction of the prime, and a
+//! hasher that does not fit the field is rejected regardless of the root's value.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use ff::PrimeField;
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Converts the root into an element of the prime field `F`.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root reduced modulo the field's prime, or
+    ///   `MerkleError::HashFunctionMismatch` if `H::DIGEST_BYTES` bytes are wider than
+    ///   `F::NUM_BITS`.
+    pub fn root_as_field_element<F: PrimeField>(&self) -> Result<F, MerkleError> {
+        if H::DIGEST_BYTES * 8 > F::NUM_BITS as usize {
+            return Err(MerkleError::HashFunctionMismatch);
+        }
+        let root = self.root();
+        let bytes = hex::decode(&root[2..])?;
+        // Horner's rule over the big-endian bytes, which leaves the integer reduced in the field
+        let radix = F::from(256);
+        Ok(bytes.iter().fold(F::ZERO, |element, byte| {
+            element * radix + F::from(*byte as u64)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::hasher::Sha3_256Hasher;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+    use num_bigint::BigUint;
+
+    // the field derive defines module level constants, so each field gets its own module
+    mod bn254 {
+        // scalar field of BN254, whose 254 bit prime is smaller than 2^256
+        #[derive(ff::PrimeField)]
+        #[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
+        #[PrimeFieldGenerator = "7"]
+        #[PrimeFieldReprEndianness = "little"]
+        pub struct Fr([u64; 4]);
+    }
+
+    mod fp64 {
+        // field of the prime 2^63 + 29, which about half of all 8 byte digests exceed
+        #[derive(ff::PrimeField)]
+        #[PrimeFieldModulus = "9223372036854775837"]
+        #[PrimeFieldGenerator = "5"]
+        #[PrimeFieldReprEndianness = "little"]
+        pub struct Fp([u64; 2]);
+    }
+
+    #[test]
+    fn test_root_as_field_element() {
+        let tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        assert_eq!(
+            tree.root_as_field_element::<bn254::Fr>().err(),
+            Some(MerkleError::HashFunctionMismatch)
+        );
+
+        let prime = BigUint::from(9223372036854775837u64);
+        let mut reduced = false;
+        for value in 0..8u64 {
+            let hasher: Sha3_256Hasher<8> = Sha3_256Hasher;
+            let mut tree = MerkleTree::with_hasher(3, &format!("0x{:016x}", 0), hasher).unwrap();
+            tree.set(1, &format!("0x{:016x}", value)).unwrap();
+            let root = BigUint::parse_bytes(&tree.root().as_bytes()[2..], 16).unwrap();
+            reduced |= root >= prime;
+            let expected = u64::try_from(root % &prime).unwrap();
+            assert_eq!(
+                tree.root_as_field_element::<fp64::Fp>().unwrap(),
+                fp64::Fp::from(expected)
+            );
+        }
+        assert!(reduced);
+    }
+}
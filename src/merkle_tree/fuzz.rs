@@ -0,0 +1,202 @@
+//! Fuzzing Support
+//!
+//! `Arbitrary` implementations for `Direction` and `ProofStep`, and `FuzzTreeOps`, a sequence of
+//! operations on a `MerkleTree` a fuzzer can generate from raw bytes. Generated nodes, such as
+//! the siblings of a `ProofStep`, are usually valid 32 byte hex and otherwise arbitrary strings,
+//! so both the hashing and the validation paths get exercised.
+//!
+//! `run_tree_ops` and `run_verify_reader` are the bodies of the cargo-fuzz targets in
+//! `fuzz/fuzz_targets`. They return normally for every input and panic only on a bug, so an
+//! input the fuzzer reports as crashing becomes a unit test by passing its bytes to the same
+//! function. `fuzz/corpus/verify_reader` is seeded with the proofs of
+//! `vectors/test_vectors.json`.
+use crate::merkle_tree::merkle_tree::{Direction, MerkleTree, ProofStep, DIGEST_BYTES};
+use arbitrary::{Arbitrary, Unstructured};
+
+// `FuzzTreeOps::New` trees are at most this deep, keeping every tree small enough to rebuild
+// on each operation
+const MAX_FUZZ_DEPTH: usize = 10;
+
+// a valid 32 byte hex node three times in four, otherwise any string
+fn arbitrary_node(u: &mut Unstructured) -> arbitrary::Result<String> {
+    if u.ratio(3u8, 4)? {
+        let bytes: [u8; DIGEST_BYTES] = u.arbitrary()?;
+        Ok(format!("0x{}", hex::encode(bytes)))
+    } else {
+        u.arbitrary()
+    }
+}
+
+impl<'a> Arbitrary<'a> for Direction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.arbitrary()? {
+            false => Direction::Left,
+            true => Direction::Right,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProofStep {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProofStep::new(u.arbitrary()?, arbitrary_node(u)?))
+    }
+}
+
+/// One operation of a fuzzed sequence, see `run_tree_ops`. Leaf indices are taken modulo one
+/// more than the number of leaves of the current tree, so they are mostly in range and
+/// sometimes one past the end.
+#[derive(Arbitrary, Clone, Debug)]
+pub enum FuzzTreeOps {
+    /// replaces the tree with `MerkleTree::new`, of depth `depth` modulo 11
+    New {
+        depth: u8,
+        #[arbitrary(with = arbitrary_node)]
+        initial_leaf: String,
+    },
+    /// `MerkleTree::set`
+    Set {
+        leaf_index: usize,
+        #[arbitrary(with = arbitrary_node)]
+        value: String,
+    },
+    /// `MerkleTree::proof`, verified against the root
+    Proof { leaf_index: usize },
+    /// `MerkleTree::verify` of a proof unrelated to the tree
+    Verify {
+        proof: Vec<ProofStep>,
+        #[arbitrary(with = arbitrary_node)]
+        leaf: String,
+    },
+}
+
+/// Applies `ops` in order to a tree that starts out empty, checking that every write that
+/// succeeds is stored and every proof the tree produces verifies against its root. Errors are
+/// expected and ignored.
+///
+/// # Arguments
+///
+/// * `ops` - The operations to apply.
+///
+/// # Panics
+///
+/// * If the tree breaks one of the checks above, or panics itself.
+pub fn apply_ops(ops: &[FuzzTreeOps]) {
+    let mut tree = MerkleTree::empty();
+    for op in ops {
+        let leaf_in_range =
+            |leaf_index: usize, tree: &MerkleTree| leaf_index % (tree.num_leaves() + 1);
+        match op {
+            FuzzTreeOps::New {
+                depth,
+                initial_leaf,
+            } => {
+                let depth = usize::from(*depth) % (MAX_FUZZ_DEPTH + 1);
+                if let Ok(new) = MerkleTree::new(depth, initial_leaf) {
+                    tree = new;
+                }
+            }
+            FuzzTreeOps::Set { leaf_index, value } => {
+                let leaf_index = leaf_in_range(*leaf_index, &tree);
+                if tree.set(leaf_index, value).is_ok() {
+                    assert_eq!(tree.get_leaf(leaf_index), Ok(value.trim()));
+                }
+            }
+            FuzzTreeOps::Proof { leaf_index } => {
+                let leaf_index = leaf_in_range(*leaf_index, &tree);
+                if let Ok(proof) = tree.proof(leaf_index) {
+                    let leaf = tree.get_leaf(leaf_index).unwrap().to_string();
+                    assert_eq!(MerkleTree::verify(&proof, leaf), Ok(tree.root()));
+                }
+            }
+            FuzzTreeOps::Verify { proof, leaf } => {
+                let _ = MerkleTree::verify(proof, leaf.clone());
+            }
+        }
+    }
+}
+
+/// The `tree_ops` fuzz target: decodes `data` into a sequence of `FuzzTreeOps` and applies it
+/// with `apply_ops`.
+pub fn run_tree_ops(data: &[u8]) {
+    if let Ok(ops) = Vec::<FuzzTreeOps>::arbitrary_take_rest(Unstructured::new(data)) {
+        apply_ops(&ops);
+    }
+}
+
+/// The `verify_reader` fuzz target: reads a 32 byte leaf from the front of `data` and verifies
+/// the rest as raw proof steps with `MerkleTree::verify_from_reader`, checking it agrees with
+/// decoding the steps through `MerkleTree::decode_proof_from_hex_string` and `MerkleTree::verify`.
+///
+/// # Panics
+///
+/// * If the two paths reach different roots.
+pub fn run_verify_reader(data: &[u8]) {
+    if data.len() < DIGEST_BYTES {
+        return;
+    }
+    let (leaf, steps) = data.split_at(DIGEST_BYTES);
+    let leaf = format!("0x{}", hex::encode(leaf));
+    let streamed = MerkleTree::verify_from_reader(steps, leaf.clone());
+    if let Ok(proof) =
+        MerkleTree::decode_proof_from_hex_string(&format!("0x{}", hex::encode(steps)))
+    {
+        assert_eq!(streamed, MerkleTree::verify(&proof, leaf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    // the roots of the vectors in `vectors/test_vectors.json`
+    const VECTOR_ROOTS: [&str; 2] = [
+        "0xa2190d2490607d6f3f1609f49ca7c75d74780d7dcf03bb7ead3053da854a4729",
+        "0xe631525aac9bbacab880ea6e4fa5dcd23b70a62b65d829ee305fff91ed578457",
+    ];
+
+    #[test]
+    fn test_corpus_seeds_verify() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/verify_reader");
+        let mut seeds = 0;
+        for entry in fs::read_dir(corpus).unwrap() {
+            let seed = fs::read(entry.unwrap().path()).unwrap();
+            run_verify_reader(&seed);
+            let leaf = format!("0x{}", hex::encode(&seed[..DIGEST_BYTES]));
+            let root = MerkleTree::verify_from_reader(&seed[DIGEST_BYTES..], leaf).unwrap();
+            assert!(VECTOR_ROOTS.contains(&root.as_str()));
+            seeds += 1;
+        }
+        assert_eq!(seeds, 6);
+    }
+
+    #[test]
+    fn test_tree_ops_handle_any_input() {
+        for data in [&[][..], &[0xff; 64], &[0; 256], b"0x1234 not a tree"] {
+            run_tree_ops(data);
+            run_verify_reader(data);
+        }
+        let leaf = format!("0x{:064x}", 5);
+        apply_ops(&[
+            FuzzTreeOps::New {
+                depth: 4,
+                initial_leaf: leaf.clone(),
+            },
+            FuzzTreeOps::Set {
+                leaf_index: 11,
+                value: format!(" {} ", leaf.to_uppercase().replacen('X', "x", 1)),
+            },
+            FuzzTreeOps::Set {
+                leaf_index: 2,
+                value: "0x12".to_string(),
+            },
+            FuzzTreeOps::Proof { leaf_index: 3 },
+            FuzzTreeOps::Proof { leaf_index: 8 },
+            FuzzTreeOps::Verify {
+                proof: vec![ProofStep::new(Direction::Right, "0xzz".to_string())],
+                leaf,
+            },
+        ]);
+    }
+}
@@ -0,0 +1,405 @@
+//! Node Hashing
+//!
+//! `MerkleHasher` abstracts the hash function a `MerkleTree` combines its nodes with, including
+//! the width of every node. Leaves, siblings and roots of a tree are all `DIGEST_BYTES` wide.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use blake2::digest::consts::U32;
+use blake2::digest::typenum::Unsigned;
+use blake2::digest::{FixedOutput, Mac, OutputSizeUser, Update};
+use blake2::{Blake2b, Blake2bMac};
+use hex_literal::hex;
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Once;
+
+/// Longest key BLAKE2b accepts, in bytes
+pub const BLAKE2B_MAX_KEY_BYTES: usize = 64;
+
+/// SHA3-256 of `"abc"`, the FIPS 202 test vector checked by `debug_self_test`
+pub const SHA3_256_ABC: [u8; 32] =
+    hex!("3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+
+static SELF_TEST: Once = Once::new();
+
+// In debug builds, checks once per process that SHA3-256 still produces the known digest, so a
+// broken dependency or feature misconfiguration fails loudly instead of silently changing roots.
+pub(crate) fn debug_self_test() {
+    if cfg!(debug_assertions) {
+        SELF_TEST.call_once(|| {
+            assert_eq!(
+                Sha3_256::digest(b"abc")[..],
+                SHA3_256_ABC,
+                "SHA3-256 self-test failed, the hash implementation is misconfigured"
+            );
+        });
+    }
+}
+
+/// Hash function used to combine child nodes into their parent.
+pub trait MerkleHasher {
+    /// width in bytes of every node of a tree using this hasher
+    const DIGEST_BYTES: usize;
+
+    /// Hashes `data` into a `DIGEST_BYTES` wide digest. Hash functions with a wider output must
+    /// truncate it to the first `DIGEST_BYTES` bytes.
+    fn digest(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Name of the hash function, bound into `MerkleTree::commitment`. Must be stable across
+    /// releases and distinct from the names of other hash functions.
+    fn algorithm_id(&self) -> &str;
+
+    /// Hashes the concatenation `left || right` of two child nodes into their parent.
+    fn hash_pair(&self, left: &[u8], right: &[u8]) -> Vec<u8> {
+        let mut concatenated = Vec::with_capacity(left.len() + right.len());
+        concatenated.extend_from_slice(left);
+        concatenated.extend_from_slice(right);
+        self.digest(&concatenated)
+    }
+}
+
+/// SHA3-256 truncated to its first `N` bytes. The default `N = 32` is plain SHA3-256, which is
+/// the hasher `MerkleTree` uses unless told otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha3_256Hasher<const N: usize = 32>;
+
+impl<const N: usize> MerkleHasher for Sha3_256Hasher<N> {
+    const DIGEST_BYTES: usize = {
+        assert!(N > 0 && N <= 32, "SHA3-256 digests are at most 32 bytes");
+        N
+    };
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        Sha3_256::digest(data)[..Self::DIGEST_BYTES].to_vec()
+    }
+
+    // truncations share the name, the commitment binds the width through the root's length
+    fn algorithm_id(&self) -> &str {
+        "sha3-256"
+    }
+}
+
+/// SHA-256 (SHA2), as used by certificate transparency logs and most traditional merkle trees.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    const DIGEST_BYTES: usize = 32;
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        Sha256::digest(data).to_vec()
+    }
+
+    fn algorithm_id(&self) -> &str {
+        "sha256"
+    }
+}
+
+/// SHA-256 applied twice, `SHA256(SHA256(left || right))`, as used by Bitcoin's transaction
+/// merkle trees. Bitcoin displays txids and merkle roots byte-reversed, so leaves taken from a
+/// block explorer must be reversed before use, and so must the resulting root. Bitcoin also
+/// pads odd levels by duplicating the last node rather than with zero leaves, so roots only match
+/// for blocks with a power of two transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DoubleSha256Hasher;
+
+impl MerkleHasher for DoubleSha256Hasher {
+    const DIGEST_BYTES: usize = 32;
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        Sha256::digest(Sha256::digest(data)).to_vec()
+    }
+
+    fn algorithm_id(&self) -> &str {
+        "double-sha256"
+    }
+}
+
+/// BLAKE2b with a 32 byte output and an empty salt and personalization. A hasher built with
+/// `with_key` runs BLAKE2b in keyed mode, so trees committing under different keys can't be
+/// confused for one another.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Blake2bHasher {
+    // empty for plain, unkeyed BLAKE2b
+    key: Vec<u8>,
+}
+
+impl Blake2bHasher {
+    /// Creates a hasher running BLAKE2b keyed with `key`. An empty key is plain BLAKE2b.
+    ///
+    /// # Panics
+    ///
+    /// * If `key` is longer than `BLAKE2B_MAX_KEY_BYTES`.
+    pub fn with_key(key: &[u8]) -> Self {
+        assert!(
+            key.len() <= BLAKE2B_MAX_KEY_BYTES,
+            "BLAKE2b keys are at most 64 bytes"
+        );
+        Blake2bHasher { key: key.to_vec() }
+    }
+}
+
+// keeps the key out of logs
+impl fmt::Debug for Blake2bHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blake2bHasher")
+            .field("keyed", &!self.key.is_empty())
+            .finish()
+    }
+}
+
+impl MerkleHasher for Blake2bHasher {
+    const DIGEST_BYTES: usize = 32;
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        if self.key.is_empty() {
+            return Blake2b::<U32>::digest(data).to_vec();
+        }
+        let mut mac = Blake2bMac::<U32>::new_with_salt_and_personal(&self.key, &[], &[])
+            .expect("key length is checked by with_key");
+        Mac::update(&mut mac, data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn algorithm_id(&self) -> &str {
+        if self.key.is_empty() {
+            "blake2b-256"
+        } else {
+            "blake2b-256-keyed"
+        }
+    }
+}
+
+/// Hashes with a fresh state from a factory for every node, so each hash starts from a keyed,
+/// salted or personalized state configured once by the caller. The factory must return
+/// identically seeded states on every call, otherwise nodes hashed at different times disagree
+/// and proofs stop verifying.
+pub struct FactoryHasher<F, D> {
+    algorithm_id: &'static str,
+    factory: F,
+    state: PhantomData<fn() -> D>,
+}
+
+impl<F: Fn() -> D, D> FactoryHasher<F, D> {
+    /// Returns a hasher starting every hash from a state returned by `factory`. `algorithm_id`
+    /// names both the hash function and how `factory` seeds it, see
+    /// `MerkleHasher::algorithm_id`.
+    pub fn new(algorithm_id: &'static str, factory: F) -> Self {
+        FactoryHasher {
+            algorithm_id,
+            factory,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<F: Clone, D> Clone for FactoryHasher<F, D> {
+    fn clone(&self) -> Self {
+        FactoryHasher {
+            algorithm_id: self.algorithm_id,
+            factory: self.factory.clone(),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<F: Fn() -> D, D: Update + FixedOutput> MerkleHasher for FactoryHasher<F, D> {
+    const DIGEST_BYTES: usize = <D as OutputSizeUser>::OutputSize::USIZE;
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        let mut state = (self.factory)();
+        Update::update(&mut state, data);
+        state.finalize_fixed().to_vec()
+    }
+
+    fn algorithm_id(&self) -> &str {
+        self.algorithm_id
+    }
+}
+
+impl<F: Fn() -> D, D: Update + FixedOutput> MerkleTree<String, FactoryHasher<F, D>> {
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree hashing every
+    /// node with a fresh state from `hasher_factory`, see `FactoryHasher`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. Must be as wide as the factory's digests, hex encoded with a `0x` prefix.
+    /// * `algorithm_id` - stable name of the hash function and its seeding, bound into
+    ///   `commitment`.
+    /// * `hasher_factory` - returns an identically seeded hash state on every call.
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree
+    pub fn new_with_hasher(
+        depth: usize,
+        initial_leaf: &str,
+        algorithm_id: &'static str,
+        hasher_factory: F,
+    ) -> Result<Self, MerkleError> {
+        MerkleTree::with_hasher(
+            depth,
+            initial_leaf,
+            FactoryHasher::new(algorithm_id, hasher_factory),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::MerkleTree;
+
+    #[test]
+    fn test_sha3_256_hasher() {
+        assert_eq!(
+            hex::encode(Sha3_256Hasher::<32>.digest(b"")),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            hex::encode(Sha3_256Hasher::<20>.digest(b"")),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4d"
+        );
+    }
+
+    #[test]
+    fn test_known_digest() {
+        assert_eq!(Sha3_256Hasher::<32>.digest(b"abc"), SHA3_256_ABC);
+        debug_self_test();
+    }
+
+    #[test]
+    fn test_hash_pair_concatenates() {
+        let hasher = Sha3_256Hasher::<32>;
+        assert_eq!(hasher.hash_pair(b"ab", b"cd"), hasher.digest(b"abcd"));
+    }
+
+    #[test]
+    fn test_sha256_hasher() {
+        assert_eq!(
+            hex::encode(Sha256Hasher.digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            DoubleSha256Hasher.digest(b"abc"),
+            Sha256Hasher.digest(&Sha256Hasher.digest(b"abc"))
+        );
+    }
+
+    // reverses a txid or merkle root between Bitcoin's display order and its internal order
+    fn reverse_hex(value: &str) -> String {
+        let mut bytes = hex::decode(value.trim_start_matches("0x")).unwrap();
+        bytes.reverse();
+        format!("0x{}", hex::encode(bytes))
+    }
+
+    fn bitcoin_root(txids: &[&str]) -> String {
+        let leaves: Vec<String> = txids.iter().map(|txid| reverse_hex(txid)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let tree = MerkleTree::from_leaves_with_hasher(&leaves, DoubleSha256Hasher).unwrap();
+        reverse_hex(&tree.root())[2..].to_string()
+    }
+
+    #[test]
+    fn test_bitcoin_block_merkle_roots() {
+        // block 170, the first block with a transaction besides the coinbase
+        assert_eq!(
+            bitcoin_root(&[
+                "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082",
+                "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+            ]),
+            "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff"
+        );
+        // block 100000
+        assert_eq!(
+            bitcoin_root(&[
+                "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+                "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+                "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+                "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+            ]),
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766"
+        );
+    }
+
+    #[test]
+    fn test_blake2b_known_answers() {
+        // BLAKE2b-256 digests from the reference implementation
+        let hasher = Blake2bHasher::default();
+        assert_eq!(
+            hex::encode(hasher.digest(b"")),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+        assert_eq!(
+            hex::encode(hasher.digest(b"abc")),
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+        );
+        assert_eq!(Blake2bHasher::with_key(&[]), hasher);
+    }
+
+    #[test]
+    fn test_blake2b_keyed_known_answers() {
+        // the reference KAT key 0x00..0x3f, with a 32 byte output
+        let key: Vec<u8> = (0..64).collect();
+        let hasher = Blake2bHasher::with_key(&key);
+        assert_eq!(
+            hex::encode(hasher.digest(b"")),
+            "2fa9fbd9be36437de204e139e97d402bce68c828f43391608c891b5faed8a98a"
+        );
+        assert_eq!(
+            hex::encode(hasher.digest(&[0, 1, 2])),
+            "3e57c5ab79418defd6e252719a380096d9abf1901db38e0be7d404eb7206c0dc"
+        );
+        assert_eq!(format!("{:?}", hasher), "Blake2bHasher { keyed: true }");
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 64 bytes")]
+    fn test_blake2b_key_too_long() {
+        Blake2bHasher::with_key(&[0; 65]);
+    }
+
+    #[test]
+    fn test_factory_hasher_keyed_blake2b() {
+        let key = b"tree key";
+        let (leaf, value) = (format!("0x{:064x}", 5), format!("0x{:064x}", 9));
+        let keyed = || Blake2bMac::<U32>::new_with_salt_and_personal(key, &[], &[]).unwrap();
+        let mut factory_tree =
+            MerkleTree::new_with_hasher(4, &leaf, "blake2b-256-keyed-test", keyed).unwrap();
+        factory_tree.set(3, &value).unwrap();
+        let mut keyed_tree =
+            MerkleTree::with_hasher(4, &leaf, Blake2bHasher::with_key(key)).unwrap();
+        keyed_tree.set(3, &value).unwrap();
+        let mut unkeyed_tree = MerkleTree::with_hasher(4, &leaf, Blake2bHasher::default()).unwrap();
+        unkeyed_tree.set(3, &value).unwrap();
+
+        assert_eq!(factory_tree.root(), keyed_tree.root());
+        assert_ne!(factory_tree.root(), unkeyed_tree.root());
+        let proof = factory_tree.proof(3).unwrap();
+        assert!(factory_tree.verify_inclusion(&proof, &value).unwrap());
+
+        // the id, not the state's type, names the hasher in the commitment
+        let mut renamed =
+            MerkleTree::new_with_hasher(4, &leaf, "blake2b-256-keyed-other", keyed).unwrap();
+        renamed.set(3, &value).unwrap();
+        assert_eq!(
+            factory_tree.hasher().algorithm_id(),
+            "blake2b-256-keyed-test"
+        );
+        assert_eq!(renamed.root(), factory_tree.root());
+        assert_ne!(factory_tree.commitment(), renamed.commitment());
+
+        // the digest width comes from the state the factory returns
+        let tree = MerkleTree::new_with_hasher(2, &format!("0x{:064x}", 0), "sha256", Sha256::new)
+            .unwrap();
+        assert_eq!(
+            tree.root(),
+            MerkleTree::with_hasher(2, &format!("0x{:064x}", 0), Sha256Hasher)
+                .unwrap()
+                .root()
+        );
+    }
+}
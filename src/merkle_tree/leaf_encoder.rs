@@ -0,0 +1,182 @@
+//! Leaf Encoding
+//!
+//! `LeafEncoder` fixes how a typed record becomes a 32 byte leaf, so every service committing
+//! the same type derives the same leaves and therefore the same root.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{normalize_node, MerkleTree, ProofStep, DIGEST_BYTES};
+use sha3::{Digest, Sha3_256};
+
+/// Types that can be committed to as a leaf of a `MerkleTree`.
+pub trait LeafEncoder {
+    /// returns the 32 byte leaf committing to `self`
+    fn encode_leaf(&self) -> [u8; 32];
+}
+
+/// Fixed size values are used as the leaf verbatim.
+impl LeafEncoder for [u8; 32] {
+    fn encode_leaf(&self) -> [u8; 32] {
+        *self
+    }
+}
+
+/// Arbitrary byte strings are hashed with SHA3-256.
+impl LeafEncoder for &[u8] {
+    fn encode_leaf(&self) -> [u8; 32] {
+        Sha3_256::digest(self).into()
+    }
+}
+
+/// Integers are big-endian and left padded with zeros.
+impl LeafEncoder for u64 {
+    fn encode_leaf(&self) -> [u8; 32] {
+        let mut leaf = [0u8; 32];
+        leaf[24..].copy_from_slice(&self.to_be_bytes());
+        leaf
+    }
+}
+
+/// Integers are big-endian and left padded with zeros.
+impl LeafEncoder for u128 {
+    fn encode_leaf(&self) -> [u8; 32] {
+        let mut leaf = [0u8; 32];
+        leaf[16..].copy_from_slice(&self.to_be_bytes());
+        leaf
+    }
+}
+
+// returns the `0x` prefixed hex leaf for `record`
+fn leaf_hex<T: LeafEncoder>(record: &T) -> String {
+    format!("0x{}", hex::encode(record.encode_leaf()))
+}
+
+impl MerkleTree {
+    /// Constructs a merkle tree committing to `records`, in order, padding with `ZERO_LEAF` up to
+    /// the next power of two.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - The records to commit to.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_records<T: LeafEncoder>(records: &[T]) -> Result<Self, MerkleError> {
+        let leaves: Vec<String> = records.iter().map(leaf_hex).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        MerkleTree::from_leaves(&leaves)
+    }
+
+    /// Checks that `proof` proves the inclusion of `record` under `root`. The roots are compared
+    /// ignoring case and surrounding whitespace, so a root taken from another service matches.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps for the record's leaf.
+    /// * `record` - The record claimed to be included.
+    /// * `root` - The expected root of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing whether the proof is valid or Error.
+    pub fn verify_record<T: LeafEncoder>(
+        proof: &[ProofStep],
+        record: &T,
+        root: &str,
+    ) -> Result<bool, MerkleError> {
+        let root = normalize_node(root, DIGEST_BYTES)?;
+        Ok(MerkleTree::verify(proof, leaf_hex(record))?.eq_ignore_ascii_case(root))
+    }
+}
+
+impl<L> MerkleTree<L> {
+    /// Sets the leaf at `leaf_index` to the encoding of `record` and re-calculates the merkle root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `record` - The record to commit to.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn set_record<T: LeafEncoder>(
+        &mut self,
+        leaf_index: usize,
+        record: &T,
+    ) -> Result<(), MerkleError> {
+        self.set(leaf_index, &leaf_hex(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Account {
+        id: u64,
+        balance: u128,
+        nonce: u64,
+    }
+
+    impl LeafEncoder for Account {
+        fn encode_leaf(&self) -> [u8; 32] {
+            let mut bytes = Vec::with_capacity(32);
+            bytes.extend_from_slice(&self.id.to_be_bytes());
+            bytes.extend_from_slice(&self.balance.to_be_bytes());
+            bytes.extend_from_slice(&self.nonce.to_be_bytes());
+            bytes.as_slice().encode_leaf()
+        }
+    }
+
+    fn accounts() -> Vec<Account> {
+        (0..3)
+            .map(|id| Account {
+                id,
+                balance: 1_000 * id as u128,
+                nonce: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_default_encoders() {
+        assert_eq!([7u8; 32].encode_leaf(), [7u8; 32]);
+        let mut expected = [0u8; 32];
+        expected[31] = 1;
+        assert_eq!(1u64.encode_leaf(), expected);
+        assert_eq!(1u128.encode_leaf(), expected);
+        assert_eq!(
+            hex::encode(b"".as_slice().encode_leaf()),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+    }
+
+    #[test]
+    fn test_prove_record_inclusion() {
+        let accounts = accounts();
+        let tree = MerkleTree::from_records(&accounts).unwrap();
+        assert_eq!(tree.num_leaves(), 4);
+
+        let proof = tree.proof(2).unwrap();
+        assert!(MerkleTree::verify_record(&proof, &accounts[2], &tree.root()).unwrap());
+        let shouted = format!(" 0x{} ", tree.root()[2..].to_uppercase());
+        assert!(MerkleTree::verify_record(&proof, &accounts[2], &shouted).unwrap());
+        assert!(!MerkleTree::verify_record(&proof, &accounts[1], &tree.root()).unwrap());
+    }
+
+    #[test]
+    fn test_set_record() {
+        let accounts = accounts();
+        let mut tree = MerkleTree::from_records(&accounts).unwrap();
+        let updated = Account {
+            id: 1,
+            balance: 5,
+            nonce: 1,
+        };
+        tree.set_record(1, &updated).unwrap();
+
+        let proof = tree.proof(1).unwrap();
+        assert!(MerkleTree::verify_record(&proof, &updated, &tree.root()).unwrap());
+        assert!(!MerkleTree::verify_record(&proof, &accounts[1], &tree.root()).unwrap());
+    }
+}
@@ -0,0 +1,166 @@
+//! Leaf Lookup
+//!
+//! `MerkleTree::find_leaf` and `MerkleTree::contains_leaf` look a value up among the leaves,
+//! ignoring the case of its hex digits. By default they scan every leaf. Once
+//! `MerkleTree::enable_leaf_index` is called the tree also keeps a map from each distinct leaf
+//! value to the leaves holding it, updated by every write, and both become a single hash map
+//! lookup. The index holds one copy of every distinct value and one entry per leaf.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LeafLookup {
+    // leaf indices holding each lowercased value, lowest first
+    positions: HashMap<String, BTreeSet<usize>>,
+}
+
+impl LeafLookup {
+    pub(crate) fn from_leaves(leaves: &[String]) -> Self {
+        let mut lookup = LeafLookup::default();
+        for (leaf_index, leaf) in leaves.iter().enumerate() {
+            lookup.insert(leaf_index, leaf);
+        }
+        lookup
+    }
+
+    fn insert(&mut self, leaf_index: usize, value: &str) {
+        self.positions
+            .entry(value.to_ascii_lowercase())
+            .or_default()
+            .insert(leaf_index);
+    }
+
+    // moves `leaf_index` from its old value, if it had one, to `value`
+    pub(crate) fn replace(&mut self, leaf_index: usize, old: Option<&String>, value: &str) {
+        if let Some(old) = old {
+            let old = old.to_ascii_lowercase();
+            if let Some(indices) = self.positions.get_mut(&old) {
+                indices.remove(&leaf_index);
+                if indices.is_empty() {
+                    self.positions.remove(&old);
+                }
+            }
+        }
+        self.insert(leaf_index, value);
+    }
+
+    fn first(&self, value: &str) -> Option<usize> {
+        let indices = self.positions.get(&value.to_ascii_lowercase())?;
+        indices.first().copied()
+    }
+}
+
+// wipes the copies of leaf values the index holds, see `MerkleTree::zeroize`
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for LeafLookup {
+    fn zeroize(&mut self) {
+        for (mut value, _) in self.positions.drain() {
+            value.zeroize();
+        }
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Starts keeping an index of leaf values for `find_leaf` and `contains_leaf`, see the
+    /// module documentation. Does nothing if the index is already enabled.
+    pub fn enable_leaf_index(&mut self) {
+        if self.leaf_lookup_ref().is_none() {
+            let nodes = self.state().0;
+            let lookup = LeafLookup::from_leaves(&nodes[nodes.len() - self.num_leaves()..]);
+            *self.leaf_lookup_mut() = Some(lookup);
+        }
+    }
+
+    /// Drops the leaf index, going back to scanning the leaves.
+    pub fn disable_leaf_index(&mut self) {
+        *self.leaf_lookup_mut() = None;
+    }
+
+    /// Returns the first leaf holding `value`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The `0x` prefixed value to look for, in either case.
+    ///
+    /// # Returns
+    ///
+    /// * The lowest index of a leaf holding `value`, or `None` if no leaf does.
+    pub fn find_leaf(&self, value: &str) -> Option<usize> {
+        let value = value.trim();
+        if let Some(lookup) = self.leaf_lookup_ref() {
+            return lookup.first(value);
+        }
+        let nodes = self.state().0;
+        nodes[nodes.len() - self.num_leaves()..]
+            .iter()
+            .position(|leaf| leaf.eq_ignore_ascii_case(value))
+    }
+
+    /// returns true if some leaf holds `value`, in either case
+    pub fn contains_leaf(&self, value: &str) -> bool {
+        self.find_leaf(value).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    // checks the index answers every lookup like a scan of the leaves
+    fn assert_index_consistent(tree: &MerkleTree, values: &[String]) {
+        let mut scanned = tree.clone();
+        scanned.disable_leaf_index();
+        for value in values.iter().map(String::as_str).chain([ZERO_LEAF]) {
+            assert_eq!(tree.find_leaf(value), scanned.find_leaf(value));
+        }
+    }
+
+    #[test]
+    fn test_leaf_index_stays_consistent() {
+        let values: Vec<String> = (1..=4).map(|i| format!("0x{:064x}", i)).collect();
+        let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        assert_eq!(tree.find_leaf(ZERO_LEAF), Some(0));
+        tree.enable_leaf_index();
+        tree.enable_undo(4);
+
+        tree.set(5, &values[0]).unwrap();
+        assert_eq!(tree.find_leaf(&values[0]), Some(5));
+        // duplicates resolve to the lowest leaf, and overwriting a leaf forgets its old value
+        tree.set(2, &values[0]).unwrap();
+        assert_eq!(tree.find_leaf(&values[0]), Some(2));
+        tree.set(2, &values[1]).unwrap();
+        assert_eq!(tree.find_leaf(&values[0]), Some(5));
+        tree.set_batch(&[(5, values[2].as_str()), (0, values[3].as_str())])
+            .unwrap();
+        assert!(!tree.contains_leaf(&values[0]));
+        assert_eq!(tree.find_leaf(ZERO_LEAF), Some(1));
+        assert_index_consistent(&tree, &values);
+
+        tree.undo().unwrap();
+        assert_eq!(tree.find_leaf(&values[0]), Some(5));
+        assert_index_consistent(&tree, &values);
+
+        let upper = format!("0x{}", values[1][2..].to_uppercase());
+        assert_eq!(tree.find_leaf(&upper), Some(2));
+        tree.set(7, &upper).unwrap();
+        tree.set(2, ZERO_LEAF).unwrap();
+        assert_eq!(tree.find_leaf(&values[1]), Some(7));
+        assert_index_consistent(&tree, &values);
+    }
+
+    #[test]
+    fn test_leaf_index_follows_growth() {
+        let mut tree = MerkleTree::empty();
+        tree.enable_leaf_index();
+        assert!(!tree.contains_leaf(ZERO_LEAF));
+        let values: Vec<String> = (1..=5).map(|i| format!("0x{:064x}", i)).collect();
+        for value in &values {
+            tree.append_growing(value).unwrap();
+        }
+        assert_eq!(tree.find_leaf(&values[4]), Some(4));
+        assert_eq!(tree.find_leaf(ZERO_LEAF), Some(5));
+        assert_index_consistent(&tree, &values);
+    }
+}
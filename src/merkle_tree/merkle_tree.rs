@@ -1,107 +1,704 @@
 #![allow(unused_imports)]
-use crate::errors::errors::MerkleError;
-use crate::utils::index::{left_child_index, parent_index};
+use crate::errors::errors::{IntegrityError, MerkleError, NodeMismatch};
+use crate::merkle_tree::config::TreeConfig;
+use crate::merkle_tree::dirty::DirtyLeaves;
+use crate::merkle_tree::hasher::{debug_self_test, MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::leaf_lookup::LeafLookup;
+use crate::merkle_tree::observer::RootObservers;
+use crate::merkle_tree::proof_cache::ProofCache;
+use crate::merkle_tree::undo::{UndoEntry, UndoLog};
+use crate::utils::index::{
+    depth_offset_to_index, index_to_depth_offset, left_child_index, level_index_range, node_count,
+    parent_index, subtree_leaf_count,
+};
 use hex;
 use num_bigint::BigUint;
 use num_traits::FromPrimitive;
-use sha3::{Digest, Sha3_256};
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// the largest depth (one indexed) a tree can be constructed with, unless a `TreeConfig` raises
+/// or lowers it
+pub const MAX_DEPTH: usize = 30;
+
+// the node count of every tree up to `MAX_DEPTH` must fit in a usize on every target
+const _: () = assert!(MAX_DEPTH < u32::BITS as usize);
+
+/// width in bytes of the nodes of a tree using the default `Sha3_256Hasher`
+pub const DIGEST_BYTES: usize = 32;
+
+/// leaf count above which `commit_to_all_leaves` warns on stderr about the size of its output
+pub const COMMIT_WARNING_LEAVES: usize = 1_000_000;
+
+/// width in hex characters, without the `0x` prefix, of a `DIGEST_BYTES` node
+pub(crate) const DIGEST_HEX: usize = 2 * DIGEST_BYTES;
+
+/// the root of an empty tree: SHA3-256 of the empty string, as RFC 6962 does for SHA-256
+pub const EMPTY_ROOT: &str = "0xa7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a";
+
+/// the all zero leaf, used to pad trees built from a partial leaf set
+pub const ZERO_LEAF: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// `ZERO_HASHES[h]` is the root of a subtree of height `h` whose leaves are all `ZERO_LEAF`, i.e.
+/// `ZERO_HASHES[0]` is `ZERO_LEAF` and every other entry hashes the previous one with itself.
+pub static ZERO_HASHES: Lazy<[String; MAX_DEPTH + 1]> = Lazy::new(|| {
+    let hasher = Sha3_256Hasher::<DIGEST_BYTES>;
+    let mut current = vec![0u8; DIGEST_BYTES];
+    std::array::from_fn(|_| {
+        let hash = format!("0x{}", hex::encode(&current));
+        current = hasher.hash_pair(&current, &current);
+        hash
+    })
+});
+
+// checks that `value` is exactly `0x` followed by `2 * width` hex digits of either case
+pub(crate) fn validate_leaf(value: &str, width: usize) -> Result<(), MerkleError> {
+    match value.strip_prefix("0x") {
+        Some(digits)
+            if digits.len() == 2 * width && digits.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            Ok(())
+        }
+        _ => Err(MerkleError::InvalidBytes),
+    }
+}
+
+// rejects depths beyond `max_depth`
+pub(crate) fn check_depth(depth: usize, max_depth: usize) -> Result<(), MerkleError> {
+    if depth > max_depth {
+        return Err(MerkleError::MaxDepthExceeded {
+            requested: depth,
+            permitted: max_depth,
+        });
+    }
+    Ok(())
+}
+
+// returns the number of nodes of a `depth` deep tree, rejecting depths beyond `max_depth` or
+// whose node count does not fit in a usize
+pub(crate) fn tree_size(depth: usize, max_depth: usize) -> Result<usize, MerkleError> {
+    check_depth(depth, max_depth)?;
+    node_count(depth).map_err(|_| MerkleError::MaxDepthExceeded {
+        requested: depth,
+        permitted: max_depth,
+    })
+}
+
+// trims surrounding whitespace off a caller supplied node and validates what is left
+pub(crate) fn normalize_node(value: &str, width: usize) -> Result<&str, MerkleError> {
+    let value = value.trim();
+    validate_leaf(value, width)?;
+    Ok(value)
+}
+
+// hashes the concatenation of two `0x` prefixed hex nodes
+pub(crate) fn hash_nodes<H: MerkleHasher>(
+    hasher: &H,
+    left: &str,
+    right: &str,
+) -> Result<String, MerkleError> {
+    let left = hex::decode(&left[2..])?;
+    let right = hex::decode(&right[2..])?;
+    Ok(format!(
+        "0x{}",
+        hex::encode(hasher.hash_pair(&left, &right))
+    ))
+}
+
+// checks that `proof` has one step per level below the root of a `depth` deep tree
+pub(crate) fn check_proof_length(proof: &[ProofStep], depth: usize) -> Result<(), MerkleError> {
+    let expected = depth.saturating_sub(1);
+    if proof.len() != expected {
+        return Err(MerkleError::ProofLengthMismatch {
+            expected,
+            actual: proof.len(),
+        });
+    }
+    Ok(())
+}
+
+// folds `proof` up from `leaf_value`, returning the implied root
+fn fold_proof<H: MerkleHasher>(
+    hasher: &H,
+    proof: &[ProofStep],
+    leaf_value: String,
+) -> Result<String, MerkleError> {
+    fold_proof_traced(hasher, proof, leaf_value, |_, _, _, _| {})
+}
+
+// `fold_proof`, calling `on_step` with the level, the left and right inputs and the output of
+// every hash, so a trace of the fold cannot differ from verification
+pub(crate) fn fold_proof_traced<H: MerkleHasher>(
+    hasher: &H,
+    proof: &[ProofStep],
+    leaf_value: String,
+    mut on_step: impl FnMut(usize, &str, &str, &str),
+) -> Result<String, MerkleError> {
+    let mut current_value = normalize_node(&leaf_value, H::DIGEST_BYTES)?.to_string();
+    // validate every sibling before hashing, so a malformed proof is rejected without any work
+    let siblings = proof
+        .iter()
+        .map(|step| normalize_node(&step.sibling, H::DIGEST_BYTES))
+        .collect::<Result<Vec<&str>, MerkleError>>()?;
+
+    for (level, (step, sibling)) in proof.iter().zip(siblings).enumerate() {
+        let (left, right) = match step.direction {
+            Direction::Right => (sibling, current_value.as_str()),
+            Direction::Left => (current_value.as_str(), sibling),
+        };
+        let output = hash_nodes(hasher, left, right)?;
+        on_step(level, left, right, &output);
+        current_value = output;
+    }
+
+    Ok(current_value)
+}
+
+// returns the `total_nodes` nodes of a non-empty tree whose leaves all hold the validated
+// `initial_leaf`, hashing once per level
+fn uniform_nodes<H: MerkleHasher>(
+    hasher: &H,
+    total_nodes: usize,
+    initial_leaf: &str,
+) -> Result<Vec<String>, MerkleError> {
+    //adjusting example in spec, which is one-indexed
+    //i.e. depth(20) == 0 to 19,
+    let depth = (total_nodes + 1).trailing_zeros() as usize - 1;
+
+    let leaf_count = total_nodes.div_ceil(2);
+    let mut nodes = vec![String::with_capacity(2 * H::DIGEST_BYTES + 2); total_nodes];
+    let mut current_hash = hex::decode(&initial_leaf[2..])?;
+    let mut current_hash_string = String::from(initial_leaf);
+
+    for node in &mut nodes[(total_nodes - leaf_count)..total_nodes] {
+        *node = current_hash_string.clone();
+    }
+
+    // build up
+    for d in (0..depth).rev() {
+        current_hash = hasher.hash_pair(&current_hash, &current_hash);
+        current_hash_string = format!("0x{}", hex::encode(&current_hash));
+
+        let (start_idx, end_idx) = level_index_range(d)?;
+        for node in &mut nodes[start_idx..end_idx] {
+            *node = current_hash_string.clone();
+        }
+    }
+    Ok(nodes)
+}
+
+// joins two equally deep breadth-first node vectors into the node vector of the tree one level
+// deeper with `left` and `right` as its halves, hashing only the new root
+fn join_subtrees<H: MerkleHasher>(
+    hasher: &H,
+    left: &[String],
+    right: &[String],
+) -> Result<Vec<String>, MerkleError> {
+    let mut nodes = Vec::with_capacity(2 * left.len() + 1);
+    nodes.push(hash_nodes(hasher, &left[0], &right[0])?);
+    let mut width = 1;
+    while width <= left.len() {
+        nodes.extend_from_slice(&left[width - 1..2 * width - 1]);
+        nodes.extend_from_slice(&right[width - 1..2 * width - 1]);
+        width *= 2;
+    }
+    Ok(nodes)
+}
+
+// recomputes every internal node of a breadth-first node vector bottom-up from its leaves
+fn build_internal_nodes<H: MerkleHasher>(
+    hasher: &H,
+    nodes: &mut [String],
+) -> Result<(), MerkleError> {
+    for index in (0..nodes.len() / 2).rev() {
+        let left = left_child_index(index)?;
+        nodes[index] = hash_nodes(hasher, &nodes[left], &nodes[left + 1])?;
+    }
+    Ok(())
+}
+
+// returns the all zero leaf for nodes of `width` bytes
+pub(crate) fn zero_leaf(width: usize) -> String {
+    format!("0x{}", "00".repeat(width))
+}
+
 ///backbone MerkleTree struct using Vec
-pub struct MerkleTree {
+///
+/// `L` is the leaf payload type of the typed leaf accessors, see `merkle_tree::payload`. A
+/// payload is kept next to the leaf hash computed from it, while nodes, proofs and roots are
+/// always hashes shared by every payload type.
+///
+/// `H` is the hash function nodes are combined with, see `MerkleHasher`. Every node, including
+/// the leaves, is `H::DIGEST_BYTES` wide.
+pub struct MerkleTree<L = String, H = Sha3_256Hasher> {
     nodes: Vec<String>,
+    hasher: H,
+    next_leaf: usize,
+    sorted_len: Option<usize>,
+    observers: RootObservers,
+    dirty: Option<DirtyLeaves>,
+    proof_cache: Option<ProofCache>,
+    undo: Option<UndoLog>,
+    lookup: Option<LeafLookup>,
+    // the value every leaf held when the tree was constructed
+    initial_leaf: String,
+    // the depth `append_growing` and `extend_with_leaves` may not go beyond
+    max_depth: usize,
+    // per leaf, the payload its hash was computed from, if it was written as a payload
+    payloads: Vec<Option<L>>,
+}
+
+// implemented by hand so clones start without observers and with an empty proof cache
+impl<L: Clone, H: Clone> Clone for MerkleTree<L, H> {
+    fn clone(&self) -> Self {
+        MerkleTree {
+            nodes: self.nodes.clone(),
+            hasher: self.hasher.clone(),
+            next_leaf: self.next_leaf,
+            sorted_len: self.sorted_len,
+            observers: RootObservers::default(),
+            dirty: self.dirty.clone(),
+            proof_cache: self.proof_cache.as_ref().map(ProofCache::empty_copy),
+            undo: self.undo.clone(),
+            lookup: self.lookup.clone(),
+            initial_leaf: self.initial_leaf.clone(),
+            max_depth: self.max_depth,
+            payloads: self.payloads.clone(),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+/// Which child the proven node is at a proof step. `Left < Right` orders proof steps
+/// canonically for encodings that sort them, e.g. to deduplicate the steps of a multi-proof; the
+/// order says nothing about where the nodes are in the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Direction {
     Left,
     Right,
 }
 
+/// One level of a proof. Steps are ordered by direction, then by sibling as a string, so siblings
+/// differing only in the case of their hex digits sort apart. Like `Direction`'s, the order is
+/// only for canonical encodings.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ProofStep {
     direction: Direction,
     sibling: String,
 }
 
-impl MerkleTree {
-    /// returns the root of the tree
+// lets proofs of secret leaves be wiped, see `MerkleTree::zeroize`
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for ProofStep {
+    fn zeroize(&mut self) {
+        self.sibling.zeroize();
+    }
+}
+
+impl ProofStep {
+    /// creates a proof step from its direction and `0x` prefixed sibling hash
+    pub fn new(direction: Direction, sibling: String) -> Self {
+        ProofStep { direction, sibling }
+    }
+
+    /// returns which side of its parent the proven node sits on at this step
+    pub fn direction(&self) -> &Direction {
+        &self.direction
+    }
+
+    /// returns the sibling hash consumed at this step
+    pub fn sibling(&self) -> &str {
+        &self.sibling
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// returns the root of the tree, or the hash of the empty string if the tree has no leaves
     pub fn root(&self) -> String {
-        return self.nodes[0].clone();
+        match self.nodes.first() {
+            Some(root) => root.clone(),
+            None => format!("0x{}", hex::encode(self.hasher.digest(&[]))),
+        }
+    }
+
+    /// Returns a SHA3-256 commitment binding the root to the tree's parameters, so trees with
+    /// the same root but a different depth or hash function never share a commitment. The
+    /// digest is over the length-prefixed `MerkleHasher::algorithm_id`, the depth as a
+    /// big-endian `u64` and the root bytes, in that order.
+    pub fn commitment(&self) -> String {
+        let algorithm = self.hasher.algorithm_id().as_bytes();
+        let root = hex::decode(&self.root()[2..]).expect("roots are hex");
+        let mut data = Vec::with_capacity(16 + algorithm.len() + root.len());
+        data.extend_from_slice(&(algorithm.len() as u64).to_be_bytes());
+        data.extend_from_slice(algorithm);
+        data.extend_from_slice(&(self.depth() as u64).to_be_bytes());
+        data.extend_from_slice(&root);
+        format!("0x{}", hex::encode(Sha3_256Hasher::<32>.digest(&data)))
     }
 
     // returns the number of leaves in the tree
     pub fn num_leaves(&self) -> usize {
-        return self.nodes.len() / 2 + 1;
+        self.nodes.len().div_ceil(2)
     }
 
-    // bool indicating if the current index is the left child
-    fn is_left_child(&self, index: usize) -> bool {
-        index % 2 == 1
+    /// returns the depth (one indexed) of the tree, i.e. the number of levels including the root,
+    /// the `levels` of `with_levels`
+    pub fn depth(&self) -> usize {
+        (self.nodes.len() + 1).trailing_zeros() as usize
     }
 
-    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree with leaf values as initial_leaf.
+    /// returns the number of edges from the root to a leaf, the `height` of `with_height`, or
+    /// `None` for the empty tree
+    pub fn height(&self) -> Option<usize> {
+        self.depth().checked_sub(1)
+    }
+
+    /// returns the value every leaf held when the tree was constructed: `initial_leaf` for the
+    /// constructors taking one, and the all zero leaf for trees built from nodes or leaves
+    pub fn initial_leaf(&self) -> &str {
+        &self.initial_leaf
+    }
+
+    /// Returns the last leaf that no longer holds `initial_leaf`, e.g. to find where appending
+    /// should resume in a tree written front to back. Values are compared ignoring the case of
+    /// their hex digits.
+    ///
+    /// # Returns
+    ///
+    /// * The largest index of a leaf whose value differs from `initial_leaf`, or `None` if every
+    ///   leaf still holds it.
+    pub fn highest_set_leaf(&self) -> Option<usize> {
+        self.nodes[self.nodes.len() - self.num_leaves()..]
+            .iter()
+            .rposition(|leaf| !leaf.eq_ignore_ascii_case(&self.initial_leaf))
+    }
+
+    /// returns the largest depth (one indexed) the tree can grow to, `MAX_DEPTH` unless it was
+    /// constructed `with_config`
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// returns true if the tree is as deep as its `max_depth` allows
+    pub fn is_at_max_depth(&self) -> bool {
+        self.depth() == self.max_depth
+    }
+
+    /// returns the number of steps in a proof for this tree
+    pub fn proof_len(&self) -> usize {
+        self.depth().saturating_sub(1)
+    }
+
+    /// returns an estimate of the heap memory held by the nodes: the capacity of every node
+    /// `String` plus the node `Vec`'s own buffer
+    pub fn memory_bytes(&self) -> usize {
+        let strings: usize = self.nodes.iter().map(String::capacity).sum();
+        strings + self.nodes.capacity() * std::mem::size_of::<String>()
+    }
+
+    /// returns the hasher the tree combines nodes with
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Returns the value stored at `leaf_index`.
     ///
     /// # Arguments
     ///
-    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
-    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    /// * `leaf_index` - The 0 indexed leaf to read.
     ///
     /// # Returns
     ///
-    /// * A new MerkleTree
-    pub fn new(depth: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
-        if depth > 30 {
-            return Err(MerkleError::MaxDepthExceeded);
+    /// * Result containing the leaf value or `MerkleError::InvalidIndex`.
+    pub fn get_leaf(&self, leaf_index: usize) -> Result<&str, MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
         }
-        //adjusting example in spec, which is one-indexed
-        //i.e. depth(20) == 0 to 19,
-        let depth = depth - 1;
+        Ok(&self.nodes[self.nodes.len() - leaf_count + leaf_index])
+    }
 
-        let string_to_decode = &initial_leaf[2..];
+    /// Returns the node at `(depth, offset)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The zero indexed depth of the node, 0 being the root.
+    /// * `offset` - The offset of the node within its depth.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the node or `MerkleError::InvalidIndex`.
+    pub fn node(&self, depth: usize, offset: usize) -> Result<&str, MerkleError> {
+        let index = depth_offset_to_index(depth, offset)?;
+        self.nodes
+            .get(index)
+            .map(String::as_str)
+            .ok_or(MerkleError::InvalidIndex)
+    }
 
-        if string_to_decode.len() != 64 {
-            return Err(MerkleError::InvalidBytes);
-        }
+    /// Returns the root of the subtree at `(depth, offset)` with the number of leaves under it,
+    /// `2^(self.depth() - 1 - depth)`, for aggregating over ranges of leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The zero indexed depth of the subtree root, 0 being the root.
+    /// * `offset` - The offset of the subtree root within its depth.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the subtree root and its leaf count, or `MerkleError::InvalidIndex`.
+    pub fn level_root(&self, depth: usize, offset: usize) -> Result<(String, usize), MerkleError> {
+        let node = self.node(depth, offset)?.to_string();
+        let index = depth_offset_to_index(depth, offset)?;
+        Ok((node, subtree_leaf_count(index, self.depth())?))
+    }
+
+    // bool indicating if the current index is the left child
+    fn is_left_child(&self, index: usize) -> bool {
+        index % 2 == 1
+    }
+
+    // reinterprets the tree's leaves as payload `M`, without payloads for any leaf
+    pub(crate) fn with_payload<M>(self) -> MerkleTree<M, H> {
+        let mut tree = MerkleTree::with_nodes(self.nodes, self.hasher);
+        tree.max_depth = self.max_depth;
+        tree.initial_leaf = self.initial_leaf;
+        tree
+    }
+
+    // records `initial_leaf` as the value the tree's leaves were constructed with
+    pub(crate) fn with_initial_leaf(mut self, initial_leaf: &str) -> Self {
+        self.initial_leaf = initial_leaf.to_string();
+        self
+    }
+
+    // number of leading leaves known to be strictly ascending, if built by `from_sorted_leaves`
+    pub(crate) fn sorted_len(&self) -> Option<usize> {
+        self.sorted_len
+    }
+
+    pub(crate) fn set_sorted_len(&mut self, sorted_len: Option<usize>) {
+        self.sorted_len = sorted_len;
+    }
+
+    pub(crate) fn payloads_ref(&self) -> &[Option<L>] {
+        &self.payloads
+    }
 
-        let leaf_count = 1 << depth;
-        let total_nodes = 2 * leaf_count - 1;
-        let mut nodes = vec![String::with_capacity(64); total_nodes];
-        let mut hasher = Sha3_256::new();
-        let mut current_hash: [u8; 32];
-        let mut current_hash_string = String::from(initial_leaf);
+    pub(crate) fn payloads_mut(&mut self) -> &mut Vec<Option<L>> {
+        &mut self.payloads
+    }
 
-        let initial_leaf_bytes;
-        match hex::decode(string_to_decode) {
-            Ok(bytes) => {
-                initial_leaf_bytes = bytes;
+    // drops the payloads of leaves `old_nodes` and the current nodes disagree on, after the
+    // nodes were replaced wholesale
+    fn drop_replaced_payloads(&mut self, old_nodes: &[String]) {
+        let leaf_count = self.num_leaves();
+        if old_nodes.len() != self.nodes.len() {
+            self.payloads.clear();
+        }
+        let first_leaf = self.nodes.len() - leaf_count;
+        self.payloads.truncate(leaf_count);
+        for (leaf_index, payload) in self.payloads.iter_mut().enumerate() {
+            if old_nodes[first_leaf + leaf_index] != self.nodes[first_leaf + leaf_index] {
+                *payload = None;
             }
-            Err(e) => return Err(MerkleError::EncodeError(e)),
         }
+    }
 
-        current_hash = match initial_leaf_bytes.try_into() {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(MerkleError::InvalidBytes),
-        };
+    // wraps a node vector read back from storage, checking every node is well formed and every
+    // parent is the hash of its children
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_node_vec(nodes: Vec<String>, hasher: H) -> Result<Self, MerkleError> {
+        for node in nodes.iter() {
+            validate_leaf(node, H::DIGEST_BYTES)?;
+        }
+        let tree = MerkleTree::with_nodes(nodes, hasher);
+        tree.validate()
+            .map_err(|e| MerkleError::SerializationError(e.to_string()))?;
+        Ok(tree)
+    }
+
+    // nodes, append position and sorted prefix, the state captured by a snapshot
+    pub(crate) fn state(&self) -> (&[String], usize, Option<usize>) {
+        (&self.nodes, self.next_leaf, self.sorted_len)
+    }
+
+    // replaces the state captured by a snapshot, returning the replaced nodes
+    pub(crate) fn restore_state(
+        &mut self,
+        nodes: Vec<String>,
+        next_leaf: usize,
+        sorted_len: Option<usize>,
+    ) -> Vec<String> {
+        let old_root = self.root();
+        let old_nodes = std::mem::replace(&mut self.nodes, nodes);
+        self.drop_replaced_payloads(&old_nodes);
+        self.next_leaf = next_leaf;
+        self.sorted_len = sorted_len;
+        self.invalidate_proofs();
+        self.clear_undo();
+        self.reindex_leaves();
+        self.notify_root_change(&old_root);
+        old_nodes
+    }
+
+    pub(crate) fn dirty_leaves_ref(&self) -> Option<&DirtyLeaves> {
+        self.dirty.as_ref()
+    }
+
+    pub(crate) fn dirty_leaves_mut(&mut self) -> &mut Option<DirtyLeaves> {
+        &mut self.dirty
+    }
+
+    // bookkeeping for every leaf written by a mutating operation, before `value` is written
+    fn leaf_written(&mut self, leaf_index: usize, value: &str) {
+        // an arbitrary write may break the ordering `non_membership_proof` relies on
+        self.sorted_len = None;
+        let first_leaf = self.nodes.len() - self.num_leaves();
+        if let Some(lookup) = &mut self.lookup {
+            lookup.replace(leaf_index, self.nodes.get(first_leaf + leaf_index), value);
+        }
+        if let Some(dirty) = &mut self.dirty {
+            dirty.insert(leaf_index);
+        }
+        if let Some(cache) = &mut self.proof_cache {
+            cache.retain_leaf(leaf_index);
+        }
+        // a payload tree re-attaches the payload after writing its hash
+        if let Some(payload) = self.payloads.get_mut(leaf_index) {
+            *payload = None;
+        }
+    }
+
+    pub(crate) fn proof_cache_ref(&self) -> Option<&ProofCache> {
+        self.proof_cache.as_ref()
+    }
+
+    pub(crate) fn proof_cache_mut(&mut self) -> &mut Option<ProofCache> {
+        &mut self.proof_cache
+    }
+
+    // drops every cached proof, for operations that rewrite internal nodes
+    fn invalidate_proofs(&mut self) {
+        if let Some(cache) = &mut self.proof_cache {
+            cache.clear();
+        }
+    }
+
+    pub(crate) fn leaf_lookup_ref(&self) -> Option<&LeafLookup> {
+        self.lookup.as_ref()
+    }
+
+    pub(crate) fn leaf_lookup_mut(&mut self) -> &mut Option<LeafLookup> {
+        &mut self.lookup
+    }
+
+    // rebuilds the leaf lookup, for operations that rewrite leaves wholesale
+    fn reindex_leaves(&mut self) {
+        if self.lookup.is_some() {
+            let first_leaf = self.nodes.len() - self.num_leaves();
+            self.lookup = Some(LeafLookup::from_leaves(&self.nodes[first_leaf..]));
+        }
+    }
+
+    pub(crate) fn undo_log_ref(&self) -> Option<&UndoLog> {
+        self.undo.as_ref()
+    }
+
+    pub(crate) fn undo_log_mut(&mut self) -> &mut Option<UndoLog> {
+        &mut self.undo
+    }
 
-        for i in (total_nodes - leaf_count)..total_nodes {
-            nodes[i] = current_hash_string.clone();
+    // forgets the undo history, for operations whose effect it can't express
+    fn clear_undo(&mut self) {
+        if let Some(undo) = &mut self.undo {
+            undo.clear();
         }
+    }
+
+    // captures the nodes on the paths of `leaf_indices` so `undo` can revert a write to them,
+    // or `None` if undo is disabled. Out of range leaves are skipped, the write rejects them.
+    fn undo_entry(&self, leaf_indices: impl IntoIterator<Item = usize>) -> Option<UndoEntry> {
+        self.undo.as_ref()?;
+        let leaf_count = self.num_leaves();
+        let first_leaf = self.nodes.len() - leaf_count;
+        let mut indices = BTreeSet::new();
+        for leaf_index in leaf_indices.into_iter().filter(|&i| i < leaf_count) {
+            let mut index = Some(first_leaf + leaf_index);
+            // paths merge, so stop at the first node already captured
+            while let Some(node) = index.filter(|&node| indices.insert(node)) {
+                index = parent_index(node);
+            }
+        }
+        Some(UndoEntry {
+            node_count: self.nodes.len(),
+            nodes: indices
+                .into_iter()
+                .map(|index| (index, self.nodes[index].clone()))
+                .collect(),
+            next_leaf: self.next_leaf,
+            sorted_len: self.sorted_len,
+        })
+    }
 
-        // build up
-        for d in (0..depth).rev() {
-            let mut concatenated_hash = [0u8; 64];
-            concatenated_hash[..32].copy_from_slice(&current_hash);
-            concatenated_hash[32..].copy_from_slice(&current_hash);
-            hasher.update(&concatenated_hash);
-            current_hash = hasher.finalize_reset().into();
-            current_hash_string = format!("0x{}", hex::encode(current_hash));
+    // records a successful mutation captured by `undo_entry`
+    fn record_undo(&mut self, entry: Option<UndoEntry>) {
+        if let (Some(undo), Some(entry)) = (&mut self.undo, entry) {
+            undo.push(entry);
+        }
+    }
 
-            let start_idx = (1 << d) - 1;
-            let end_idx = (1 << (d + 1)) - 1;
-            for i in start_idx..end_idx {
-                nodes[i] = current_hash_string.clone();
+    // reverts the mutation recorded in `entry`, notifying observers
+    pub(crate) fn apply_undo(&mut self, entry: UndoEntry) {
+        let old_root = self.root();
+        self.nodes.truncate(entry.node_count);
+        self.payloads.truncate(self.num_leaves());
+        let first_leaf = self.nodes.len() - self.num_leaves();
+        for (index, node) in entry.nodes {
+            if index >= first_leaf {
+                if let Some(dirty) = &mut self.dirty {
+                    dirty.insert(index - first_leaf);
+                }
+                if let Some(payload) = self.payloads.get_mut(index - first_leaf) {
+                    *payload = None;
+                }
             }
+            self.nodes[index] = node;
+        }
+        self.next_leaf = entry.next_leaf;
+        self.sorted_len = entry.sorted_len;
+        self.invalidate_proofs();
+        self.reindex_leaves();
+        self.notify_root_change(&old_root);
+    }
+
+    pub(crate) fn root_observers(&mut self) -> &mut RootObservers {
+        &mut self.observers
+    }
+
+    // notifies the root observers if the root is no longer `old_root`
+    fn notify_root_change(&mut self, old_root: &str) {
+        let root = self.root();
+        if root != old_root {
+            self.observers.notify(&root);
         }
+    }
 
-        Ok(MerkleTree { nodes })
+    // wraps an already built node vector
+    pub(crate) fn with_nodes(nodes: Vec<String>, hasher: H) -> Self {
+        MerkleTree {
+            nodes,
+            hasher,
+            next_leaf: 0,
+            sorted_len: None,
+            observers: RootObservers::default(),
+            dirty: None,
+            proof_cache: None,
+            undo: None,
+            lookup: None,
+            initial_leaf: zero_leaf(H::DIGEST_BYTES),
+            max_depth: MAX_DEPTH,
+            payloads: Vec::new(),
+        }
     }
 
     /// Sets a new leaf value and re-calculates the merkle root.
@@ -115,261 +712,3051 @@ impl MerkleTree {
     ///
     /// * Result indicating success or error
     pub fn set(&mut self, leaf_index: usize, value: &str) -> Result<(), MerkleError> {
-        let leaf_count = self.num_leaves();
-        if leaf_index >= leaf_count {
-            return Err(MerkleError::InvalidIndex);
-        }
-
-        let array_index = self.nodes.len() - leaf_count + leaf_index;
-
-        self.nodes[array_index] = value.to_string();
-
-        let mut hasher = Sha3_256::new();
-        let mut curr_index = parent_index(array_index);
-        while let Some(index) = curr_index {
-            let left_child_hash = hex::decode(&self.nodes[left_child_index(index)][2..])
-                .map_err(|e| MerkleError::EncodeError(e))?;
-            let right_child_hash = hex::decode(&self.nodes[left_child_index(index) + 1][2..])
-                .map_err(|e| MerkleError::EncodeError(e))?;
-
-            let mut concatenated_hash: Vec<u8> = Vec::new();
-            concatenated_hash.extend(&left_child_hash);
-            concatenated_hash.extend(&right_child_hash);
-
-            hasher.update(&concatenated_hash);
-            self.nodes[index] = format!("0x{}", hex::encode(hasher.finalize_reset()));
-            curr_index = parent_index(index);
-        }
+        let old_root = self.root();
+        let undo = self.undo_entry([leaf_index]);
+        self.write_leaf(leaf_index, value)?;
+        self.record_undo(undo);
+        self.notify_root_change(&old_root);
         Ok(())
     }
 
-    /// Constructs a proof out of `ProofStep` objects, which can be used verify the proof.
-    /// Records direction and sibling all the way to the root to prove inclusion of a leaf.
+    /// Like `set`, but skips the write when the leaf already holds `value`, ignoring surrounding
+    /// whitespace and the case of its hex digits. A skipped write recomputes nothing and records
+    /// no undo entry.
     ///
     /// # Arguments
     ///
-    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `value` - The new value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
     ///
     /// # Returns
     ///
-    /// * `Vec<ProofStep>` containing the proof steps to be verified.
-    pub fn proof(&self, leaf_index: usize) -> Vec<ProofStep> {
-        let mut proof_steps = Vec::new();
-
-        let mut index = leaf_index + self.nodes.len() - self.num_leaves();
-        while let Some(parent_index) = parent_index(index) {
-            let sibling_index = if self.is_left_child(index) {
-                index + 1
-            } else {
-                index - 1
-            };
-
-            let step = ProofStep {
-                direction: if self.is_left_child(index) {
-                    Direction::Left
-                } else {
-                    Direction::Right
-                },
-                sibling: self.nodes[sibling_index].clone(),
-            };
-
-            proof_steps.push(step);
-
-            // Move up the tree
-            index = parent_index;
+    /// * Result containing true if the leaf changed, or false if it already held `value`.
+    pub fn set_if_changed(&mut self, leaf_index: usize, value: &str) -> Result<bool, MerkleError> {
+        let normalized = normalize_node(value, H::DIGEST_BYTES)?;
+        if self.get_leaf(leaf_index)?.eq_ignore_ascii_case(normalized) {
+            return Ok(false);
         }
-        proof_steps
+        self.set(leaf_index, normalized)?;
+        Ok(true)
     }
 
-    /// Given a `proof` and leaf_value, calculates and returns the root.
+    /// Like `set`, but returns every node the write recomputed, for syncing a replica: the array
+    /// index and new hash of each node from the leaf up to the root.
     ///
     /// # Arguments
     ///
-    /// * `proof` - `Vec<ProofStep>` containing the proof steps to be verified.
-    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `value` - The new value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
     ///
     /// # Returns
     ///
-    /// * Result containing the root of the tree or Error.
-    pub fn verify(proof: &Vec<ProofStep>, leaf_value: String) -> Result<String, MerkleError> {
-        let mut hasher = Sha3_256::new();
-        let mut current_value = leaf_value;
-
-        for step in proof.iter() {
-            let mut concatenated: Vec<u8> = Vec::new();
-            let sibling_hash =
-                hex::decode(&step.sibling[2..]).map_err(|e| MerkleError::EncodeError(e))?;
-            let current_hash =
-                hex::decode(&current_value[2..]).map_err(|e| MerkleError::EncodeError(e))?;
-            match step.direction {
-                Direction::Right => {
-                    concatenated.extend(&sibling_hash);
-                    concatenated.extend(&current_hash);
-                }
-                Direction::Left => {
-                    concatenated.extend(&current_hash);
-                    concatenated.extend(&sibling_hash);
-                }
+    /// * Result containing the `(index, hash)` pairs in leaf-to-root order, or Error.
+    pub fn set_returning_changes(
+        &mut self,
+        leaf_index: usize,
+        value: &str,
+    ) -> Result<Vec<(usize, String)>, MerkleError> {
+        self.set(leaf_index, value)?;
+        let mut changed = self.path_nodes(leaf_index)?;
+        changed.push(0);
+        Ok(changed
+            .into_iter()
+            .map(|index| (index, self.nodes[index].clone()))
+            .collect())
+    }
+
+    /// Sets several leaves at once and re-calculates the merkle root, hashing every node shared
+    /// by the updated paths only once. Nothing is written if any update is invalid.
+    ///
+    /// Updates are applied in order, so when several target the same leaf the last one wins, as
+    /// it would with repeated calls to `set`. Every update is still validated, so an invalid
+    /// value rejects the batch even if a later update to the same leaf would overwrite it.
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - The `(leaf_index, value)` pairs to set.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn set_batch(&mut self, updates: &[(usize, &str)]) -> Result<(), MerkleError> {
+        let old_root = self.root();
+        let levels = self.batch_levels(updates)?;
+        if !levels.is_empty() {
+            self.record_undo(self.undo_entry(updates.iter().map(|&(leaf_index, _)| leaf_index)));
+        }
+        let first_leaf = self.nodes.len() - self.num_leaves();
+        if let Some(leaves) = levels.first() {
+            for (index, node) in leaves {
+                self.leaf_written(index - first_leaf, node);
             }
-            hasher.update(concatenated);
-            current_value = format!("0x{}", hex::encode(hasher.finalize_reset()));
         }
+        for (index, node) in levels.into_iter().flatten() {
+            self.nodes[index] = node;
+        }
+        self.notify_root_change(&old_root);
+        Ok(())
+    }
 
-        Ok(current_value)
+    /// Applies a stream of updates like repeated calls to `set`, but recomputes the tree in a
+    /// single bottom-up pass once the stream ends. Only the `(leaf_index, value)` pairs are
+    /// buffered. As with `set_batch`, nothing is written if any update is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - The `(leaf_index, value)` pairs to set, in order.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn apply_updates_from_iter<I: IntoIterator<Item = (usize, String)>>(
+        &mut self,
+        updates: I,
+    ) -> Result<(), MerkleError> {
+        let updates: Vec<(usize, String)> = updates.into_iter().collect();
+        let updates: Vec<(usize, &str)> = updates
+            .iter()
+            .map(|(leaf_index, value)| (*leaf_index, value.as_str()))
+            .collect();
+        self.set_batch(&updates)
     }
-}
+
+    /// Applies a batch of updates as one transaction and returns the new root. Every update is
+    /// validated and every changed node hashed before the first write, so the tree either moves
+    /// straight from the old root to the new one or, if any update is invalid, is left as it was.
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - The `(leaf_index, value)` pairs to set, in order.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root after every update, or Error.
+    pub fn batch_update_and_get_root(
+        &mut self,
+        updates: &[(usize, &str)],
+    ) -> Result<String, MerkleError> {
+        self.set_batch(updates)?;
+        Ok(self.root())
+    }
+
+    // writes `value` to a leaf and re-calculates its path, without notifying observers
+    fn write_leaf(&mut self, leaf_index: usize, value: &str) -> Result<(), MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let value = normalize_node(value, H::DIGEST_BYTES)?;
+
+        let array_index = self.nodes.len() - leaf_count + leaf_index;
+
+        self.leaf_written(leaf_index, value);
+        self.nodes[array_index] = value.to_string();
+
+        let mut curr_index = parent_index(array_index);
+        while let Some(index) = curr_index {
+            let left = left_child_index(index)?;
+            self.nodes[index] = hash_nodes(&self.hasher, &self.nodes[left], &self.nodes[left + 1])?;
+            curr_index = parent_index(index);
+        }
+        Ok(())
+    }
+
+    /// Returns the root the tree would have if the leaf at `leaf_index` were set to `value`,
+    /// without changing the tree. Folds `value` up the leaf's path using the tree's current
+    /// siblings.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to simulate setting.
+    /// * `value` - The candidate value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the hypothetical root or Error.
+    pub fn simulate_set(&self, leaf_index: usize, value: &str) -> Result<String, MerkleError> {
+        self.simulate_set_batch(&[(leaf_index, value)])
+    }
+
+    /// Returns the root the tree would have after setting every `(leaf_index, value)` in
+    /// `updates`, without changing the tree. Later updates to the same leaf win, as they would
+    /// with repeated calls to `set`. Each level is merged once, so nodes shared by several
+    /// updated paths are only hashed once, which makes this a cheap alternative to a snapshot and
+    /// rollback for read-only projections.
+    ///
+    /// # Arguments
+    ///
+    /// * `updates` - The `(leaf_index, value)` pairs to simulate.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the hypothetical root or Error.
+    pub fn simulate_set_batch(&self, updates: &[(usize, &str)]) -> Result<String, MerkleError> {
+        match self.batch_levels(updates)?.last() {
+            Some(root_level) => Ok(root_level[&0].clone()),
+            None => Ok(self.root()),
+        }
+    }
+
+    // computes the nodes changed by `updates` level by level, from the leaves up to the root
+    fn batch_levels(
+        &self,
+        updates: &[(usize, &str)],
+    ) -> Result<Vec<BTreeMap<usize, String>>, MerkleError> {
+        let leaf_count = self.num_leaves();
+        let mut changed = BTreeMap::new();
+        for (leaf_index, value) in updates {
+            if *leaf_index >= leaf_count {
+                return Err(MerkleError::InvalidIndex);
+            }
+            let value = normalize_node(value, H::DIGEST_BYTES)?;
+            changed.insert(
+                self.nodes.len() - leaf_count + leaf_index,
+                value.to_string(),
+            );
+        }
+        if changed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // derive each level of changed parents from the level below until reaching the root
+        let mut levels = vec![changed];
+        while !levels.last().unwrap().contains_key(&0) {
+            let changed = levels.last().unwrap();
+            let mut parents = BTreeMap::new();
+            for index in changed.keys() {
+                let parent = parent_index(*index).unwrap();
+                if parents.contains_key(&parent) {
+                    continue;
+                }
+                let left = left_child_index(parent)?;
+                let node = |index: usize| changed.get(&index).unwrap_or(&self.nodes[index]);
+                parents.insert(
+                    parent,
+                    hash_nodes(&self.hasher, node(left), node(left + 1))?,
+                );
+            }
+            levels.push(parents);
+        }
+        Ok(levels)
+    }
+
+    /// Writes `value` to the next leaf that has not been appended to and re-calculates the merkle
+    /// root. Trees from `new` start appending at leaf 0 and trees from `from_leaves` after their
+    /// last leaf. An empty tree grows to a single leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The new value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the index of the written leaf, or `MerkleError::TreeFull`.
+    pub fn append(&mut self, value: &str) -> Result<usize, MerkleError> {
+        let old_root = self.root();
+        let undo = self.undo_entry([self.next_leaf]);
+        let leaf_index = self.push_leaf(value)?;
+        self.record_undo(undo);
+        self.notify_root_change(&old_root);
+        Ok(leaf_index)
+    }
+
+    // appends `value` without notifying observers
+    fn push_leaf(&mut self, value: &str) -> Result<usize, MerkleError> {
+        if self.nodes.is_empty() {
+            let value = normalize_node(value, H::DIGEST_BYTES)?;
+            self.leaf_written(0, value);
+            self.nodes.push(value.to_string());
+        } else if self.next_leaf < self.num_leaves() {
+            self.write_leaf(self.next_leaf, value)?;
+        } else {
+            return Err(MerkleError::TreeFull);
+        }
+        self.next_leaf += 1;
+        Ok(self.next_leaf - 1)
+    }
+
+    /// Like `append`, but when every leaf has been appended to the tree doubles in size instead of
+    /// returning `MerkleError::TreeFull`. The existing tree becomes the left subtree of a new root
+    /// and an all zero subtree the right one, so existing leaf indices and proofs for them (plus
+    /// one extra step) stay valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The new value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the index of the written leaf, or `MerkleError::MaxDepthExceeded` if
+    ///   growing would make the tree deeper than `MAX_DEPTH`.
+    pub fn append_growing(&mut self, value: &str) -> Result<usize, MerkleError> {
+        let old_root = self.root();
+        if !self.nodes.is_empty() && self.next_leaf == self.num_leaves() {
+            normalize_node(value, H::DIGEST_BYTES)?;
+            self.grow()?;
+        }
+        let undo = self.undo_entry([self.next_leaf]);
+        let leaf_index = self.push_leaf(value)?;
+        self.record_undo(undo);
+        self.notify_root_change(&old_root);
+        Ok(leaf_index)
+    }
+
+    // doubles the number of leaves, re-rooting the current tree as the left child of a new root
+    // whose right child is a subtree of zero leaves with the same depth
+    fn grow(&mut self) -> Result<(), MerkleError> {
+        let depth = self.depth();
+        check_depth(depth + 1, self.max_depth)?;
+
+        // zero subtree node per level, from the leaves up to the subtree root
+        let mut zeros = vec![zero_leaf(H::DIGEST_BYTES)];
+        for _ in 1..depth {
+            let below = zeros.last().unwrap();
+            zeros.push(hash_nodes(&self.hasher, below, below)?);
+        }
+
+        let mut right = Vec::with_capacity(self.nodes.len());
+        for level in 0..depth {
+            right.extend(std::iter::repeat_n(
+                zeros[depth - 1 - level].clone(),
+                1 << level,
+            ));
+        }
+        self.nodes = join_subtrees(&self.hasher, &self.nodes, &right)?;
+        self.invalidate_proofs();
+        self.reindex_leaves();
+        self.clear_undo();
+        Ok(())
+    }
+
+    /// Returns a tree one level deeper whose left half is this tree and whose right half holds
+    /// `additional_leaves`, padded with zero leaves. The right half is built from its leaves and
+    /// joined to the existing nodes, so only the new root is hashed on top of it. Leaf indices
+    /// of this tree stay the same and the new leaves start at `num_leaves()`. An empty tree is
+    /// extended into a tree built from `additional_leaves` alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `additional_leaves` - At most `num_leaves()` new leaf values. Each must be
+    ///   `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the extended MerkleTree, `MerkleError::TreeFull` if the leaves don't
+    ///   fit in the right half, or `MerkleError::MaxDepthExceeded`.
+    pub fn extend_with_leaves(
+        &self,
+        additional_leaves: &[&str],
+    ) -> Result<MerkleTree<L, H>, MerkleError>
+    where
+        L: Clone,
+        H: Clone,
+    {
+        let leaf_count = self.num_leaves();
+        if leaf_count == 0 {
+            let tree = MerkleTree::from_leaves_with_hasher(additional_leaves, self.hasher.clone())?;
+            check_depth(tree.depth(), self.max_depth)?;
+            let mut tree = tree.with_payload();
+            tree.max_depth = self.max_depth;
+            tree.initial_leaf = self.initial_leaf.clone();
+            return Ok(tree);
+        }
+        if additional_leaves.len() > leaf_count {
+            return Err(MerkleError::TreeFull);
+        }
+        check_depth(self.depth() + 1, self.max_depth)?;
+
+        let mut padded = additional_leaves.to_vec();
+        let zero = zero_leaf(H::DIGEST_BYTES);
+        padded.resize(leaf_count, &zero);
+        let right = MerkleTree::from_leaves_with_hasher(&padded, self.hasher.clone())?;
+
+        let nodes = join_subtrees(&self.hasher, &self.nodes, &right.nodes)?;
+        let mut tree = MerkleTree::with_nodes(nodes, self.hasher.clone());
+        tree.next_leaf = leaf_count + additional_leaves.len();
+        tree.max_depth = self.max_depth;
+        tree.initial_leaf = self.initial_leaf.clone();
+        // the left half keeps its leaf indices
+        tree.payloads = self.payloads.clone();
+        Ok(tree)
+    }
+
+    /// Constructs a proof out of `ProofStep` objects, which can be used verify the proof.
+    /// Records direction and sibling all the way to the root to prove inclusion of a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing `Vec<ProofStep>` with the proof steps to be verified, or `MerkleError::InvalidIndex`.
+    pub fn proof(&self, leaf_index: usize) -> Result<Vec<ProofStep>, MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let Some(cache) = &self.proof_cache else {
+            return Ok(self.build_proof(leaf_index));
+        };
+        if let Some(proof) = cache.get(leaf_index) {
+            return Ok(proof);
+        }
+        let proof = self.build_proof(leaf_index);
+        cache.insert(leaf_index, &proof);
+        Ok(proof)
+    }
+
+    /// Like `proof`, but also returns the leaf, so a verifier gets everything it needs in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf value and its proof steps, or `MerkleError::InvalidIndex`.
+    pub fn proof_with_leaf(
+        &self,
+        leaf_index: usize,
+    ) -> Result<(String, Vec<ProofStep>), MerkleError> {
+        let proof = self.proof(leaf_index)?;
+        Ok((self.get_leaf(leaf_index)?.to_string(), proof))
+    }
+
+    /// Constructs the leaf value and proof of every leaf, for applications that commit to all
+    /// leaves up front and open them one at a time later. This takes `O(n log n)` time and
+    /// memory, so it prints a warning to stderr for trees with more than
+    /// `COMMIT_WARNING_LEAVES` leaves. The proofs bypass the proof cache, so one bulk call does
+    /// not evict the proofs it holds.
+    ///
+    /// # Returns
+    ///
+    /// * A map from each 0 indexed leaf to the value and proof `proof_with_leaf` returns for it.
+    pub fn commit_to_all_leaves(&self) -> HashMap<usize, (String, Vec<ProofStep>)> {
+        let leaf_count = self.num_leaves();
+        if leaf_count > COMMIT_WARNING_LEAVES {
+            eprintln!(
+                "warning: commit_to_all_leaves is building {} proofs, which may exhaust memory",
+                leaf_count
+            );
+        }
+        self.nodes[self.nodes.len() - leaf_count..]
+            .iter()
+            .enumerate()
+            .map(|(leaf_index, leaf)| (leaf_index, (leaf.clone(), self.build_proof(leaf_index))))
+            .collect()
+    }
+
+    // walks from an in range leaf up to the root, collecting its siblings
+    fn build_proof(&self, leaf_index: usize) -> Vec<ProofStep> {
+        let leaf_count = self.num_leaves();
+        let mut proof_steps = Vec::new();
+
+        let mut index = leaf_index + self.nodes.len() - leaf_count;
+        while let Some(parent_index) = parent_index(index) {
+            let sibling_index = if self.is_left_child(index) {
+                index + 1
+            } else {
+                index - 1
+            };
+
+            let step = ProofStep {
+                direction: if self.is_left_child(index) {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                },
+                sibling: self.nodes[sibling_index].clone(),
+            };
+
+            proof_steps.push(step);
+
+            // Move up the tree
+            index = parent_index;
+        }
+        proof_steps
+    }
+
+    /// Returns every node at `depth`, left to right. Levels are contiguous in the breadth-first
+    /// layout, so this is a slice of the backing storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The level to return, 0 being the root.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the `2^depth` nodes of the level or `MerkleError::InvalidIndex`.
+    pub fn level(&self, depth: usize) -> Result<&[String], MerkleError> {
+        if depth >= self.depth() {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Ok(&self.nodes[(1 << depth) - 1..(2 << depth) - 1])
+    }
+
+    /// Iterates over the levels of the tree from the root down to the leaves, see `level`.
+    pub fn levels(&self) -> impl Iterator<Item = &[String]> + '_ {
+        (0..self.depth()).map(|depth| &self.nodes[(1 << depth) - 1..(2 << depth) - 1])
+    }
+
+    /// Returns only the sibling hashes of the proof for `leaf_index`, bottom-up. Verifiers
+    /// re-derive the direction at step `i` from bit `i` of the leaf index (set means the proven
+    /// node is a right child), as many Solidity verifiers and Bitcoin's `PartialMerkleTree` do.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want the authentication path for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the sibling hashes or `MerkleError::InvalidIndex`.
+    pub fn get_authentication_path(&self, leaf_index: usize) -> Result<Vec<String>, MerkleError> {
+        Ok(self
+            .proof(leaf_index)?
+            .into_iter()
+            .map(|step| step.sibling)
+            .collect())
+    }
+
+    /// Checks every internal node against the hash of its two stored children, without
+    /// changing the tree. A corrupted internal node is reported at its own index; a corrupted
+    /// leaf is reported as its parent mismatching.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if every internal node is consistent, otherwise an `IntegrityError` listing
+    ///   the mismatching nodes in breadth-first order.
+    pub fn validate(&self) -> Result<(), IntegrityError> {
+        self.check_nodes(0..self.nodes.len() / 2)
+    }
+
+    /// Checks the internal nodes on the path from `leaf_index` to the root, like `validate` but
+    /// only for a single leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf whose path to check.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the path is consistent, `MerkleError::InvalidIndex`, or
+    ///   `MerkleError::Integrity` listing the mismatching nodes on the path.
+    pub fn validate_path(&self, leaf_index: usize) -> Result<(), MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let mut path = Vec::new();
+        let mut index = parent_index(self.nodes.len() - leaf_count + leaf_index);
+        while let Some(current) = index {
+            path.push(current);
+            index = parent_index(current);
+        }
+        path.reverse();
+        Ok(self.check_nodes(path)?)
+    }
+
+    /// Keeps the leaves and recomputes every internal node from them, e.g. after loading nodes
+    /// whose interior is untrusted. Root observers are notified if the root changes.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new root, or `MerkleError::InvalidBytes` without changing the
+    ///   tree if a leaf is not a valid node, since corrupt leaves can't be repaired.
+    pub fn repair(&mut self) -> Result<String, MerkleError> {
+        let leaf_count = self.num_leaves();
+        for leaf in &self.nodes[self.nodes.len() - leaf_count..] {
+            validate_leaf(leaf, H::DIGEST_BYTES)?;
+        }
+        let old_root = self.root();
+        build_internal_nodes(&self.hasher, &mut self.nodes)?;
+        self.invalidate_proofs();
+        self.clear_undo();
+        self.notify_root_change(&old_root);
+        Ok(self.root())
+    }
+
+    /// Sets every leaf back to `initial_leaf`, or to the tree's own `initial_leaf()` if none is
+    /// given, keeping the depth, hasher and settings of the tree. The append position goes back
+    /// to the first leaf, the undo history is cleared and root observers are notified if the
+    /// root changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_leaf` - The new value for every leaf, which becomes the tree's `initial_leaf()`.
+    ///   Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success, or `MerkleError::InvalidBytes` without changing the tree.
+    pub fn reset_to_initial(&mut self, initial_leaf: Option<&str>) -> Result<(), MerkleError> {
+        let initial_leaf = match initial_leaf {
+            Some(leaf) => normalize_node(leaf, H::DIGEST_BYTES)?.to_string(),
+            None => self.initial_leaf.clone(),
+        };
+        if !self.nodes.is_empty() {
+            let nodes = uniform_nodes(&self.hasher, self.nodes.len(), &initial_leaf)?;
+            self.restore_state(nodes, 0, None);
+        }
+        self.initial_leaf = initial_leaf;
+        Ok(())
+    }
+
+    /// Recomputes every internal node of the subtree rooted at `(root_depth, root_offset)` from
+    /// the subtree's current leaves upward. Nodes above the subtree root are left as they are,
+    /// so callers updating several subtrees recompute their common ancestors afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `root_depth` - The level of the subtree root, 0 being the root of the tree.
+    /// * `root_offset` - The position of the subtree root within its level.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success, or `MerkleError::InvalidIndex` if the coordinates are out of
+    ///   range or point to a leaf.
+    pub fn recompute_subtree(
+        &mut self,
+        root_depth: usize,
+        root_offset: usize,
+    ) -> Result<(), MerkleError> {
+        if root_depth + 1 >= self.depth() || root_offset >= 1 << root_depth {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let old_root = self.root();
+        for depth in (root_depth..self.depth() - 1).rev() {
+            let shift = depth - root_depth;
+            for offset in root_offset << shift..(root_offset + 1) << shift {
+                let index = depth_offset_to_index(depth, offset)?;
+                let left = left_child_index(index)?;
+                self.nodes[index] =
+                    hash_nodes(&self.hasher, &self.nodes[left], &self.nodes[left + 1])?;
+            }
+        }
+        self.invalidate_proofs();
+        self.clear_undo();
+        self.notify_root_change(&old_root);
+        Ok(())
+    }
+
+    // compares each internal node in `indices` with the hash of its stored children
+    fn check_nodes(&self, indices: impl IntoIterator<Item = usize>) -> Result<(), IntegrityError> {
+        let mut error = IntegrityError {
+            mismatches: Vec::new(),
+            total: 0,
+        };
+        for index in indices {
+            let left = left_child_index(index).expect("internal nodes have children");
+            let children_valid = [left, left + 1]
+                .iter()
+                .all(|child| validate_leaf(&self.nodes[*child], H::DIGEST_BYTES).is_ok());
+            let expected = if children_valid {
+                hash_nodes(&self.hasher, &self.nodes[left], &self.nodes[left + 1]).ok()
+            } else {
+                None
+            };
+            if expected.as_ref() == Some(&self.nodes[index]) {
+                continue;
+            }
+            error.total += 1;
+            if error.mismatches.len() < IntegrityError::MAX_REPORTED_MISMATCHES {
+                error.mismatches.push(NodeMismatch {
+                    index,
+                    expected,
+                    found: self.nodes[index].clone(),
+                });
+            }
+        }
+        match error.total {
+            0 => Ok(()),
+            _ => Err(error),
+        }
+    }
+
+    /// Iterates over every node breadth-first: the root, then each level left to right.
+    ///
+    /// # Returns
+    ///
+    /// * Iterator of `(depth, offset, hash)` triples, with depth 0 being the root.
+    pub fn iter_nodes_bfs(&self) -> impl Iterator<Item = (usize, usize, &str)> + '_ {
+        self.nodes.iter().enumerate().map(|(index, node)| {
+            let (depth, offset) = index_to_depth_offset(index);
+            (depth, offset, node.as_str())
+        })
+    }
+
+    /// Iterates over every node depth-first in pre-order: each node before its left subtree,
+    /// then its right subtree.
+    ///
+    /// # Returns
+    ///
+    /// * Iterator of `(depth, offset, hash)` triples, with depth 0 being the root.
+    pub fn iter_nodes_dfs_preorder(&self) -> impl Iterator<Item = (usize, usize, &str)> + '_ {
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        std::iter::from_fn(move || {
+            let index = stack.pop()?;
+            if let Some(left) = left_child_index(index)
+                .ok()
+                .filter(|left| *left < self.nodes.len())
+            {
+                stack.push(left + 1);
+                stack.push(left);
+            }
+            let (depth, offset) = index_to_depth_offset(index);
+            Some((depth, offset, self.nodes[index].as_str()))
+        })
+    }
+
+    /// Renders the top `max_depth + 1` levels of the tree as an indented ASCII diagram for
+    /// debugging. Each node is a line `[depth,offset] prefix`, where `prefix` is the first 8 hex
+    /// characters of its hash, indented two spaces per level. The subtrees below `max_depth`
+    /// are elided to a single `[...]` line, so `max_depth == 0` shows only the root and `[...]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_depth` - The deepest level (0 being the root) to draw.
+    ///
+    /// # Returns
+    ///
+    /// * The diagram, one line per node. Empty for an empty tree.
+    pub fn draw_ascii(&self, max_depth: usize) -> String {
+        let mut diagram = String::new();
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(index) = stack.pop() {
+            let (depth, offset) = index_to_depth_offset(index);
+            let node = &self.nodes[index];
+            let prefix = &node[2..node.len().min(10)];
+            diagram.push_str(&format!(
+                "{}[{},{}] {}\n",
+                "  ".repeat(depth),
+                depth,
+                offset,
+                prefix
+            ));
+
+            let Some(left) = left_child_index(index)
+                .ok()
+                .filter(|left| *left < self.nodes.len())
+            else {
+                continue;
+            };
+            if depth == max_depth {
+                diagram.push_str(&format!("{}[...]\n", "  ".repeat(depth + 1)));
+            } else {
+                stack.push(left + 1);
+                stack.push(left);
+            }
+        }
+        diagram
+    }
+
+    /// Returns the array indices of the nodes on the path from `leaf_index` up to, but not
+    /// including, the root. Ordered bottom-up, so entry `i` is the node proven at proof step `i`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf to walk up from.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the path indices or `MerkleError::InvalidIndex`.
+    pub fn path_nodes(&self, leaf_index: usize) -> Result<Vec<usize>, MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+
+        let mut path = Vec::new();
+        let mut index = leaf_index + self.nodes.len() - leaf_count;
+        while let Some(parent_index) = parent_index(index) {
+            path.push(index);
+            index = parent_index;
+        }
+        Ok(path)
+    }
+
+    /// Returns the array indices of the internal nodes that updating `leaf_indices` recomputes,
+    /// i.e. the union of their paths to the root without the leaves themselves, so a storage
+    /// backend can prefetch them before a batch update.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_indices` - The 0 indexed leaves that will change. Duplicates are allowed.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the node indices in ascending order, or `MerkleError::InvalidIndex`.
+    pub fn dirty_ancestor_indices(
+        &self,
+        leaf_indices: &[usize],
+    ) -> Result<Vec<usize>, MerkleError> {
+        let leaf_count = self.num_leaves();
+        let mut dirty = BTreeSet::new();
+        for &leaf_index in leaf_indices {
+            if leaf_index >= leaf_count {
+                return Err(MerkleError::InvalidIndex);
+            }
+            let mut index = leaf_index + self.nodes.len() - leaf_count;
+            // paths merge, so the rest of this one is already dirty once a parent is
+            while let Some(parent) = parent_index(index) {
+                if !dirty.insert(parent) {
+                    break;
+                }
+                index = parent;
+            }
+        }
+        Ok(dirty.into_iter().collect())
+    }
+
+    /// Returns the array indices of the siblings used by `proof(leaf_index)`, in the same
+    /// bottom-up order as the proof steps and `path_nodes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want the proof sibling indices for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the sibling indices or `MerkleError::InvalidIndex`.
+    pub fn proof_sibling_indices(&self, leaf_index: usize) -> Result<Vec<usize>, MerkleError> {
+        Ok(self
+            .path_nodes(leaf_index)?
+            .into_iter()
+            .map(|index| {
+                if self.is_left_child(index) {
+                    index + 1
+                } else {
+                    index - 1
+                }
+            })
+            .collect())
+    }
+
+    /// Counts how many sibling positions the individual proofs for `leaf_indices` have in common:
+    /// the total number of sibling positions across the proofs minus the number of distinct
+    /// ones. An estimate of what a multi-proof saves; no proofs are generated. Out of range
+    /// indices are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_indices` - The 0 indexed leaves that would be proven.
+    ///
+    /// # Returns
+    ///
+    /// * The number of shared sibling positions, 0 for fewer than two leaves.
+    pub fn count_proof_nodes_shared(&self, leaf_indices: &[usize]) -> usize {
+        let mut total = 0;
+        let mut distinct = BTreeSet::new();
+        for &leaf_index in leaf_indices {
+            if let Ok(siblings) = self.proof_sibling_indices(leaf_index) {
+                total += siblings.len();
+                distinct.extend(siblings);
+            }
+        }
+        total - distinct.len()
+    }
+
+    /// Returns the positions `proof(leaf_index)` reads its siblings from: per step, bottom-up,
+    /// the sibling's array index and the direction of the proven node, as in the proof step.
+    /// Lets callers fetch the siblings from a separate node store instead of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want the proof positions for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the `(sibling_index, direction)` pairs or `MerkleError::InvalidIndex`.
+    pub fn proof_positions(
+        &self,
+        leaf_index: usize,
+    ) -> Result<Vec<(usize, Direction)>, MerkleError> {
+        Ok(self
+            .path_nodes(leaf_index)?
+            .into_iter()
+            .map(|index| {
+                if self.is_left_child(index) {
+                    (index + 1, Direction::Left)
+                } else {
+                    (index - 1, Direction::Right)
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the siblings of `proof(leaf_index)` addressed by coordinate instead of direction:
+    /// per step, bottom-up, the sibling's zero indexed `(depth, offset)` and its value, for
+    /// coordinate-addressed verifiers.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the `(depth, offset, sibling)` triples or `MerkleError::InvalidIndex`.
+    pub fn coordinate_proof(
+        &self,
+        leaf_index: usize,
+    ) -> Result<Vec<(usize, usize, String)>, MerkleError> {
+        Ok(self
+            .proof_positions(leaf_index)?
+            .into_iter()
+            .map(|(index, _)| {
+                let (depth, offset) = index_to_depth_offset(index);
+                (depth, offset, self.nodes[index].clone())
+            })
+            .collect())
+    }
+
+    /// Checks that `expected` is the root of this tree. Surrounding whitespace and the case of
+    /// the hex digits are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `expected` - The root the tree should have, e.g. from a reference implementation.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success, `MerkleError::RootMismatch` with both roots, or
+    ///   `MerkleError::InvalidBytes` if `expected` is not a node.
+    pub fn assert_root(&self, expected: &str) -> Result<(), MerkleError> {
+        let expected = normalize_node(expected, H::DIGEST_BYTES)?;
+        let actual = self.root();
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(MerkleError::RootMismatch {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that `proof` has one step per level below the root of this tree, i.e. that it was
+    /// generated for a tree of the same depth.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to check.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or `MerkleError::ProofLengthMismatch`.
+    pub fn validate_proof_length(&self, proof: &[ProofStep]) -> Result<(), MerkleError> {
+        check_proof_length(proof, self.depth())
+    }
+
+    /// Checks that `proof` proves the inclusion of `leaf_value` in this tree. Unlike `verify`, a proof
+    /// generated for a tree of a different depth is rejected with an error instead of producing a
+    /// wrong root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing whether the proof matches the root, compared ignoring case, or
+    ///   `MerkleError::ProofLengthMismatch`.
+    pub fn verify_inclusion(
+        &self,
+        proof: &[ProofStep],
+        leaf_value: &str,
+    ) -> Result<bool, MerkleError> {
+        self.validate_proof_length(proof)?;
+        Ok(fold_proof(&self.hasher, proof, leaf_value.to_string())?
+            .eq_ignore_ascii_case(&self.root()))
+    }
+
+    /// Refreshes a previously generated `proof` for `proof_leaf` after `changed_leaf` was `set`.
+    /// Only the sibling at the level just below the two leaves' lowest common ancestor can change,
+    /// so that single step is updated in place instead of regenerating the proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof for `proof_leaf`, generated before `changed_leaf` was updated.
+    /// * `proof_leaf` - 0 indexed leaf the proof belongs to.
+    /// * `changed_leaf` - 0 indexed leaf that was updated.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or `MerkleError::InvalidIndex`.
+    pub fn patch_proof(
+        &self,
+        proof: &mut [ProofStep],
+        proof_leaf: usize,
+        changed_leaf: usize,
+    ) -> Result<(), MerkleError> {
+        let leaf_count = self.num_leaves();
+        if proof_leaf >= leaf_count || changed_leaf >= leaf_count {
+            return Err(MerkleError::InvalidIndex);
+        }
+
+        let offset = self.nodes.len() - leaf_count;
+        let mut proof_index = proof_leaf + offset;
+        let mut changed_index = changed_leaf + offset;
+        let mut level = 0;
+        while proof_index != changed_index {
+            if parent_index(proof_index) == parent_index(changed_index) {
+                let step = proof.get_mut(level).ok_or(MerkleError::InvalidIndex)?;
+                step.sibling = self.nodes[changed_index].clone();
+                break;
+            }
+            proof_index = (proof_index - 1) / 2;
+            changed_index = (changed_index - 1) / 2;
+            level += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<String, H> {
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree with leaf values as initial_leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be `H::DIGEST_BYTES` hex string starting with '0x'
+    /// * `hasher` - hash function the tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree
+    pub fn with_hasher(depth: usize, initial_leaf: &str, hasher: H) -> Result<Self, MerkleError> {
+        MerkleTree::with_hasher_and_config(depth, initial_leaf, hasher, TreeConfig::default())
+    }
+
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree with leaf values
+    /// as initial_leaf, whose depth is limited by `config` instead of `MAX_DEPTH`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be `H::DIGEST_BYTES` hex string starting with '0x'
+    /// * `hasher` - hash function the tree combines nodes with.
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree, or `MerkleError::MaxDepthExceeded` beyond the configured depth limit.
+    pub fn with_hasher_and_config(
+        depth: usize,
+        initial_leaf: &str,
+        hasher: H,
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        let total_nodes = tree_size(depth, config.depth_limit())?;
+        validate_leaf(initial_leaf, H::DIGEST_BYTES)?;
+        if total_nodes == 0 {
+            let mut tree = MerkleTree::empty_with_hasher(hasher).with_initial_leaf(initial_leaf);
+            tree.max_depth = config.depth_limit();
+            return Ok(tree);
+        }
+        let nodes = uniform_nodes(&hasher, total_nodes, initial_leaf)?;
+        let mut tree = MerkleTree::with_nodes(nodes, hasher).with_initial_leaf(initial_leaf);
+        tree.max_depth = config.depth_limit();
+        Ok(tree)
+    }
+
+    /// Constructs a merkle tree with no leaves, whose root is the hash of the empty string.
+    ///
+    /// # Arguments
+    ///
+    /// * `hasher` - hash function the tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * A new, empty MerkleTree
+    pub fn empty_with_hasher(hasher: H) -> Self {
+        MerkleTree::with_nodes(Vec::new(), hasher)
+    }
+
+    /// Constructs a merkle tree whose leaves are `leaves`, in order. If the number of leaves is not a
+    /// power of two the remaining leaves are padded with the all zero leaf. No leaves produce an
+    /// empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf values. Each must be `H::DIGEST_BYTES` hex string starting with `0x`
+    /// * `hasher` - hash function the tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_leaves_with_hasher(leaves: &[&str], hasher: H) -> Result<Self, MerkleError> {
+        MerkleTree::from_leaves_with_hasher_and_config(leaves, hasher, TreeConfig::default())
+    }
+
+    /// Constructs a merkle tree whose leaves are `leaves`, in order, with the depth limit and
+    /// leaf size of `config`. If the number of leaves is not a power of two the remaining leaves
+    /// are padded with the all zero leaf. No leaves produce an empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf values. Each must be `0x` prefixed hex of `config.leaf_size()`
+    ///   bytes, or `H::DIGEST_BYTES` if it has none.
+    /// * `hasher` - hash function the tree combines nodes with, and hashes sized leaves with.
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree, `MerkleError::InvalidBytes` for a leaf of the
+    ///   wrong size, or `MerkleError::MaxDepthExceeded` beyond the configured depth limit.
+    pub fn from_leaves_with_hasher_and_config(
+        leaves: &[&str],
+        hasher: H,
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        if leaves.is_empty() {
+            let mut tree = MerkleTree::with_nodes(Vec::new(), hasher);
+            tree.max_depth = config.depth_limit();
+            return Ok(tree);
+        }
+        let leaf_count = leaves.len().next_power_of_two();
+        check_depth(
+            leaf_count.trailing_zeros() as usize + 1,
+            config.depth_limit(),
+        )?;
+
+        let mut nodes = vec![String::new(); leaf_count - 1];
+        for leaf in leaves {
+            match config.leaf_size() {
+                None => {
+                    validate_leaf(leaf, H::DIGEST_BYTES)?;
+                    nodes.push(leaf.to_string());
+                }
+                Some(leaf_bytes) => {
+                    validate_leaf(leaf, leaf_bytes)?;
+                    let node = hasher.digest(&hex::decode(&leaf[2..])?);
+                    nodes.push(format!("0x{}", hex::encode(node)));
+                }
+            }
+        }
+        nodes.resize(2 * leaf_count - 1, zero_leaf(H::DIGEST_BYTES));
+        build_internal_nodes(&hasher, &mut nodes)?;
+
+        let mut tree = MerkleTree::with_nodes(nodes, hasher);
+        tree.next_leaf = leaves.len();
+        tree.max_depth = config.depth_limit();
+        Ok(tree)
+    }
+
+    /// Given a `proof` and leaf_value, calculates and returns the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - `Vec<ProofStep>` containing the proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be `H::DIGEST_BYTES` hex string with `0x` prefix.
+    /// * `hasher` - hash function the proof's tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root of the tree or Error.
+    pub fn verify_with_hasher(
+        proof: &[ProofStep],
+        leaf_value: String,
+        hasher: &H,
+    ) -> Result<String, MerkleError> {
+        fold_proof(hasher, proof, leaf_value)
+    }
+}
+
+impl MerkleTree {
+    /// the largest depth (one indexed) a tree can be constructed with, see `MAX_DEPTH`
+    pub const MAX_DEPTH: usize = MAX_DEPTH;
+
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree with leaf values as initial_leaf.
+    /// An alias of `with_levels`: `depth` counts the levels including the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree
+    pub fn new(depth: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
+        MerkleTree::with_levels(depth, initial_leaf)
+    }
+
+    /// Given the number of `levels` including the root, constructs a merkle tree with leaf values
+    /// as initial_leaf. Ex: 3 levels are the root, its two children and four leaves, so a tree
+    /// of `levels` has `2^(levels - 1)` leaves. 0 levels is the empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `levels` - The number of levels of the tree, counting the root and the leaves.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree, or `MerkleError::MaxDepthExceeded` beyond `MAX_DEPTH` levels.
+    pub fn with_levels(levels: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
+        debug_self_test();
+        MerkleTree::with_hasher(levels, initial_leaf, Sha3_256Hasher)
+    }
+
+    /// Given the `height` of the root above the leaves, constructs a merkle tree with leaf values
+    /// as initial_leaf. Ex: height 2 is two edges from the root down to a leaf, so the tree has
+    /// the root, its two children and four leaves, and a tree of `height` has `2^height` leaves.
+    /// It is the tree `with_levels(height + 1)` builds.
+    ///
+    /// # Arguments
+    ///
+    /// * `height` - The number of edges on a path from the root to a leaf.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree, or `MerkleError::MaxDepthExceeded` beyond a height of `MAX_DEPTH - 1`.
+    pub fn with_height(height: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
+        MerkleTree::with_levels(height.saturating_add(1), initial_leaf)
+    }
+
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree with leaf values
+    /// as initial_leaf, whose depth is limited by `config` instead of `MAX_DEPTH`. The limit also
+    /// applies when the tree grows.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value to be assinged to the leaves. must be 32 bit hex string starting with '0x'
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree, or `MerkleError::MaxDepthExceeded` beyond the configured depth limit.
+    pub fn with_config(
+        depth: usize,
+        initial_leaf: &str,
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        debug_self_test();
+        MerkleTree::with_hasher_and_config(depth, initial_leaf, Sha3_256Hasher, config)
+    }
+
+    /// Given `depth` (one indexed), constructs a merkle tree whose leaves are all `ZERO_LEAF`. Equivalent
+    /// to `MerkleTree::new(depth, ZERO_LEAF)`, but every level is filled from `ZERO_HASHES` so no
+    /// hashing happens. Depth 0 produces an empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or `MerkleError::MaxDepthExceeded`.
+    pub fn new_with_precomputed_zeros(depth: usize) -> Result<Self, MerkleError> {
+        let mut nodes = Vec::with_capacity(tree_size(depth, MAX_DEPTH)?);
+        for d in 0..depth {
+            let level_hash = &ZERO_HASHES[depth - 1 - d];
+            nodes.resize(nodes.len() + (1 << d), level_hash.clone());
+        }
+        Ok(MerkleTree::with_nodes(nodes, Sha3_256Hasher))
+    }
+
+    /// Returns the root of `MerkleTree::new(depth, initial_leaf)` without building the tree,
+    /// hashing once per level. Depth 0 is the empty tree, whose root is `EMPTY_ROOT`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root or Error.
+    pub fn default_root(depth: usize, initial_leaf: &str) -> Result<String, MerkleError> {
+        match MerkleTree::default_hashes(depth, initial_leaf)?.pop() {
+            Some(root) => Ok(root),
+            None => Ok(EMPTY_ROOT.to_string()),
+        }
+    }
+
+    /// Returns the root of `MerkleTree::new(depth, ZERO_LEAF)`, a well-known reference value, read
+    /// from `ZERO_HASHES` without hashing or building the tree. Depth 0 is the empty tree, whose
+    /// root is `EMPTY_ROOT`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root or `MerkleError::MaxDepthExceeded`.
+    pub fn zero_root(depth: usize) -> Result<String, MerkleError> {
+        match depth {
+            0 => Ok(EMPTY_ROOT.to_string()),
+            depth => {
+                check_depth(depth, MAX_DEPTH)?;
+                Ok(ZERO_HASHES[depth - 1].clone())
+            }
+        }
+    }
+
+    /// Returns the proof `MerkleTree::new(depth, initial_leaf).proof(leaf_index)` would produce
+    /// without building the tree: each sibling is the default hash of its level and the
+    /// directions follow the bits of `leaf_index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    /// * `initial_leaf` - value of every leaf. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps, `MerkleError::InvalidIndex` if the tree has no leaf
+    ///   `leaf_index`, or Error.
+    pub fn default_proof(
+        depth: usize,
+        leaf_index: usize,
+        initial_leaf: &str,
+    ) -> Result<Vec<ProofStep>, MerkleError> {
+        let defaults = MerkleTree::default_hashes(depth, initial_leaf)?;
+        if depth == 0 || leaf_index >= 1 << (depth - 1) {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Ok(defaults[..depth - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, sibling)| {
+                let direction = match (leaf_index >> level) & 1 {
+                    0 => Direction::Left,
+                    _ => Direction::Right,
+                };
+                ProofStep::new(direction, sibling.clone())
+            })
+            .collect())
+    }
+
+    // the hash of every level of an all `initial_leaf` tree, from the leaves up to the root
+    fn default_hashes(depth: usize, initial_leaf: &str) -> Result<Vec<String>, MerkleError> {
+        check_depth(depth, MAX_DEPTH)?;
+        validate_leaf(initial_leaf, DIGEST_BYTES)?;
+        let mut hashes: Vec<String> = Vec::with_capacity(depth);
+        for level in 0..depth {
+            let hash = match level {
+                0 => initial_leaf.to_string(),
+                _ => hash_nodes(
+                    &Sha3_256Hasher::<DIGEST_BYTES>,
+                    &hashes[level - 1],
+                    &hashes[level - 1],
+                )?,
+            };
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Constructs a merkle tree with no leaves, whose `root()` is `EMPTY_ROOT`. It can be grown
+    /// with `append`.
+    ///
+    /// # Returns
+    ///
+    /// * A new, empty MerkleTree
+    pub fn empty() -> Self {
+        MerkleTree::empty_with_hasher(Sha3_256Hasher)
+    }
+
+    /// Constructs a merkle tree whose leaves are `leaves`, in order. If the number of leaves is not a
+    /// power of two the remaining leaves are padded with `ZERO_LEAF`. No leaves produce an
+    /// empty tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf values. Each must be 32 bit hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_leaves(leaves: &[&str]) -> Result<Self, MerkleError> {
+        MerkleTree::from_leaves_with_hasher(leaves, Sha3_256Hasher)
+    }
+
+    /// Constructs a merkle tree whose leaves are `leaves`, in order, with the depth limit and
+    /// leaf size of `config`, see `from_leaves_with_hasher_and_config`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf values. Each must be `0x` prefixed hex of `config.leaf_size()`
+    ///   bytes, or 32 bytes if it has none.
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_leaves_with_config(
+        leaves: &[&str],
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        MerkleTree::from_leaves_with_hasher_and_config(leaves, Sha3_256Hasher, config)
+    }
+
+    /// Consumes the tree into its raw node bytes, breadth-first starting with the root. Together
+    /// with `from_raw_nodes` this is a low-level serialization pair for byte oriented storage.
+    ///
+    /// # Returns
+    ///
+    /// * The `2^depth - 1` nodes of the tree.
+    pub fn into_raw_nodes(self) -> Vec<[u8; DIGEST_BYTES]> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let mut raw = [0u8; DIGEST_BYTES];
+                // every write validates the node, so all of them are 32 byte hex
+                hex::decode_to_slice(&node[2..], &mut raw).expect("nodes are 32 byte hex");
+                raw
+            })
+            .collect()
+    }
+
+    /// Constructs a merkle tree from raw node bytes produced by `into_raw_nodes`, without
+    /// recomputing any hashes. The nodes are trusted to be consistent.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - Every node of the tree breadth-first, starting with the root.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the MerkleTree, or `MerkleError::SerializationError` if the number of
+    ///   nodes is not `2^k - 1`.
+    pub fn from_raw_nodes(nodes: Vec<[u8; DIGEST_BYTES]>) -> Result<Self, MerkleError> {
+        if !(nodes.len() + 1).is_power_of_two() {
+            return Err(MerkleError::SerializationError(format!(
+                "{} nodes do not form a complete tree",
+                nodes.len()
+            )));
+        }
+        check_depth((nodes.len() + 1).trailing_zeros() as usize, MAX_DEPTH)?;
+        let nodes = nodes
+            .iter()
+            .map(|node| format!("0x{}", hex::encode(node)))
+            .collect();
+        Ok(MerkleTree::with_nodes(nodes, Sha3_256Hasher))
+    }
+
+    /// Given a `proof` and leaf_value, calculates and returns the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - `Vec<ProofStep>` containing the proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root of the tree or Error.
+    pub fn verify(proof: &[ProofStep], leaf_value: String) -> Result<String, MerkleError> {
+        MerkleTree::verify_with_hasher(proof, leaf_value, &Sha3_256Hasher::<DIGEST_BYTES>)
+    }
+
+    /// Checks that `proof` proves the inclusion of `leaf_value` under `root` in a tree of depth
+    /// `depth`, rejecting a proof of any other length instead of producing a wrong root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    /// * `root` - The expected root of the tree.
+    /// * `depth` - The depth (one indexed) of the tree the proof was generated for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing whether the proof matches the root, compared ignoring case and
+    ///   surrounding whitespace, `MerkleError::ProofLengthMismatch`, or `MerkleError::InvalidBytes`
+    ///   if `root` is not a valid node.
+    pub fn verify_against_root(
+        proof: &[ProofStep],
+        leaf_value: &str,
+        root: &str,
+        depth: usize,
+    ) -> Result<bool, MerkleError> {
+        check_proof_length(proof, depth)?;
+        let root = normalize_node(root, DIGEST_BYTES)?;
+        Ok(MerkleTree::verify(proof, leaf_value.to_string())?.eq_ignore_ascii_case(root))
+    }
+}
+
+#[test]
+fn test_merkle_tree_depth_20() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::new(20, initial_leaf).unwrap();
+    assert_eq!(
+        tree.root(),
+        "0xd4490f4d374ca8a44685fe9471c5b8dbe58cdffd13d30d9aba15dd29efb92930"
+    );
+}
+
+#[test]
+fn test_zero_root() {
+    assert_eq!(
+        MerkleTree::zero_root(20).unwrap(),
+        MerkleTree::new(20, ZERO_LEAF).unwrap().root()
+    );
+    assert_eq!(
+        MerkleTree::zero_root(7).unwrap(),
+        MerkleTree::default_root(7, ZERO_LEAF).unwrap()
+    );
+    assert_eq!(MerkleTree::zero_root(1).unwrap(), ZERO_LEAF);
+    assert_eq!(MerkleTree::zero_root(0).unwrap(), EMPTY_ROOT);
+    assert_eq!(
+        MerkleTree::zero_root(MAX_DEPTH + 1),
+        Err(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
+    );
+}
+
+#[test]
+fn test_default_root_and_proof() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let root = "0xd4490f4d374ca8a44685fe9471c5b8dbe58cdffd13d30d9aba15dd29efb92930";
+    assert_eq!(MerkleTree::default_root(20, initial_leaf).unwrap(), root);
+
+    let proof = MerkleTree::default_proof(20, 12345, initial_leaf).unwrap();
+    assert_eq!(proof.len(), 19);
+    assert_eq!(
+        MerkleTree::verify(&proof, initial_leaf.to_string()).unwrap(),
+        root
+    );
+
+    let tree = MerkleTree::new(4, initial_leaf).unwrap();
+    for (step, expected) in MerkleTree::default_proof(4, 5, initial_leaf)
+        .unwrap()
+        .iter()
+        .zip(tree.proof(5).unwrap().iter())
+    {
+        assert_eq!(step.direction(), expected.direction());
+        assert_eq!(step.sibling(), expected.sibling());
+    }
+
+    assert_eq!(
+        MerkleTree::default_root(0, initial_leaf).unwrap(),
+        EMPTY_ROOT
+    );
+    assert_eq!(
+        MerkleTree::default_proof(4, 8, initial_leaf).err(),
+        Some(MerkleError::InvalidIndex)
+    );
+    assert_eq!(
+        MerkleTree::default_root(MAX_DEPTH + 1, initial_leaf).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
+    );
+}
+
+#[test]
+fn test_proof_with_leaf() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+
+    let (leaf, proof) = tree.proof_with_leaf(6).unwrap();
+    assert_eq!(leaf, leaves[6]);
+    assert_eq!(proof, tree.proof(6).unwrap());
+    assert_eq!(MerkleTree::verify(&proof, leaf).unwrap(), tree.root());
+    assert_eq!(tree.proof_with_leaf(8), Err(MerkleError::InvalidIndex));
+}
+
+#[test]
+fn test_commit_to_all_leaves() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let mut tree = MerkleTree::from_leaves(&leaves).unwrap();
+    tree.enable_proof_cache(4);
+
+    let commitments = tree.commit_to_all_leaves();
+    assert_eq!(commitments.len(), leaves.len());
+    assert_eq!(tree.proof_cache_len(), 0);
+    assert_eq!(tree.proof_cache_misses(), 0);
+    for (leaf_index, (leaf, proof)) in &commitments {
+        assert_eq!(leaf, leaves[*leaf_index]);
+        assert_eq!(*proof, tree.proof(*leaf_index).unwrap());
+        assert_eq!(
+            MerkleTree::verify(proof, leaf.clone()).unwrap(),
+            tree.root()
+        );
+    }
+    assert!(MerkleTree::empty().commit_to_all_leaves().is_empty());
+}
+
+#[test]
+fn test_get_authentication_path() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+
+    let leaf_index = 6;
+    let path = tree.get_authentication_path(leaf_index).unwrap();
+    assert_eq!(path.len(), tree.proof_len());
+
+    // re-derive the directions from the index, as a verifier would
+    let proof: Vec<ProofStep> = path
+        .into_iter()
+        .enumerate()
+        .map(|(level, sibling)| match (leaf_index >> level) & 1 {
+            0 => ProofStep::new(Direction::Left, sibling),
+            _ => ProofStep::new(Direction::Right, sibling),
+        })
+        .collect();
+    assert_eq!(
+        MerkleTree::verify(&proof, leaves[leaf_index].to_string()).unwrap(),
+        tree.root()
+    );
+    assert_eq!(
+        tree.get_authentication_path(8),
+        Err(MerkleError::InvalidIndex)
+    );
+}
+
+#[test]
+fn test_memory_bytes() {
+    let tree = MerkleTree::new(10, ZERO_LEAF).unwrap();
+    let num_nodes = 1023;
+    let per_node = ZERO_LEAF.len() + std::mem::size_of::<String>();
+    let estimate = tree.memory_bytes();
+    assert!(estimate >= num_nodes * per_node);
+    assert!(estimate <= 2 * num_nodes * per_node);
+    assert_eq!(MerkleTree::empty().memory_bytes(), 0);
+}
+
+#[test]
+fn test_merkle_tree_full() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::new(3, initial_leaf).unwrap();
+    for i in 3..7 {
+        assert_eq!(
+            &tree.nodes[i],
+            "0xabababababababababababababababababababababababababababababababab"
+        )
+    }
+    for i in 1..3 {
+        assert_eq!(
+            &tree.nodes[i],
+            "0x699fc94ff1ec83f1abf531030e324003e7758298281645245f7c698425a5e0e7"
+        )
+    }
+    assert_eq!(
+        tree.root(),
+        "0xa2422433244a1da24b3c4db126dcc593666f98365403e6aaf07fae011c824f09"
+    );
+}
+
+#[test]
+fn test_merkle_tree_set() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let mut tree = MerkleTree::new(2, initial_leaf).unwrap();
+    assert_eq!(tree.nodes.len(), 3);
+    assert_eq!(
+        tree.root(),
+        "0x699fc94ff1ec83f1abf531030e324003e7758298281645245f7c698425a5e0e7"
+    );
+    tree.set(
+        0,
+        "0xabababababababababababababababababababababababababababababababcd",
+    )
+    .unwrap();
+    assert_eq!(
+        &tree.nodes[1],
+        "0xabababababababababababababababababababababababababababababababcd"
+    );
+    assert_eq!(
+        tree.root(),
+        "0x1e69d9c46ca7065c20d7a9bb407f574fd92fd862f69cb96e1146926c8f198e81"
+    )
+}
+
+#[test]
+fn test_merkle_tree_set_higher_depth() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababcd";
+    let mut tree = MerkleTree::new(10, initial_leaf).unwrap();
+    for i in 0..tree.num_leaves() {
+        tree.set(
+            i,
+            "0xabababababababababababababababababababababababababababababababab",
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        tree.root(),
+        "0xc795494aa662dd012c5de6c52f0ab28ee9135fe846074d62bb7807cf98742fd9"
+    )
+}
+
+#[test]
+fn test_proof() {
+    let initial_leaf = "0x0000000000000000000000000000000000000000000000000000000000000000";
+    let mut tree = MerkleTree::new(5, initial_leaf).unwrap();
+    let num_leaves = tree.num_leaves();
+
+    let multiplier = BigUint::parse_bytes(
+        b"1111111111111111111111111111111111111111111111111111111111111111",
+        16,
+    )
+    .expect("Failed to parse hex string to BigInt");
+
+    for i in 0..num_leaves {
+        let product = BigUint::from_usize(i).unwrap() * &multiplier;
+        let value = format!("0x{:064x}", product);
+        tree.set(i, &value).unwrap();
+    }
+
+    assert_eq!(
+        tree.root(),
+        "0x57054e43fa56333fd51343b09460d48b9204999c376624f52480c5593b91eff4"
+    );
+
+    let proof = tree.proof(3).unwrap();
+
+    let expected_proof = [
+        ProofStep {
+            direction: Direction::Right,
+            sibling: "0x2222222222222222222222222222222222222222222222222222222222222222"
+                .to_string(),
+        },
+        ProofStep {
+            direction: Direction::Right,
+            sibling: "0x35e794f1b42c224a8e390ce37e141a8d74aa53e151c1d1b9a03f88c65adb9e10"
+                .to_string(),
+        },
+        ProofStep {
+            direction: Direction::Left,
+            sibling: "0x26fca7737f48fa702664c8b468e34c858e62f51762386bd0bddaa7050e0dd7c0"
+                .to_string(),
+        },
+        ProofStep {
+            direction: Direction::Left,
+            sibling: "0xe7e11a86a0c1d8d8624b1629cb58e39bb4d0364cb8cb33c4029662ab30336858"
+                .to_string(),
+        },
+    ];
+
+    assert_eq!(proof.len(), expected_proof.len());
+    for i in 0..proof.len() {
+        assert_eq!(proof[i].direction, expected_proof[i].direction);
+        assert_eq!(proof[i].sibling, expected_proof[i].sibling);
+    }
+}
+
+#[test]
+fn test_verify() {
+    let initial_leaf = "0x0000000000000000000000000000000000000000000000000000000000000000";
+    let mut tree = MerkleTree::new(5, initial_leaf).unwrap();
+    let num_leaves = tree.num_leaves();
+
+    let multiplier = BigUint::parse_bytes(
+        b"1111111111111111111111111111111111111111111111111111111111111111",
+        16,
+    )
+    .expect("Failed to parse hex string to BigInt");
+
+    for i in 0..num_leaves {
+        let product = BigUint::from_usize(i).unwrap() * &multiplier;
+        let value = format!("0x{:064x}", product);
+        tree.set(i, &value).unwrap();
+    }
+
+    let leaf_5_bigint = multiplier * BigUint::from(5u32);
+    let leaf_5_string = format!("0x{:064x}", leaf_5_bigint);
+
+    let root = tree.root();
+    let proof = tree.proof(5).unwrap();
+
+    assert_eq!(MerkleTree::verify(&proof, leaf_5_string).unwrap(), root);
+}
+
+#[test]
+fn test_patch_proof() {
+    let initial_leaf = "0x0000000000000000000000000000000000000000000000000000000000000000";
+    let mut tree = MerkleTree::new(5, initial_leaf).unwrap();
+    let leaf_3 = "0x3333333333333333333333333333333333333333333333333333333333333333";
+    tree.set(3, leaf_3).unwrap();
+
+    let mut proof = tree.proof(3).unwrap();
+    tree.set(
+        2,
+        "0x2222222222222222222222222222222222222222222222222222222222222222",
+    )
+    .unwrap();
+    assert_ne!(
+        MerkleTree::verify(&proof, leaf_3.to_string()).unwrap(),
+        tree.root()
+    );
+
+    tree.patch_proof(&mut proof, 3, 2).unwrap();
+    assert_eq!(
+        MerkleTree::verify(&proof, leaf_3.to_string()).unwrap(),
+        tree.root()
+    );
+
+    // a leaf in the other half of the tree only touches the top step
+    tree.set(
+        14,
+        "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+    )
+    .unwrap();
+    tree.patch_proof(&mut proof, 3, 14).unwrap();
+    assert_eq!(
+        MerkleTree::verify(&proof, leaf_3.to_string()).unwrap(),
+        tree.root()
+    );
+    assert_eq!(
+        tree.patch_proof(&mut proof, 3, 16),
+        Err(MerkleError::InvalidIndex)
+    );
+}
+
+#[test]
+fn test_from_leaves() {
+    let leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::from_leaves(&[leaf; 4]).unwrap();
+    assert_eq!(tree.root(), MerkleTree::new(3, leaf).unwrap().root());
+
+    // three leaves are padded up to four with the zero leaf
+    let padded = MerkleTree::from_leaves(&[leaf; 3]).unwrap();
+    let mut expected = MerkleTree::new(3, leaf).unwrap();
+    expected.set(3, ZERO_LEAF).unwrap();
+    assert_eq!(padded.num_leaves(), 4);
+    assert_eq!(padded.root(), expected.root());
+
+    assert_eq!(MerkleTree::from_leaves(&[]).unwrap().num_leaves(), 0);
+    assert_eq!(
+        MerkleTree::from_leaves(&[leaf, "0xabab"]).err(),
+        Some(MerkleError::InvalidBytes)
+    );
+}
+
+#[test]
+fn test_proof_sibling_indices() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::new(4, initial_leaf).unwrap();
+
+    assert_eq!(tree.path_nodes(5).unwrap(), vec![12, 5, 2]);
+    assert_eq!(tree.proof_sibling_indices(5).unwrap(), vec![11, 6, 1]);
+    assert_eq!(tree.proof_sibling_indices(0).unwrap(), vec![8, 4, 2]);
+
+    let proof = tree.proof(5).unwrap();
+    for (step, index) in proof.iter().zip(tree.proof_sibling_indices(5).unwrap()) {
+        assert_eq!(step.sibling, tree.nodes[index]);
+    }
+
+    assert_eq!(tree.path_nodes(8), Err(MerkleError::InvalidIndex));
+    assert_eq!(
+        tree.proof_sibling_indices(8),
+        Err(MerkleError::InvalidIndex)
+    );
+}
+
+#[test]
+fn test_count_proof_nodes_shared() {
+    let tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    assert_eq!(tree.count_proof_nodes_shared(&[]), 0);
+    assert_eq!(tree.count_proof_nodes_shared(&[3]), 0);
+    // leaves 0 and 1 are siblings, so only their siblings above the leaf level coincide
+    assert_eq!(tree.count_proof_nodes_shared(&[0, 1]), 2);
+    // leaves in different halves share nothing
+    assert_eq!(tree.count_proof_nodes_shared(&[0, 7]), 0);
+    assert_eq!(tree.count_proof_nodes_shared(&[0, 2, 3]), 1 + 2);
+    assert_eq!(tree.count_proof_nodes_shared(&[0, 1, 8]), 2);
+}
+
+#[test]
+fn test_proof_positions() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+
+    assert_eq!(
+        tree.proof_positions(5).unwrap(),
+        vec![
+            (11, Direction::Right),
+            (6, Direction::Left),
+            (1, Direction::Right)
+        ]
+    );
+    let proof = tree.proof(5).unwrap();
+    for (step, (index, direction)) in proof.iter().zip(tree.proof_positions(5).unwrap()) {
+        assert_eq!(step.sibling, tree.nodes[index]);
+        assert_eq!(step.direction, direction);
+    }
+    assert_eq!(tree.proof_positions(8), Err(MerkleError::InvalidIndex));
+}
+
+#[test]
+fn test_coordinate_proof() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+    assert_eq!(tree.depth(), 4);
+
+    for leaf_index in 0..8 {
+        let coordinates = tree.coordinate_proof(leaf_index).unwrap();
+        let positions = tree.proof_positions(leaf_index).unwrap();
+        assert_eq!(coordinates.len(), 3);
+        for ((depth, offset, sibling), (index, _)) in coordinates.iter().zip(positions) {
+            assert_eq!(depth_offset_to_index(*depth, *offset), Ok(index));
+            assert_eq!(*sibling, tree.nodes[index]);
+        }
+    }
+    let coordinates = tree.coordinate_proof(5).unwrap();
+    let coordinates: Vec<(usize, usize)> = coordinates.iter().map(|(d, o, _)| (*d, *o)).collect();
+    assert_eq!(coordinates, vec![(3, 4), (2, 3), (1, 0)]);
+    assert_eq!(tree.coordinate_proof(8), Err(MerkleError::InvalidIndex));
+}
+
+#[test]
+fn test_depth() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    for depth in 1..=10 {
+        let tree = MerkleTree::new(depth, initial_leaf).unwrap();
+        assert_eq!(tree.depth(), depth);
+        assert!(!tree.is_at_max_depth());
+    }
+    assert_eq!(MerkleTree::MAX_DEPTH, 30);
+    assert_eq!(
+        MerkleTree::new(MerkleTree::MAX_DEPTH + 1, initial_leaf).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
+    );
+}
+
+#[test]
+fn test_verify_inclusion_length_mismatch() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::new(4, initial_leaf).unwrap();
+    let proof = tree.proof(2).unwrap();
+    assert_eq!(tree.proof_len(), 3);
+    assert!(tree.verify_inclusion(&proof, initial_leaf).unwrap());
+
+    let short = MerkleTree::new(3, initial_leaf).unwrap().proof(2).unwrap();
+    assert_eq!(
+        tree.verify_inclusion(&short, initial_leaf),
+        Err(MerkleError::ProofLengthMismatch {
+            expected: 3,
+            actual: 2
+        })
+    );
+
+    let long = MerkleTree::new(6, initial_leaf).unwrap().proof(2).unwrap();
+    assert_eq!(
+        tree.verify_inclusion(&long, initial_leaf),
+        Err(MerkleError::ProofLengthMismatch {
+            expected: 3,
+            actual: 5
+        })
+    );
+    assert_eq!(
+        tree.validate_proof_length(&long),
+        Err(MerkleError::ProofLengthMismatch {
+            expected: 3,
+            actual: 5
+        })
+    );
+    assert_eq!(tree.validate_proof_length(&proof), Ok(()));
+}
+
+#[test]
+fn test_verify_inclusion_ignores_case() {
+    // a depth 1 tree's root is its leaf, exactly as it was set
+    let mut tree = MerkleTree::new(1, ZERO_LEAF).unwrap();
+    let leaf = format!("0x{}", "ab".repeat(32));
+    tree.set(0, &leaf.to_uppercase().replacen('X', "x", 1))
+        .unwrap();
+    assert!(tree.verify_inclusion(&[], &leaf).unwrap());
+
+    let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    tree.set(1, &format!("0x{}", "AB".repeat(32))).unwrap();
+    let proof = tree.proof(1).unwrap();
+    assert!(tree.verify_inclusion(&proof, &leaf).unwrap());
+}
+
+#[test]
+fn test_assert_root() {
+    let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    let root = tree.root();
+    assert_eq!(tree.assert_root(&root), Ok(()));
+    let padded_upper = format!(" 0x{} ", root[2..].to_uppercase());
+    assert_eq!(tree.assert_root(&padded_upper), Ok(()));
+
+    tree.set(1, &format!("0x{:064x}", 7)).unwrap();
+    assert_eq!(
+        tree.assert_root(&root),
+        Err(MerkleError::RootMismatch {
+            expected: root.clone(),
+            actual: tree.root()
+        })
+    );
+    assert_eq!(tree.assert_root("0x00"), Err(MerkleError::InvalidBytes));
+}
+
+#[test]
+fn test_verify_against_root() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let tree = MerkleTree::new(4, initial_leaf).unwrap();
+    let proof = tree.proof(5).unwrap();
+    assert!(MerkleTree::verify_against_root(&proof, initial_leaf, &tree.root(), 4).unwrap());
+    assert!(!MerkleTree::verify_against_root(&proof, ZERO_LEAF, &tree.root(), 4).unwrap());
+    let shouted = format!(" 0x{} ", tree.root()[2..].to_uppercase());
+    assert!(MerkleTree::verify_against_root(&proof, initial_leaf, &shouted, 4).unwrap());
+    assert_eq!(
+        MerkleTree::verify_against_root(&proof, initial_leaf, "0x12", 4),
+        Err(MerkleError::InvalidBytes)
+    );
+    assert_eq!(
+        MerkleTree::verify_against_root(&proof, initial_leaf, &tree.root(), 5),
+        Err(MerkleError::ProofLengthMismatch {
+            expected: 4,
+            actual: 3
+        })
+    );
+}
+
+#[test]
+fn test_merkle_tree_20_byte_digest() {
+    type Sha3_160 = Sha3_256Hasher<20>;
+    let initial_leaf = "0xabababababababababababababababababababab";
+    let mut tree = MerkleTree::with_hasher(3, initial_leaf, Sha3_160::default()).unwrap();
+    assert_eq!(&tree.nodes[1], "0x51f8af962154ec1218528875fbb1dfaa2983bc3d");
+    assert_eq!(tree.root(), "0x258ff840678cbb9a9a75ff716b3c6f57ddf88240");
+
+    let value = "0xcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd";
+    tree.set(2, value).unwrap();
+    let proof = tree.proof(2).unwrap();
+    assert!(proof.iter().all(|step| step.sibling.len() == 42));
+    assert_eq!(
+        MerkleTree::verify_with_hasher(&proof, value.to_string(), &Sha3_160::default()).unwrap(),
+        tree.root()
+    );
+
+    let rebuilt = MerkleTree::from_leaves_with_hasher(
+        &[initial_leaf, initial_leaf, value, initial_leaf],
+        Sha3_160::default(),
+    )
+    .unwrap();
+    assert_eq!(rebuilt.root(), tree.root());
+
+    // 32 byte leaves are rejected by a 20 byte tree
+    assert_eq!(
+        MerkleTree::with_hasher(3, ZERO_LEAF, Sha3_160::default()).err(),
+        Some(MerkleError::InvalidBytes)
+    );
+}
+
+#[test]
+fn test_iter_nodes() {
+    let leaves = [
+        "0x0000000000000000000000000000000000000000000000000000000000000000",
+        "0x1111111111111111111111111111111111111111111111111111111111111111",
+        "0x2222222222222222222222222222222222222222222222222222222222222222",
+        "0x3333333333333333333333333333333333333333333333333333333333333333",
+    ];
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+
+    let bfs: Vec<_> = tree.iter_nodes_bfs().collect();
+    let coordinates: Vec<_> = bfs.iter().map(|&(d, o, _)| (d, o)).collect();
+    assert_eq!(
+        coordinates,
+        vec![(0, 0), (1, 0), (1, 1), (2, 0), (2, 1), (2, 2), (2, 3)]
+    );
+    assert_eq!(bfs[0].2, tree.root());
+    assert_eq!(bfs[6].2, leaves[3]);
+
+    let dfs: Vec<_> = tree
+        .iter_nodes_dfs_preorder()
+        .map(|(d, o, _)| (d, o))
+        .collect();
+    assert_eq!(
+        dfs,
+        vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (2, 2), (2, 3)]
+    );
+    let dfs_leaves: Vec<_> = tree
+        .iter_nodes_dfs_preorder()
+        .filter(|&(d, _, _)| d == 2)
+        .map(|(_, _, hash)| hash)
+        .collect();
+    assert_eq!(dfs_leaves, leaves);
+}
+
+#[test]
+fn test_empty_tree() {
+    let mut tree = MerkleTree::empty();
+    assert_eq!(tree.root(), EMPTY_ROOT);
+    assert_eq!(tree.num_leaves(), 0);
+    assert_eq!(tree.depth(), 0);
+    assert_eq!(tree.proof(0).err(), Some(MerkleError::InvalidIndex));
+    assert_eq!(tree.set(0, ZERO_LEAF), Err(MerkleError::InvalidIndex));
+    assert_eq!(MerkleTree::from_leaves(&[]).unwrap().root(), EMPTY_ROOT);
+}
+
+#[test]
+fn test_append() {
+    let leaf = "0xabababababababababababababababababababababababababababababababab";
+    let mut tree = MerkleTree::empty();
+    assert_eq!(tree.append(leaf), Ok(0));
+    assert_eq!(tree.num_leaves(), 1);
+    assert_eq!(tree.root(), leaf);
+    assert!(tree.proof(0).unwrap().is_empty());
+    assert_eq!(tree.append(leaf), Err(MerkleError::TreeFull));
+
+    let mut tree = MerkleTree::from_leaves(&[leaf, leaf, leaf]).unwrap();
+    assert_eq!(tree.append(leaf), Ok(3));
+    assert_eq!(tree.root(), MerkleTree::new(3, leaf).unwrap().root());
+    assert_eq!(tree.append(leaf), Err(MerkleError::TreeFull));
+}
+
+#[test]
+fn test_validate_leaf_uses_digest_hex() {
+    let valid = format!("0x{}", "a".repeat(DIGEST_HEX));
+    assert_eq!(valid.len(), DIGEST_HEX + 2);
+    assert_eq!(validate_leaf(&valid, DIGEST_BYTES), Ok(()));
+
+    let short = format!("0x{}", "a".repeat(DIGEST_HEX - 1));
+    assert_eq!(short.len(), DIGEST_HEX + 1);
+    assert_eq!(
+        validate_leaf(&short, DIGEST_BYTES),
+        Err(MerkleError::InvalidBytes)
+    );
+}
+
+#[test]
+fn test_simulate_set() {
+    let leaves: Vec<String> = (1..=5).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+    let value = format!("0x{}", "ab".repeat(32));
+
+    let simulated = tree.simulate_set(6, &value).unwrap();
+    let mut applied = tree.clone();
+    applied.set(6, &value).unwrap();
+    assert_eq!(simulated, applied.root());
+    assert_eq!(tree.get_leaf(6).unwrap(), ZERO_LEAF);
+
+    assert_eq!(tree.simulate_set(8, &value), Err(MerkleError::InvalidIndex));
+    assert_eq!(tree.simulate_set(0, "0x00"), Err(MerkleError::InvalidBytes));
+    assert_eq!(tree.simulate_set(6, &format!(" {} ", value)), Ok(simulated));
+}
+
+#[test]
+fn test_set_returning_changes() {
+    let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    let mut replica = tree.clone();
+    let value = format!("0x{:064x}", 9);
+
+    let changes = tree.set_returning_changes(5, &value).unwrap();
+    let indices: Vec<usize> = changes.iter().map(|(index, _)| *index).collect();
+    assert_eq!(indices, vec![12, 5, 2, 0]);
+    assert_eq!(changes[0].1, value);
+    for (index, hash) in &changes {
+        assert_eq!(&tree.nodes[*index], hash);
+        replica.nodes[*index] = hash.clone();
+    }
+    assert_eq!(replica.nodes, tree.nodes);
+
+    assert_eq!(
+        tree.set_returning_changes(16, &value),
+        Err(MerkleError::InvalidIndex)
+    );
+}
+
+#[test]
+fn test_simulate_set_batch_leaves_tree_untouched() {
+    let leaves: Vec<String> = (1..=4).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+    let value = format!("0x{}", "cd".repeat(32));
+
+    let mut applied = tree.clone();
+    applied.set(0, &value).unwrap();
+    assert_eq!(
+        tree.simulate_set_batch(&[(0, &value)]).unwrap(),
+        applied.root()
+    );
+    assert_eq!(tree.get_leaf(0).unwrap(), leaves[0]);
+    let padded = format!("\t{}\n", value);
+    assert_eq!(
+        tree.simulate_set_batch(&[(0, &padded)]).unwrap(),
+        applied.root()
+    );
+}
+
+#[test]
+fn test_apply_updates_from_iter() {
+    let mut applied = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    let mut streamed = applied.clone();
+    let updates = (0..12).map(|i| ((i * 5) % 8, format!("0x{:064x}", i)));
+    for (leaf_index, value) in updates.clone() {
+        applied.set(leaf_index, &value).unwrap();
+    }
+    streamed.apply_updates_from_iter(updates).unwrap();
+    assert_eq!(streamed.root(), applied.root());
+    assert_eq!(streamed.get_leaf(7).unwrap(), applied.get_leaf(7).unwrap());
+    streamed
+        .apply_updates_from_iter([(7, format!(" {} ", applied.get_leaf(7).unwrap()))])
+        .unwrap();
+    assert_eq!(streamed.root(), applied.root());
+
+    let invalid = [(1, ZERO_LEAF.to_string()), (8, ZERO_LEAF.to_string())];
+    assert_eq!(
+        streamed.apply_updates_from_iter(invalid),
+        Err(MerkleError::InvalidIndex)
+    );
+    assert_eq!(streamed.root(), applied.root());
+}
+
+#[test]
+fn test_set_batch() {
+    let tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    let values: Vec<String> = (1..=4).map(|i| format!("0x{:064x}", i)).collect();
+    let updates = [
+        (0, values[0].as_str()),
+        (1, values[1].as_str()),
+        (6, values[2].as_str()),
+        (0, values[3].as_str()),
+    ];
+
+    let mut applied = tree.clone();
+    for (leaf_index, value) in updates {
+        applied.set(leaf_index, value).unwrap();
+    }
+    assert_eq!(tree.simulate_set_batch(&updates).unwrap(), applied.root());
+
+    let mut batched = tree.clone();
+    batched.set_batch(&updates).unwrap();
+    assert_eq!(batched.root(), applied.root());
+    assert_eq!(batched.get_leaf(0).unwrap(), values[3]);
+    assert_eq!(
+        batched.set_batch(&[(1, ZERO_LEAF), (8, ZERO_LEAF)]),
+        Err(MerkleError::InvalidIndex)
+    );
+    assert_eq!(batched.root(), applied.root());
+    assert_eq!(tree.simulate_set_batch(&[]).unwrap(), tree.root());
+
+    // values are trimmed like `set` trims them
+    let padded = format!(" {} ", values[3]);
+    let mut trimmed = tree.clone();
+    trimmed
+        .set_batch(&[
+            (0, padded.as_str()),
+            (1, values[1].as_str()),
+            (6, values[2].as_str()),
+        ])
+        .unwrap();
+    assert_eq!(trimmed.root(), applied.root());
+    assert_eq!(trimmed.get_leaf(0).unwrap(), values[3]);
+    let mut appended = MerkleTree::empty();
+    appended.append_growing(&padded).unwrap();
+    appended.append_growing(&padded).unwrap();
+    assert_eq!(appended.get_leaf(1).unwrap(), values[3]);
+
+    let single = MerkleTree::new(1, ZERO_LEAF).unwrap();
+    assert_eq!(single.simulate_set(0, &values[0]).unwrap(), values[0]);
+}
+
+#[test]
+fn test_set_batch_duplicate_indices() {
+    let a = format!("0x{:064x}", 0xa);
+    let b = format!("0x{:064x}", 0xb);
+    let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    tree.enable_undo(1);
+    let updates = [(3, a.as_str()), (1, a.as_str()), (3, b.as_str())];
+    let simulated = tree.simulate_set_batch(&updates).unwrap();
+    tree.set_batch(&updates).unwrap();
+    assert_eq!(tree.get_leaf(3).unwrap(), b);
+    assert_eq!(tree.get_leaf(1).unwrap(), a);
+    assert_eq!(tree.root(), simulated);
+
+    let mut sequential = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    for (leaf_index, value) in updates {
+        sequential.set(leaf_index, value).unwrap();
+    }
+    assert_eq!(tree.root(), sequential.root());
+
+    // the batch is undone as a unit, back to the value before either write to leaf 3
+    tree.undo().unwrap();
+    assert_eq!(tree.get_leaf(3).unwrap(), ZERO_LEAF);
+
+    let root = tree.root();
+    assert_eq!(
+        tree.set_batch(&[(3, "0x00"), (3, b.as_str())]),
+        Err(MerkleError::InvalidBytes)
+    );
+    assert_eq!(tree.root(), root);
+}
+
+#[test]
+fn test_draw_ascii() {
+    let tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    let root = &tree.root()[2..10];
+    let child = &ZERO_HASHES[1][2..10];
+
+    assert_eq!(tree.draw_ascii(0), format!("[0,0] {}\n  [...]\n", root));
+    assert_eq!(
+        tree.draw_ascii(1),
+        format!(
+            "[0,0] {root}\n  [1,0] {child}\n    [...]\n  [1,1] {child}\n    [...]\n",
+            root = root,
+            child = child
+        )
+    );
+
+    let full = tree.draw_ascii(2);
+    assert_eq!(full.lines().count(), 7);
+    assert!(!full.contains("[...]"));
+    assert_eq!(full, tree.draw_ascii(10));
+    assert!(full.ends_with("    [2,3] 00000000\n"));
+
+    assert_eq!(MerkleTree::empty().draw_ascii(3), "");
+}
+
+#[test]
+fn test_levels() {
+    let tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    assert_eq!(tree.level(0).unwrap(), [tree.root()]);
+    assert_eq!(tree.level(1).unwrap().len(), 2);
+    assert_eq!(tree.level(2).unwrap(), vec![ZERO_LEAF.to_string(); 4]);
+    assert_eq!(tree.level(3), Err(MerkleError::InvalidIndex));
+
+    let sizes: Vec<usize> = tree.levels().map(<[String]>::len).collect();
+    assert_eq!(sizes, vec![1, 2, 4]);
+    assert_eq!(MerkleTree::empty().levels().count(), 0);
+    assert_eq!(MerkleTree::empty().level(0), Err(MerkleError::InvalidIndex));
+}
+
+#[test]
+fn test_validate() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.validate_path(5), Ok(()));
+    assert_eq!(tree.validate_path(8), Err(MerkleError::InvalidIndex));
+
+    // corrupt internal node 2, which its parent (the root) no longer matches either
+    let mut corrupted = MerkleTree::from_leaves(&leaves).unwrap();
+    corrupted.nodes[2] = ZERO_LEAF.to_string();
+    let error = corrupted.validate().unwrap_err();
+    assert_eq!(error.total, 2);
+    assert_eq!(error.mismatches[0].index, 0);
+    assert_eq!(error.mismatches[1].index, 2);
+    assert_eq!(error.mismatches[1].found, ZERO_LEAF);
+    assert_eq!(
+        error.mismatches[1].expected.as_deref(),
+        Some(&*tree.nodes[2])
+    );
+    // the left half's path only passes through the root, the right half's through node 2 too
+    assert!(matches!(
+        corrupted.validate_path(1),
+        Err(MerkleError::Integrity(IntegrityError { total: 1, .. }))
+    ));
+    assert!(matches!(
+        corrupted.validate_path(4),
+        Err(MerkleError::Integrity(IntegrityError { total: 2, .. }))
+    ));
+
+    // a corrupted leaf shows up as its parent mismatching
+    let mut corrupted = MerkleTree::from_leaves(&leaves).unwrap();
+    corrupted.nodes[10] = ZERO_LEAF.to_string();
+    let error = corrupted.validate().unwrap_err();
+    assert_eq!(error.total, 1);
+    assert_eq!(error.mismatches[0].index, parent_index(10).unwrap());
+}
+
+#[test]
+fn test_extend_with_leaves() {
+    let leaves: Vec<String> = (1..=7).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves[..4]).unwrap();
+
+    let extended = tree.extend_with_leaves(&leaves[4..]).unwrap();
+    assert_eq!(extended.depth(), tree.depth() + 1);
+    assert_eq!(extended.validate(), Ok(()));
+    assert_eq!(
+        extended.root(),
+        MerkleTree::from_leaves(&leaves).unwrap().root()
+    );
+    assert_eq!(extended.get_leaf(2).unwrap(), leaves[2]);
+    assert_eq!(extended.level(1).unwrap()[0], tree.root());
+
+    // the original tree is unchanged and the right half holds at most as many leaves as it
+    assert_eq!(tree.num_leaves(), 4);
+    assert_eq!(
+        tree.extend_with_leaves(&leaves[..5]).err(),
+        Some(MerkleError::TreeFull)
+    );
+    assert_eq!(
+        MerkleTree::empty()
+            .extend_with_leaves(&leaves[..3])
+            .unwrap()
+            .root(),
+        MerkleTree::from_leaves(&leaves[..3]).unwrap().root()
+    );
+}
+
+#[test]
+fn test_repair() {
+    let leaves: Vec<String> = (1..=4).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let honest = MerkleTree::from_leaves(&leaves).unwrap();
+
+    // from_raw_nodes trusts its input, so stale internal nodes load unchecked
+    let mut raw = honest.clone().into_raw_nodes();
+    raw[0] = [1u8; DIGEST_BYTES];
+    raw[2] = [2u8; DIGEST_BYTES];
+    let mut tree = MerkleTree::from_raw_nodes(raw).unwrap();
+    assert!(tree.validate().is_err());
+
+    assert_eq!(tree.repair().unwrap(), honest.root());
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!(tree.root(), honest.root());
+
+    tree.nodes[4] = "0x00".to_string();
+    assert_eq!(tree.repair(), Err(MerkleError::InvalidBytes));
+}
 
 #[test]
-fn test_merkle_tree_depth_20() {
-    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
-    let tree = MerkleTree::new(20, initial_leaf).unwrap();
+fn test_strict_hex_validation() {
+    let leaf = format!("0x{}", "ab".repeat(32));
+    let mut tree = MerkleTree::new(2, ZERO_LEAF).unwrap();
+
+    // surrounding whitespace is trimmed before validating
+    tree.set(1, &format!(" {} ", leaf)).unwrap();
+    assert_eq!(tree.get_leaf(1).unwrap(), leaf);
+    let proof = tree.proof(1).unwrap();
     assert_eq!(
-        tree.root(),
-        "0xd4490f4d374ca8a44685fe9471c5b8dbe58cdffd13d30d9aba15dd29efb92930"
+        MerkleTree::verify(&proof, format!("\t{}\n", leaf)).unwrap(),
+        tree.root()
+    );
+
+    let short = format!("0x{}", "a".repeat(63));
+    assert_eq!(tree.set(0, &short), Err(MerkleError::InvalidBytes));
+    assert_eq!(
+        MerkleTree::verify(&proof, short.clone()),
+        Err(MerkleError::InvalidBytes)
+    );
+    let inner_space = format!("0x{} {}", "a".repeat(32), "a".repeat(31));
+    assert_eq!(tree.set(0, &inner_space), Err(MerkleError::InvalidBytes));
+    assert_eq!(
+        tree.set(0, &format!("0X{}", "ab".repeat(32))),
+        Err(MerkleError::InvalidBytes)
+    );
+    // mixed case hex is accepted
+    tree.set(0, &format!("0x{}", "aB".repeat(32))).unwrap();
+
+    let bad_sibling = vec![ProofStep::new(Direction::Left, short)];
+    assert_eq!(
+        MerkleTree::verify(&bad_sibling, leaf),
+        Err(MerkleError::InvalidBytes)
     );
 }
 
 #[test]
-fn test_merkle_tree_full() {
-    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
-    let tree = MerkleTree::new(3, initial_leaf).unwrap();
-    for i in 3..7 {
-        assert_eq!(
-            &tree.nodes[i],
-            "0xabababababababababababababababababababababababababababababababab"
-        )
+fn test_recompute_subtree() {
+    let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let expected = MerkleTree::from_leaves(&leaves).unwrap();
+
+    // write the right half's leaves without re-hashing, as a raw batch update would
+    let mut tree = MerkleTree::from_leaves(&leaves[..5]).unwrap();
+    for (leaf_index, leaf) in leaves.iter().enumerate().skip(5) {
+        tree.nodes[7 + leaf_index] = leaf.to_string();
     }
-    for i in 1..3 {
+    tree.recompute_subtree(1, 1).unwrap();
+    assert_eq!(tree.nodes[2], expected.nodes[2]);
+    assert_eq!(tree.nodes[5], expected.nodes[5]);
+    // the ancestors above the subtree are still stale
+    assert_ne!(tree.root(), expected.root());
+    tree.recompute_subtree(0, 0).unwrap();
+    assert_eq!(tree.root(), expected.root());
+    assert_eq!(tree.validate(), Ok(()));
+
+    assert_eq!(tree.recompute_subtree(3, 0), Err(MerkleError::InvalidIndex));
+    assert_eq!(tree.recompute_subtree(1, 2), Err(MerkleError::InvalidIndex));
+}
+
+#[test]
+fn test_raw_nodes_round_trip() {
+    let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    tree.set(1, &format!("0x{}", "ab".repeat(32))).unwrap();
+    let root = tree.root();
+
+    let raw = tree.into_raw_nodes();
+    assert_eq!(raw.len(), 7);
+    assert_eq!(raw[4], [0xab; 32]);
+    assert_eq!(format!("0x{}", hex::encode(raw[0])), root);
+
+    let tree = MerkleTree::from_raw_nodes(raw).unwrap();
+    assert_eq!(tree.root(), root);
+    assert_eq!(tree.depth(), 3);
+
+    assert_eq!(
+        MerkleTree::from_raw_nodes(Vec::new()).unwrap().num_leaves(),
+        0
+    );
+    assert!(matches!(
+        MerkleTree::from_raw_nodes(vec![[0u8; 32]; 4]),
+        Err(MerkleError::SerializationError(_))
+    ));
+}
+
+#[test]
+fn test_append_growing() {
+    let leaves: Vec<String> = (1..=9).map(|i| format!("0x{:064x}", i)).collect();
+    let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    assert_eq!(tree.num_leaves(), 8);
+
+    for (i, leaf) in leaves.iter().enumerate() {
+        assert_eq!(tree.append_growing(leaf).unwrap(), i);
+    }
+    assert_eq!(tree.depth(), 5);
+    assert_eq!(tree.num_leaves(), 16);
+    for (i, leaf) in leaves.iter().enumerate() {
+        assert_eq!(tree.get_leaf(i).unwrap(), leaf);
+    }
+
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let expected = MerkleTree::from_leaves(&leaves).unwrap();
+    assert_eq!(tree.depth(), expected.depth());
+    assert_eq!(tree.root(), expected.root());
+    assert_eq!(
+        MerkleTree::verify(&tree.proof(3).unwrap(), leaves[3].to_string()).unwrap(),
+        expected.root()
+    );
+}
+
+#[test]
+fn test_append_growing_from_empty() {
+    let mut tree = MerkleTree::empty();
+    assert_eq!(tree.append_growing(ZERO_LEAF).unwrap(), 0);
+    assert_eq!(tree.append_growing(ZERO_LEAF).unwrap(), 1);
+    assert_eq!(tree.depth(), 2);
+
+    // invalid values are rejected before the tree grows
+    assert_eq!(tree.append_growing("0x00"), Err(MerkleError::InvalidBytes));
+    assert_eq!(tree.depth(), 2);
+}
+
+#[test]
+fn test_new_with_precomputed_zeros() {
+    for depth in 1..=12 {
+        let precomputed = MerkleTree::new_with_precomputed_zeros(depth).unwrap();
+        let hashed = MerkleTree::new(depth, ZERO_LEAF).unwrap();
+        assert_eq!(precomputed.nodes, hashed.nodes);
+    }
+    assert_eq!(ZERO_HASHES[0], ZERO_LEAF);
+    assert_eq!(
+        MerkleTree::new_with_precomputed_zeros(0).unwrap().root(),
+        EMPTY_ROOT
+    );
+    assert_eq!(
+        MerkleTree::new_with_precomputed_zeros(MAX_DEPTH + 1).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
+    );
+}
+
+#[test]
+fn test_batch_update_and_get_root() {
+    let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    let (a, b) = (format!("0x{:064x}", 1), format!("0x{:064x}", 2));
+    let updates = [(2, a.as_str()), (7, b.as_str()), (2, b.as_str())];
+
+    let expected = tree.simulate_set_batch(&updates).unwrap();
+    assert_eq!(tree.batch_update_and_get_root(&updates).unwrap(), expected);
+    assert_eq!(tree.root(), expected);
+    assert_eq!(tree.get_leaf(2).unwrap(), b);
+
+    // a bad update anywhere in the batch leaves the tree untouched
+    let snapshot = tree.clone();
+    assert_eq!(
+        tree.batch_update_and_get_root(&[(0, a.as_str()), (8, a.as_str())]),
+        Err(MerkleError::InvalidIndex)
+    );
+    assert_eq!(
+        tree.batch_update_and_get_root(&[(0, a.as_str()), (1, "0x00")]),
+        Err(MerkleError::InvalidBytes)
+    );
+    assert_eq!(tree.state(), snapshot.state());
+    assert_eq!(tree.batch_update_and_get_root(&[]).unwrap(), expected);
+}
+
+// SHA3-256 hasher counting its calls, to check how much work verification does
+#[cfg(test)]
+#[derive(Default)]
+struct CountingHasher {
+    calls: std::cell::Cell<usize>,
+}
+
+#[cfg(test)]
+impl MerkleHasher for CountingHasher {
+    const DIGEST_BYTES: usize = DIGEST_BYTES;
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        self.calls.set(self.calls.get() + 1);
+        Sha3_256Hasher::<DIGEST_BYTES>.digest(data)
+    }
+
+    fn algorithm_id(&self) -> &str {
+        "sha3-256"
+    }
+}
+
+#[test]
+fn test_set_if_changed() {
+    let mut tree = MerkleTree::with_hasher(4, ZERO_LEAF, CountingHasher::default()).unwrap();
+    let leaf = format!("0x{:064x}", 0xab);
+    assert_eq!(tree.set_if_changed(3, &leaf), Ok(true));
+    let root = tree.root();
+
+    tree.hasher().calls.set(0);
+    tree.enable_undo(2);
+    assert_eq!(tree.set_if_changed(3, &leaf), Ok(false));
+    let upper = format!(" 0x{} ", leaf[2..].to_uppercase());
+    assert_eq!(tree.set_if_changed(3, &upper), Ok(false));
+    assert_eq!(tree.hasher().calls.get(), 0);
+    assert_eq!(tree.root(), root);
+    assert_eq!(tree.undo(), Err(MerkleError::NothingToUndo));
+
+    assert_eq!(tree.set_if_changed(3, ZERO_LEAF), Ok(true));
+    assert_eq!(tree.hasher().calls.get(), 3);
+    assert_ne!(tree.root(), root);
+    assert_eq!(
+        tree.set_if_changed(8, ZERO_LEAF),
+        Err(MerkleError::InvalidIndex)
+    );
+    assert_eq!(
+        tree.set_if_changed(3, "0x12"),
+        Err(MerkleError::InvalidBytes)
+    );
+}
+
+#[test]
+fn test_verify_tampered_proofs() {
+    let mut tree = MerkleTree::new(12, ZERO_LEAF).unwrap();
+    let leaf = format!("0x{:064x}", 7);
+    tree.set(1000, &leaf).unwrap();
+    let root = tree.root();
+    let proof = tree.proof(1000).unwrap();
+    let hasher = CountingHasher::default();
+    // verifies with the counting hasher, returning the root and the number of hashes it took
+    let verify = |proof: &[ProofStep], leaf: &str| {
+        hasher.calls.set(0);
+        let result = MerkleTree::verify_with_hasher(proof, leaf.to_string(), &hasher);
+        (result, hasher.calls.get())
+    };
+    assert_eq!(verify(&proof, &leaf), (Ok(root.clone()), 11));
+
+    for level in 0..proof.len() {
+        // a flipped direction still costs one hash per step, and never reaches the root
+        let mut flipped = proof.clone();
+        flipped[level].direction = match flipped[level].direction {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        };
+        let (result, hashes) = verify(&flipped, &leaf);
+        assert_ne!(result.unwrap(), root);
+        assert_eq!(hashes, 11);
+
+        // as does any altered sibling byte
+        for byte in [0, DIGEST_BYTES - 1] {
+            let mut altered = proof.clone();
+            let mut sibling = hex::decode(&altered[level].sibling[2..]).unwrap();
+            sibling[byte] ^= 1;
+            altered[level].sibling = format!("0x{}", hex::encode(sibling));
+            let (result, hashes) = verify(&altered, &leaf);
+            assert_ne!(result.unwrap(), root);
+            assert_eq!(hashes, 11);
+        }
+
+        // while a sibling that is not a node is rejected before hashing anything
+        for sibling in [
+            "",
+            "0x",
+            "0",
+            "0xé",
+            &proof[level].sibling[..40],
+            &"é".repeat(33),
+        ] {
+            let mut malformed = proof.clone();
+            malformed[level].sibling = sibling.to_string();
+            assert_eq!(
+                verify(&malformed, &leaf),
+                (Err(MerkleError::InvalidBytes), 0)
+            );
+        }
+    }
+
+    // a truncated proof folds to an inner node, and is rejected outright when the depth is known
+    for len in 0..proof.len() {
+        let truncated = &proof[..len];
+        let (result, hashes) = verify(truncated, &leaf);
+        assert_ne!(result.unwrap(), root);
+        assert_eq!(hashes, len);
         assert_eq!(
-            &tree.nodes[i],
-            "0x699fc94ff1ec83f1abf531030e324003e7758298281645245f7c698425a5e0e7"
-        )
+            MerkleTree::verify_against_root(truncated, &leaf, &root, 12),
+            Err(MerkleError::ProofLengthMismatch {
+                expected: 11,
+                actual: len
+            })
+        );
+        assert!(tree.verify_inclusion(truncated, &leaf).is_err());
     }
+    assert_eq!(verify(&proof, "0x"), (Err(MerkleError::InvalidBytes), 0));
+}
+
+#[test]
+fn test_node_converts_index_errors() {
+    let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    tree.set(3, &format!("0x{:064x}", 1)).unwrap();
+    assert_eq!(tree.node(0, 0).unwrap(), tree.root());
+    assert_eq!(tree.node(2, 3).unwrap(), tree.get_leaf(3).unwrap());
+
+    // an offset past the end of its level and a depth too large for a usize index both come
+    // back from `utils::index` as a `ValidationError`
     assert_eq!(
-        tree.root(),
-        "0xa2422433244a1da24b3c4db126dcc593666f98365403e6aaf07fae011c824f09"
+        depth_offset_to_index(1, 2),
+        Err(crate::errors::errors::ValidationError::Invalid)
+    );
+    assert_eq!(tree.node(1, 2), Err(MerkleError::InvalidIndex));
+    assert_eq!(
+        tree.node(usize::BITS as usize, 0),
+        Err(MerkleError::InvalidIndex)
     );
+    // a valid index beneath the leaves is out of bounds for the tree itself
+    assert_eq!(tree.node(3, 0), Err(MerkleError::InvalidIndex));
 }
 
 #[test]
-fn test_merkle_tree_set() {
-    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
-    let mut tree = MerkleTree::new(2, initial_leaf).unwrap();
-    assert_eq!(tree.nodes.len(), 3);
+fn test_new_checks_tree_size() {
+    assert_eq!(tree_size(MAX_DEPTH, MAX_DEPTH), Ok((1 << MAX_DEPTH) - 1));
     assert_eq!(
-        tree.root(),
-        "0x699fc94ff1ec83f1abf531030e324003e7758298281645245f7c698425a5e0e7"
+        tree_size(MAX_DEPTH + 1, MAX_DEPTH),
+        Err(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
     );
-    tree.set(
-        0,
-        "0xabababababababababababababababababababababababababababababababcd",
-    )
-    .unwrap();
     assert_eq!(
-        &tree.nodes[1],
-        "0xabababababababababababababababababababababababababababababababcd"
+        MerkleTree::new(usize::MAX, ZERO_LEAF).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: usize::MAX,
+            permitted: MAX_DEPTH
+        })
     );
+    // depth 0 is the empty tree rather than an underflowing leaf count
+    let tree = MerkleTree::new(0, ZERO_LEAF).unwrap();
+    assert_eq!(tree.num_leaves(), 0);
+    assert_eq!(tree.root(), MerkleTree::empty().root());
     assert_eq!(
-        tree.root(),
-        "0x1e69d9c46ca7065c20d7a9bb407f574fd92fd862f69cb96e1146926c8f198e81"
-    )
+        MerkleTree::new(0, "0x00").err(),
+        Some(MerkleError::InvalidBytes)
+    );
 }
 
 #[test]
-fn test_merkle_tree_set_higher_depth() {
-    let initial_leaf = "0xabababababababababababababababababababababababababababababababcd";
-    let mut tree = MerkleTree::new(10, initial_leaf).unwrap();
-    for i in 0..tree.num_leaves() {
-        tree.set(
-            i,
-            "0xabababababababababababababababababababababababababababababababab",
-        )
-        .unwrap();
+fn test_dirty_ancestor_indices() {
+    let tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+    assert_eq!(
+        tree.dirty_ancestor_indices(&[]).unwrap(),
+        Vec::<usize>::new()
+    );
+    assert_eq!(tree.dirty_ancestor_indices(&[0]).unwrap(), vec![0, 1, 3]);
+    assert_eq!(
+        tree.dirty_ancestor_indices(&[1, 0, 1]).unwrap(),
+        vec![0, 1, 3]
+    );
+    assert_eq!(
+        tree.dirty_ancestor_indices(&[0, 7]).unwrap(),
+        vec![0, 1, 2, 3, 6]
+    );
+
+    // matches the union of the leaves' paths, minus the leaves, plus the root
+    let leaves = [6, 2, 3];
+    let mut expected: BTreeSet<usize> = [0].into();
+    for leaf_index in leaves {
+        expected.extend(tree.path_nodes(leaf_index).unwrap().into_iter().skip(1));
     }
     assert_eq!(
-        tree.root(),
-        "0xc795494aa662dd012c5de6c52f0ab28ee9135fe846074d62bb7807cf98742fd9"
-    )
+        tree.dirty_ancestor_indices(&leaves).unwrap(),
+        expected.into_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        tree.dirty_ancestor_indices(&[0, 8]),
+        Err(MerkleError::InvalidIndex)
+    );
 }
 
 #[test]
-fn test_proof() {
-    let initial_leaf = "0x0000000000000000000000000000000000000000000000000000000000000000";
-    let mut tree = MerkleTree::new(5, initial_leaf).unwrap();
-    let num_leaves = tree.num_leaves();
+fn test_commitment_binds_parameters() {
+    // a one leaf tree's root is its leaf, so it can share the root of any deeper tree
+    let deep = MerkleTree::new(3, ZERO_LEAF).unwrap();
+    let shallow = MerkleTree::new(1, &deep.root()).unwrap();
+    assert_eq!(shallow.root(), deep.root());
+    assert_ne!(shallow.commitment(), deep.commitment());
 
-    let multiplier = BigUint::parse_bytes(
-        b"1111111111111111111111111111111111111111111111111111111111111111",
-        16,
-    )
-    .expect("Failed to parse hex string to BigInt");
+    // and two one leaf trees share the root whatever their hash functions
+    let sha2 =
+        MerkleTree::with_hasher(1, &deep.root(), crate::merkle_tree::hasher::Sha256Hasher).unwrap();
+    assert_eq!(sha2.root(), shallow.root());
+    assert_ne!(sha2.commitment(), shallow.commitment());
 
-    for i in 0..num_leaves {
-        let product = BigUint::from_usize(i).unwrap() * &multiplier;
-        let value = format!("0x{:064x}", product);
-        tree.set(i, &value).unwrap();
+    assert_eq!(
+        deep.commitment(),
+        MerkleTree::new(3, ZERO_LEAF).unwrap().commitment()
+    );
+    assert_eq!(deep.commitment().len(), 2 + DIGEST_HEX);
+}
+
+#[test]
+fn test_levels_and_height_conventions() {
+    // (levels, leaves) and (height, leaves) for the smallest trees
+    for (levels, leaves) in [(0, 0), (1, 1), (2, 2), (3, 4), (4, 8)] {
+        let tree = MerkleTree::with_levels(levels, ZERO_LEAF).unwrap();
+        assert_eq!(tree.num_leaves(), leaves);
+        assert_eq!(tree.depth(), levels);
+        assert_eq!(
+            tree.state(),
+            MerkleTree::new(levels, ZERO_LEAF).unwrap().state()
+        );
+    }
+    for (height, leaves) in [(0, 1), (1, 2), (2, 4), (3, 8)] {
+        let tree = MerkleTree::with_height(height, ZERO_LEAF).unwrap();
+        assert_eq!(tree.num_leaves(), leaves);
+        assert_eq!(tree.height(), Some(height));
+        assert_eq!(tree.depth(), height + 1);
     }
+    assert_eq!(MerkleTree::empty().height(), None);
 
     assert_eq!(
-        tree.root(),
-        "0x57054e43fa56333fd51343b09460d48b9204999c376624f52480c5593b91eff4"
+        MerkleTree::with_height(MAX_DEPTH, ZERO_LEAF).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: MAX_DEPTH + 1,
+            permitted: MAX_DEPTH
+        })
+    );
+    assert_eq!(
+        MerkleTree::with_height(usize::MAX, ZERO_LEAF).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: usize::MAX,
+            permitted: MAX_DEPTH
+        })
     );
+}
 
-    let proof = tree.proof(3);
+#[test]
+fn test_with_config_limits_depth() {
+    let config = TreeConfig::default().max_depth(16).unwrap();
+    assert_eq!(
+        MerkleTree::with_config(17, ZERO_LEAF, config).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: 17,
+            permitted: 16
+        })
+    );
+    assert_eq!(
+        MerkleTree::new(17, ZERO_LEAF).unwrap().max_depth(),
+        MAX_DEPTH
+    );
 
-    let expected_proof = vec![
-        ProofStep {
-            direction: Direction::Right,
-            sibling: "0x2222222222222222222222222222222222222222222222222222222222222222"
-                .to_string(),
-        },
-        ProofStep {
-            direction: Direction::Right,
-            sibling: "0x35e794f1b42c224a8e390ce37e141a8d74aa53e151c1d1b9a03f88c65adb9e10"
-                .to_string(),
-        },
-        ProofStep {
-            direction: Direction::Left,
-            sibling: "0x26fca7737f48fa702664c8b468e34c858e62f51762386bd0bddaa7050e0dd7c0"
-                .to_string(),
-        },
-        ProofStep {
-            direction: Direction::Left,
-            sibling: "0xe7e11a86a0c1d8d8624b1629cb58e39bb4d0364cb8cb33c4029662ab30336858"
-                .to_string(),
-        },
+    // the limit carries over to growing the tree
+    let config = TreeConfig::default().max_depth(3).unwrap();
+    let mut tree = MerkleTree::with_config(2, ZERO_LEAF, config).unwrap();
+    assert_eq!(tree.max_depth(), 3);
+    for _ in 0..4 {
+        tree.append_growing(ZERO_LEAF).unwrap();
+    }
+    assert!(tree.is_at_max_depth());
+    assert_eq!(
+        tree.append_growing(ZERO_LEAF),
+        Err(MerkleError::MaxDepthExceeded {
+            requested: 4,
+            permitted: 3
+        })
+    );
+    assert_eq!(tree.clone().max_depth(), 3);
+    assert_eq!(
+        MerkleTree::with_config(0, ZERO_LEAF, config)
+            .unwrap()
+            .max_depth(),
+        3
+    );
+
+    // and to extending it, also from an empty tree
+    let leaf = format!("0x{:064x}", 1);
+    let tree = MerkleTree::with_config(2, &leaf, config).unwrap();
+    let mut extended = tree.extend_with_leaves(&[&leaf]).unwrap();
+    assert_eq!((extended.depth(), extended.max_depth()), (3, 3));
+    assert_eq!(
+        extended.extend_with_leaves(&[&leaf]).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: 4,
+            permitted: 3
+        })
+    );
+    extended.reset_to_initial(None).unwrap();
+    assert_eq!(extended.get_leaf(3), Ok(leaf.as_str()));
+    let empty = MerkleTree::with_config(0, ZERO_LEAF, config).unwrap();
+    let extended = empty.extend_with_leaves(&[ZERO_LEAF; 4]).unwrap();
+    assert_eq!(extended.max_depth(), 3);
+    assert_eq!(
+        empty.extend_with_leaves(&[ZERO_LEAF; 5]).err(),
+        Some(MerkleError::MaxDepthExceeded {
+            requested: 4,
+            permitted: 3
+        })
+    );
+}
+
+#[test]
+fn test_proof_steps_sort_canonically() {
+    assert!(Direction::Left < Direction::Right);
+    let sibling = |value: u64| format!("0x{:064x}", value);
+    let mut steps = vec![
+        ProofStep::new(Direction::Right, sibling(1)),
+        ProofStep::new(Direction::Left, sibling(2)),
+        ProofStep::new(Direction::Right, sibling(0)),
+        ProofStep::new(Direction::Left, sibling(2)),
     ];
+    steps.sort();
+    steps.dedup();
+    assert_eq!(
+        steps,
+        [
+            ProofStep::new(Direction::Left, sibling(2)),
+            ProofStep::new(Direction::Right, sibling(0)),
+            ProofStep::new(Direction::Right, sibling(1)),
+        ]
+    );
+}
 
-    assert_eq!(proof.len(), expected_proof.len());
-    for i in 0..proof.len() {
-        assert_eq!(proof[i].direction, expected_proof[i].direction);
-        assert_eq!(proof[i].sibling, expected_proof[i].sibling);
+#[test]
+fn test_level_root_counts_leaves() {
+    let leaves: Vec<String> = (0..8).map(|i| format!("0x{:064x}", i)).collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let tree = MerkleTree::from_leaves(&leaves).unwrap();
+    assert_eq!(tree.level_root(0, 0).unwrap(), (tree.root(), 8));
+    for (depth, offset, leaf_count) in [(1, 1, 4), (2, 2, 2), (3, 5, 1)] {
+        let (node, count) = tree.level_root(depth, offset).unwrap();
+        assert_eq!(node, tree.node(depth, offset).unwrap());
+        assert_eq!(count, leaf_count);
     }
+    assert_eq!(tree.level_root(3, 8), Err(MerkleError::InvalidIndex));
+    assert_eq!(tree.level_root(4, 0), Err(MerkleError::InvalidIndex));
 }
 
 #[test]
-fn test_verify() {
-    let initial_leaf = "0x0000000000000000000000000000000000000000000000000000000000000000";
-    let mut tree = MerkleTree::new(5, initial_leaf).unwrap();
-    let num_leaves = tree.num_leaves();
+fn test_highest_set_leaf() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let mut tree = MerkleTree::new(4, initial_leaf).unwrap();
+    assert_eq!(tree.initial_leaf(), initial_leaf);
+    assert_eq!(tree.highest_set_leaf(), None);
+    tree.set(0, ZERO_LEAF).unwrap();
+    tree.set(3, ZERO_LEAF).unwrap();
+    assert_eq!(tree.highest_set_leaf(), Some(3));
+    // writing the initial value back, in another case, unsets the leaf
+    tree.set(3, &initial_leaf.to_uppercase().replace("0X", "0x"))
+        .unwrap();
+    assert_eq!(tree.highest_set_leaf(), Some(0));
 
-    let multiplier = BigUint::parse_bytes(
-        b"1111111111111111111111111111111111111111111111111111111111111111",
-        16,
-    )
-    .expect("Failed to parse hex string to BigInt");
+    let padded = MerkleTree::from_leaves(&[initial_leaf, initial_leaf, initial_leaf]).unwrap();
+    assert_eq!(padded.initial_leaf(), ZERO_LEAF);
+    assert_eq!(padded.highest_set_leaf(), Some(2));
+    assert_eq!(MerkleTree::empty().highest_set_leaf(), None);
+}
 
-    for i in 0..num_leaves {
-        let product = BigUint::from_usize(i).unwrap() * &multiplier;
-        let value = format!("0x{:064x}", product);
-        tree.set(i, &value).unwrap();
-    }
+#[test]
+fn test_reset_to_initial() {
+    let initial_leaf = "0xabababababababababababababababababababababababababababababababab";
+    let mut tree = MerkleTree::new(4, initial_leaf).unwrap();
+    let fresh = tree.clone();
+    tree.enable_undo(2);
+    tree.append(ZERO_LEAF).unwrap();
+    tree.set(5, ZERO_LEAF).unwrap();
 
-    let leaf_5_bigint = multiplier * BigUint::from(5u32);
-    let leaf_5_string = format!("0x{:064x}", leaf_5_bigint);
+    tree.reset_to_initial(None).unwrap();
+    assert_eq!(tree.nodes, fresh.nodes);
+    assert_eq!(tree.undo(), Err(MerkleError::NothingToUndo));
+    assert_eq!(tree.append(ZERO_LEAF), Ok(0));
 
-    let root = tree.root();
-    let proof = tree.proof(5);
+    let other = format!("0x{:064x}", 9);
+    tree.reset_to_initial(Some(&format!(" {} ", other)))
+        .unwrap();
+    assert_eq!(tree.nodes, MerkleTree::new(4, &other).unwrap().nodes);
+    assert_eq!(tree.initial_leaf(), other);
+    assert_eq!(
+        tree.reset_to_initial(Some("0x12")),
+        Err(MerkleError::InvalidBytes)
+    );
+    assert_eq!(tree.initial_leaf(), other);
 
-    assert_eq!(MerkleTree::verify(&proof, leaf_5_string).unwrap(), root);
+    let mut zeros = MerkleTree::new_with_precomputed_zeros(3).unwrap();
+    zeros.set(1, &other).unwrap();
+    zeros.reset_to_initial(None).unwrap();
+    assert_eq!(zeros.root(), MerkleTree::new(3, ZERO_LEAF).unwrap().root());
+    let mut empty = MerkleTree::empty();
+    empty.reset_to_initial(Some(&other)).unwrap();
+    assert_eq!(empty.root(), EMPTY_ROOT);
+}
+
+#[test]
+fn test_from_leaves_with_leaf_size() {
+    let records: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 64]).collect();
+    let leaves: Vec<String> = records
+        .iter()
+        .map(|record| format!("0x{}", hex::encode(record)))
+        .collect();
+    let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+    let config = TreeConfig::default().leaf_bytes(64).unwrap();
+    assert_eq!(config.leaf_size(), Some(64));
+
+    let tree = MerkleTree::from_leaves_with_config(&leaves, config).unwrap();
+    assert!(tree.validate().is_ok());
+    assert_eq!(tree.num_leaves(), 8);
+    // each 64 byte leaf is hashed into a 32 byte bottom node
+    let slices: Vec<&[u8]> = records.iter().map(Vec::as_slice).collect();
+    assert_eq!(tree.root(), MerkleTree::from_data(&slices).unwrap().root());
+    let proof = tree.proof(3).unwrap();
+    assert_eq!(
+        MerkleTree::verify_data_with(&proof, &records[3], &Sha3_256Hasher::<DIGEST_BYTES>).unwrap(),
+        tree.root()
+    );
+
+    // leaves are validated against the leaf size, not the digest size
+    assert_eq!(
+        MerkleTree::from_leaves_with_config(&[ZERO_LEAF], config).err(),
+        Some(MerkleError::InvalidBytes)
+    );
+    assert_eq!(
+        MerkleTree::from_leaves_with_config(&leaves, TreeConfig::default()).err(),
+        Some(MerkleError::InvalidBytes)
+    );
+    assert_eq!(
+        MerkleTree::from_leaves_with_config(&[ZERO_LEAF], TreeConfig::default())
+            .unwrap()
+            .root(),
+        MerkleTree::from_leaves(&[ZERO_LEAF]).unwrap().root()
+    );
+    assert_eq!(
+        TreeConfig::default().leaf_bytes(0),
+        Err(MerkleError::InvalidBytes)
+    );
 }
@@ -1,2 +1,42 @@
 //! Merkle Tree
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+#[cfg(feature = "tokio")]
+pub mod async_build;
+pub mod batch_verify;
+pub mod caching_store;
+pub mod compact;
+pub mod config;
+pub mod cursor;
+pub mod data_encoder;
+pub mod dirty;
+#[cfg(feature = "ethereum")]
+pub mod ethereum;
+#[cfg(feature = "ff")]
+pub mod field;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+pub mod hasher;
+pub mod leaf_encoder;
+pub mod leaf_lookup;
+#[allow(clippy::module_inception)]
 pub mod merkle_tree;
+pub mod observer;
+pub mod opening;
+pub mod payload;
+pub mod persistent;
+pub mod proof_cache;
+pub mod reader;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+#[cfg(feature = "sled")]
+pub mod sled_store;
+pub mod snapshot;
+pub mod snapshot_file;
+pub mod sorted;
+pub mod sparse;
+pub mod store;
+pub mod trace;
+pub mod undo;
+#[cfg(feature = "zeroize")]
+pub mod zeroize_support;
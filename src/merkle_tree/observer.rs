@@ -0,0 +1,154 @@
+//! Root Change Observers
+//!
+//! Callbacks registered with `MerkleTree::on_root_change` run once at the end of every mutating
+//! operation (`set`, `set_batch`, `append`, ...) that changed the root, with the new root.
+//! Operations that leave the root as it was, like setting a leaf to its current value or an
+//! empty batch, don't notify.
+//!
+//! Callbacks run while the tree is mutably borrowed, so they can't read or mutate the tree; they
+//! should only hand the root off to other systems. A cloned tree starts without observers.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use std::sync::{Mutex, PoisonError};
+
+/// Callback invoked with the new `0x` prefixed root.
+pub type RootCallback = Box<dyn FnMut(&str) + Send>;
+
+/// Identifies a registered callback so it can be removed again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverHandle(u64);
+
+#[derive(Default)]
+pub(crate) struct RootObservers {
+    next_id: u64,
+    // behind a mutex only so trees holding `Send` callbacks stay `Sync`; it is always reached
+    // through `&mut self` and never locked
+    callbacks: Mutex<Vec<(ObserverHandle, RootCallback)>>,
+}
+
+impl RootObservers {
+    fn callbacks(&mut self) -> &mut Vec<(ObserverHandle, RootCallback)> {
+        self.callbacks
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn notify(&mut self, root: &str) {
+        for (_, callback) in self.callbacks().iter_mut() {
+            callback(root);
+        }
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Registers `callback` to be invoked with the new root whenever a mutating operation
+    /// changes it. See the module documentation for when callbacks run.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - invoked with the new `0x` prefixed root.
+    ///
+    /// # Returns
+    ///
+    /// * Handle that removes the callback again with `remove_root_observer`.
+    pub fn on_root_change(&mut self, callback: RootCallback) -> ObserverHandle {
+        let observers = self.root_observers();
+        let handle = ObserverHandle(observers.next_id);
+        observers.next_id += 1;
+        observers.callbacks().push((handle, callback));
+        handle
+    }
+
+    /// Removes a callback registered with `on_root_change`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The handle returned when the callback was registered.
+    ///
+    /// # Returns
+    ///
+    /// * true if the callback was registered and has been removed.
+    pub fn remove_root_observer(&mut self, handle: ObserverHandle) -> bool {
+        let callbacks = self.root_observers().callbacks();
+        let count = callbacks.len();
+        callbacks.retain(|(registered, _)| *registered != handle);
+        callbacks.len() != count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+    use std::sync::Arc;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    // registers an observer recording every root it is called with
+    fn record_roots(tree: &mut MerkleTree) -> (ObserverHandle, Arc<Mutex<Vec<String>>>) {
+        let roots = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&roots);
+        let handle = tree.on_root_change(Box::new(move |root| {
+            recorded.lock().unwrap().push(root.to_string())
+        }));
+        (handle, roots)
+    }
+
+    #[test]
+    fn test_set_notifies_new_root() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        let (_, roots) = record_roots(&mut tree);
+
+        tree.set(1, LEAF).unwrap();
+        assert_eq!(*roots.lock().unwrap(), vec![tree.root()]);
+
+        // same value, same root
+        tree.set(1, LEAF).unwrap();
+        assert_eq!(roots.lock().unwrap().len(), 1);
+
+        // failed operations don't notify
+        assert!(tree.set(9, LEAF).is_err());
+        assert_eq!(roots.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_set_batch_notifies_once() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        let (_, roots) = record_roots(&mut tree);
+
+        tree.set_batch(&[(0, LEAF), (2, LEAF), (3, LEAF)]).unwrap();
+        assert_eq!(*roots.lock().unwrap(), vec![tree.root()]);
+
+        tree.set_batch(&[]).unwrap();
+        tree.set_batch(&[(0, LEAF)]).unwrap();
+        assert_eq!(roots.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_append_growing_notifies_once() {
+        let mut tree = MerkleTree::new(1, ZERO_LEAF).unwrap();
+        let (_, roots) = record_roots(&mut tree);
+        tree.append_growing(LEAF).unwrap();
+        tree.append_growing(LEAF).unwrap();
+        assert_eq!(roots.lock().unwrap().len(), 2);
+        assert_eq!(roots.lock().unwrap()[1], tree.root());
+    }
+
+    #[test]
+    fn test_remove_root_observer() {
+        let mut tree = MerkleTree::new(2, ZERO_LEAF).unwrap();
+        let (first, first_roots) = record_roots(&mut tree);
+        let (_, second_roots) = record_roots(&mut tree);
+
+        assert!(tree.remove_root_observer(first));
+        assert!(!tree.remove_root_observer(first));
+        tree.set(0, LEAF).unwrap();
+        assert!(first_roots.lock().unwrap().is_empty());
+        assert_eq!(second_roots.lock().unwrap().len(), 1);
+
+        // clones start without observers
+        let mut clone = tree.clone();
+        clone.set(1, LEAF).unwrap();
+        assert_eq!(second_roots.lock().unwrap().len(), 1);
+    }
+}
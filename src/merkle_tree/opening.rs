@@ -0,0 +1,517 @@
+//! Binary Proofs and Openings
+//!
+//! `serialize_proof` writes a proof as bytes:
+//!
+//! * 1 byte node width `w`
+//! * 4 byte big-endian number of steps
+//! * per step, 1 byte direction (`0` left, `1` right) followed by the `w` byte sibling
+//!
+//! `MerkleTree::encode_proof_as_hex_string` writes a proof as a single `0x` prefixed hex string
+//! for text based transports: per step, 1 byte direction (`00` left, `01` right) followed by the
+//! 32 byte sibling, with no header. `MerkleTree::verify_from_reader` reads the same 33 byte
+//! steps as raw bytes from a reader, hashing each one as it arrives.
+//!
+//! `MerkleTree::verify_raw` verifies the most compact form: the siblings concatenated as raw
+//! 32 byte nodes, leaf first, plus a bitmask whose bit `i` is set if the proven node is the right
+//! child at step `i` (which for a full proof is the leaf index).
+//!
+//! An opening bundles everything an offline verifier needs to check one leaf against a root
+//! without access to the tree:
+//!
+//! * 8 byte big-endian leaf index
+//! * the 32 byte leaf value
+//! * the 32 byte root
+//! * the serialized proof
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{
+    check_depth, normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES, MAX_DEPTH,
+};
+use std::io::{ErrorKind, Read};
+
+// decodes a `0x` prefixed hex node
+fn node_bytes(node: &str) -> Result<Vec<u8>, MerkleError> {
+    let digits = node.strip_prefix("0x").ok_or(MerkleError::InvalidBytes)?;
+    Ok(hex::decode(digits)?)
+}
+
+// splits `len` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], MerkleError> {
+    if bytes.len() < len {
+        return Err(MerkleError::SerializationError(
+            "unexpected end of input".to_string(),
+        ));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+/// Serializes `proof` into the binary format described in the module documentation.
+///
+/// # Arguments
+///
+/// * `proof` - The proof steps to serialize. All siblings must have the same width.
+///
+/// # Returns
+///
+/// * Result containing the serialized proof or Error.
+pub fn serialize_proof(proof: &[ProofStep]) -> Result<Vec<u8>, MerkleError> {
+    let siblings = proof
+        .iter()
+        .map(|step| node_bytes(step.sibling()))
+        .collect::<Result<Vec<Vec<u8>>, MerkleError>>()?;
+    let width = siblings.first().map_or(DIGEST_BYTES, Vec::len);
+    if width > u8::MAX as usize || siblings.iter().any(|sibling| sibling.len() != width) {
+        return Err(MerkleError::InvalidBytes);
+    }
+
+    let mut bytes = Vec::with_capacity(5 + proof.len() * (width + 1));
+    bytes.push(width as u8);
+    bytes.extend_from_slice(&(proof.len() as u32).to_be_bytes());
+    for (step, sibling) in proof.iter().zip(siblings) {
+        bytes.push(direction_byte(step.direction()));
+        bytes.extend_from_slice(&sibling);
+    }
+    Ok(bytes)
+}
+
+/// Parses a proof written by `serialize_proof`.
+///
+/// # Arguments
+///
+/// * `bytes` - The serialized proof.
+///
+/// # Returns
+///
+/// * Result containing the proof steps or `MerkleError::SerializationError`.
+pub fn deserialize_proof(mut bytes: &[u8]) -> Result<Vec<ProofStep>, MerkleError> {
+    let proof = read_proof(&mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(MerkleError::SerializationError(
+            "trailing bytes after proof".to_string(),
+        ));
+    }
+    Ok(proof)
+}
+
+// reads a direction byte
+fn read_direction(byte: u8) -> Result<Direction, MerkleError> {
+    match byte {
+        0 => Ok(Direction::Left),
+        1 => Ok(Direction::Right),
+        other => Err(MerkleError::SerializationError(format!(
+            "invalid direction {}",
+            other
+        ))),
+    }
+}
+
+// writes a direction byte
+fn direction_byte(direction: &Direction) -> u8 {
+    match direction {
+        Direction::Left => 0,
+        Direction::Right => 1,
+    }
+}
+
+// fills `step` from `reader`, returning false if the input ended before its first byte
+fn read_step<R: Read>(reader: &mut R, step: &mut [u8]) -> Result<bool, MerkleError> {
+    let mut filled = 0;
+    while filled < step.len() {
+        match reader.read(&mut step[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(MerkleError::InvalidFormat),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(MerkleError::SerializationError(e.to_string())),
+        }
+    }
+    Ok(true)
+}
+
+// reads a serialized proof off the front of `bytes`
+fn read_proof(bytes: &mut &[u8]) -> Result<Vec<ProofStep>, MerkleError> {
+    let width = take(bytes, 1)?[0] as usize;
+    let count = u32::from_be_bytes(take(bytes, 4)?.try_into().unwrap()) as usize;
+    let mut proof = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let direction = read_direction(take(bytes, 1)?[0])?;
+        let sibling = format!("0x{}", hex::encode(take(bytes, width)?));
+        proof.push(ProofStep::new(direction, sibling));
+    }
+    Ok(proof)
+}
+
+impl<L> MerkleTree<L> {
+    /// Bundles the value, index and proof of `leaf_index` with the tree's root into a binary
+    /// opening that `verify_exportable` can check without the tree. See the module documentation
+    /// for the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf to open.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the opening or `MerkleError::InvalidIndex`.
+    pub fn exportable_opening(&self, leaf_index: usize) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(leaf_index as u64).to_be_bytes());
+        bytes.extend_from_slice(&node_bytes(self.get_leaf(leaf_index)?)?);
+        bytes.extend_from_slice(&node_bytes(&self.root())?);
+        bytes.extend_from_slice(&serialize_proof(&self.proof(leaf_index)?)?);
+        Ok(bytes)
+    }
+}
+
+impl MerkleTree {
+    /// Encodes `proof` as a single `0x` prefixed hex string, see the module documentation for
+    /// the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to encode. Siblings must be 32 byte hex strings.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the hex string or `MerkleError::InvalidBytes`.
+    pub fn encode_proof_as_hex_string(proof: &[ProofStep]) -> Result<String, MerkleError> {
+        let mut bytes = Vec::with_capacity(proof.len() * (DIGEST_BYTES + 1));
+        for step in proof.iter() {
+            let sibling = node_bytes(step.sibling())?;
+            if sibling.len() != DIGEST_BYTES {
+                return Err(MerkleError::InvalidBytes);
+            }
+            bytes.push(direction_byte(step.direction()));
+            bytes.extend_from_slice(&sibling);
+        }
+        Ok(format!("0x{}", hex::encode(bytes)))
+    }
+
+    /// Decodes a proof written by `encode_proof_as_hex_string`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The `0x` prefixed hex string.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps or Error.
+    pub fn decode_proof_from_hex_string(s: &str) -> Result<Vec<ProofStep>, MerkleError> {
+        let bytes = node_bytes(s)?;
+        if bytes.len() % (DIGEST_BYTES + 1) != 0 {
+            return Err(MerkleError::SerializationError(format!(
+                "{} bytes is not a whole number of proof steps",
+                bytes.len()
+            )));
+        }
+        bytes
+            .chunks(DIGEST_BYTES + 1)
+            .map(|step| {
+                Ok(ProofStep::new(
+                    read_direction(step[0])?,
+                    format!("0x{}", hex::encode(&step[1..])),
+                ))
+            })
+            .collect()
+    }
+
+    /// Calculates the root from a proof read step by step from `reader`, in the layout of
+    /// `encode_proof_as_hex_string` as raw bytes, so the proof is never held in memory at once.
+    /// Reading stops at the end of the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The source of the 33 byte proof steps, e.g. a socket.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root the proof leads to, `MerkleError::InvalidFormat` if the input
+    ///   ends partway through a step or a direction byte is neither `0` nor `1`, or
+    ///   `MerkleError::SerializationError` if reading fails.
+    pub fn verify_from_reader<R: Read>(
+        mut reader: R,
+        leaf_value: String,
+    ) -> Result<String, MerkleError> {
+        let hasher = Sha3_256Hasher::<DIGEST_BYTES>;
+        let mut current = node_bytes(normalize_node(&leaf_value, DIGEST_BYTES)?)?;
+        let mut step = [0u8; DIGEST_BYTES + 1];
+        while read_step(&mut reader, &mut step)? {
+            let sibling = &step[1..];
+            current = match step[0] {
+                0 => hasher.hash_pair(&current, sibling),
+                1 => hasher.hash_pair(sibling, &current),
+                _ => return Err(MerkleError::InvalidFormat),
+            };
+        }
+        Ok(format!("0x{}", hex::encode(current)))
+    }
+
+    /// Given raw siblings and a direction bitmask, calculates and returns the root, see the module
+    /// documentation for the format. No `ProofStep`s or hex strings are built.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings` - The `num_steps` siblings, 32 bytes each, leaf first.
+    /// * `directions` - Bit `i` set if the proven node is the right child at step `i`. Bits at or
+    ///   above `num_steps` must be clear.
+    /// * `num_steps` - The number of proof steps.
+    /// * `leaf` - The leaf you want to verify the proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root, `MerkleError::InvalidBytes` if `siblings` isn't a whole
+    ///   number of nodes, `MerkleError::ProofLengthMismatch` if it doesn't hold `num_steps`
+    ///   siblings, or `MerkleError::InvalidIndex` if `directions` has bits set
+    ///   beyond the proof.
+    pub fn verify_raw(
+        siblings: &[u8],
+        directions: u64,
+        num_steps: usize,
+        leaf: [u8; 32],
+    ) -> Result<[u8; 32], MerkleError> {
+        check_depth(num_steps.saturating_add(1), MAX_DEPTH)?;
+        if !siblings.len().is_multiple_of(DIGEST_BYTES) {
+            return Err(MerkleError::InvalidBytes);
+        }
+        if siblings.len() != num_steps * DIGEST_BYTES {
+            return Err(MerkleError::ProofLengthMismatch {
+                expected: num_steps,
+                actual: siblings.len() / DIGEST_BYTES,
+            });
+        }
+        if directions >> num_steps != 0 {
+            return Err(MerkleError::InvalidIndex);
+        }
+
+        let hasher = Sha3_256Hasher::<DIGEST_BYTES>;
+        let mut current = leaf.to_vec();
+        for (step, sibling) in siblings.chunks_exact(DIGEST_BYTES).enumerate() {
+            current = if (directions >> step) & 1 == 1 {
+                hasher.hash_pair(sibling, &current)
+            } else {
+                hasher.hash_pair(&current, sibling)
+            };
+        }
+        Ok(current.try_into().expect("SHA3-256 digests are 32 bytes"))
+    }
+}
+
+/// Checks an opening produced by `MerkleTree::exportable_opening`: the proof must lead from the
+/// leaf to the root and its directions must match the leaf index.
+///
+/// # Arguments
+///
+/// * `blob` - The serialized opening.
+///
+/// # Returns
+///
+/// * Result containing whether the opening is valid, or `MerkleError::SerializationError` if
+///   it cannot be parsed.
+pub fn verify_exportable(mut blob: &[u8]) -> Result<bool, MerkleError> {
+    let leaf_index = u64::from_be_bytes(take(&mut blob, 8)?.try_into().unwrap()) as usize;
+    let leaf = format!("0x{}", hex::encode(take(&mut blob, DIGEST_BYTES)?));
+    let root = format!("0x{}", hex::encode(take(&mut blob, DIGEST_BYTES)?));
+    let proof = deserialize_proof(blob)?;
+
+    let index_matches = proof.len() < usize::BITS as usize
+        && leaf_index >> proof.len() == 0
+        && proof.iter().enumerate().all(|(level, step)| {
+            (*step.direction() == Direction::Right) == ((leaf_index >> level) & 1 == 1)
+        });
+    Ok(index_matches && MerkleTree::verify(&proof, leaf)? == root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+    use crate::utils::random_updates;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+
+    fn tree() -> MerkleTree {
+        let leaves: Vec<String> = (1..=6).map(|i| format!("0x{:064x}", i)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        MerkleTree::from_leaves(&leaves).unwrap()
+    }
+
+    #[test]
+    fn test_proof_round_trip() {
+        let tree = tree();
+        let proof = tree.proof(5).unwrap();
+        let bytes = serialize_proof(&proof).unwrap();
+        assert_eq!(bytes.len(), 5 + 3 * 33);
+
+        let parsed = deserialize_proof(&bytes).unwrap();
+        assert_eq!(parsed.len(), proof.len());
+        for (parsed, step) in parsed.iter().zip(proof.iter()) {
+            assert_eq!(parsed.direction(), step.direction());
+            assert_eq!(parsed.sibling(), step.sibling());
+        }
+
+        assert!(matches!(
+            deserialize_proof(&bytes[..bytes.len() - 1]),
+            Err(MerkleError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_proof_hex_string_round_trip() {
+        let tree = tree();
+        let proof = tree.proof(5).unwrap();
+        let encoded = MerkleTree::encode_proof_as_hex_string(&proof).unwrap();
+        assert_eq!(encoded.len(), 2 + 2 * 3 * 33);
+        // leaf 5 is a right child at the first step
+        assert!(encoded.starts_with(&format!("0x01{}", &proof[0].sibling()[2..])));
+        assert_eq!(
+            MerkleTree::encode_proof_as_hex_string(&proof).unwrap(),
+            encoded
+        );
+
+        let decoded = MerkleTree::decode_proof_from_hex_string(&encoded).unwrap();
+        assert_eq!(
+            MerkleTree::verify(&decoded, tree.get_leaf(5).unwrap().to_string()).unwrap(),
+            tree.root()
+        );
+        assert_eq!(MerkleTree::encode_proof_as_hex_string(&[]).unwrap(), "0x");
+        assert!(MerkleTree::decode_proof_from_hex_string("0x")
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(
+            MerkleTree::decode_proof_from_hex_string(&encoded[..encoded.len() - 2]),
+            Err(MerkleError::SerializationError(_))
+        ));
+        let bad_direction = format!("0x02{}", &encoded[4..]);
+        assert!(matches!(
+            MerkleTree::decode_proof_from_hex_string(&bad_direction),
+            Err(MerkleError::SerializationError(_))
+        ));
+    }
+
+    // yields one byte per read, as a slow socket might
+    struct Trickle<R>(R);
+
+    impl<R: Read> Read for Trickle<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_verify_from_reader() {
+        let tree = tree();
+        let leaf = tree.get_leaf(5).unwrap().to_string();
+        let encoded = MerkleTree::encode_proof_as_hex_string(&tree.proof(5).unwrap()).unwrap();
+        let bytes = node_bytes(&encoded).unwrap();
+
+        let root = MerkleTree::verify_from_reader(Cursor::new(bytes.clone()), leaf.clone());
+        assert_eq!(root.unwrap(), tree.root());
+        let trickled =
+            MerkleTree::verify_from_reader(Trickle(Cursor::new(bytes.clone())), leaf.clone());
+        assert_eq!(trickled.unwrap(), tree.root());
+        assert_eq!(
+            MerkleTree::verify_from_reader(Cursor::new(Vec::new()), leaf.clone()).unwrap(),
+            leaf
+        );
+
+        assert_eq!(
+            MerkleTree::verify_from_reader(
+                Cursor::new(bytes[..bytes.len() - 1].to_vec()),
+                leaf.clone()
+            ),
+            Err(MerkleError::InvalidFormat)
+        );
+        let mut bad_direction = bytes;
+        bad_direction[33] = 2;
+        assert_eq!(
+            MerkleTree::verify_from_reader(Cursor::new(bad_direction), leaf),
+            Err(MerkleError::InvalidFormat)
+        );
+        assert_eq!(
+            MerkleTree::verify_from_reader(Cursor::new(Vec::new()), "0x00".to_string()),
+            Err(MerkleError::InvalidBytes)
+        );
+    }
+
+    #[test]
+    fn test_exportable_opening_round_trip() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for depth in 1..=5 {
+            let mut tree = MerkleTree::random(depth, &mut rng);
+            random_updates(&mut tree, 4, &mut rng).unwrap();
+            for leaf_index in 0..tree.num_leaves() {
+                let blob = tree.exportable_opening(leaf_index).unwrap();
+                assert!(verify_exportable(&blob).unwrap());
+            }
+        }
+        let tree = tree();
+        assert_eq!(tree.exportable_opening(8), Err(MerkleError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_verify_exportable_rejects_tampering() {
+        let tree = tree();
+        let blob = tree.exportable_opening(2).unwrap();
+
+        // claim the proof opens a different index
+        let mut wrong_index = blob.clone();
+        wrong_index[7] = 3;
+        assert!(!verify_exportable(&wrong_index).unwrap());
+
+        // swap in a different leaf value
+        let mut wrong_leaf = blob.clone();
+        wrong_leaf[8..40].copy_from_slice(&node_bytes(ZERO_LEAF).unwrap());
+        assert!(!verify_exportable(&wrong_leaf).unwrap());
+
+        assert!(matches!(
+            verify_exportable(&blob[..20]),
+            Err(MerkleError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_raw_matches_verify() {
+        let tree = tree();
+        for leaf_index in [0, 5] {
+            let proof = tree.proof(leaf_index).unwrap();
+            let siblings: Vec<u8> = proof
+                .iter()
+                .flat_map(|step| node_bytes(step.sibling()).unwrap())
+                .collect();
+            let leaf = tree.get_leaf(leaf_index).unwrap();
+            let root = MerkleTree::verify_raw(
+                &siblings,
+                leaf_index as u64,
+                proof.len(),
+                node_bytes(leaf).unwrap().try_into().unwrap(),
+            )
+            .unwrap();
+            let expected = MerkleTree::verify(&proof, leaf.to_string()).unwrap();
+            assert_eq!(format!("0x{}", hex::encode(root)), expected);
+            assert_eq!(expected, tree.root());
+        }
+
+        let siblings = [0u8; 3 * 32];
+        assert_eq!(
+            MerkleTree::verify_raw(&siblings[..64], 0, 3, [0; 32]),
+            Err(MerkleError::ProofLengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            MerkleTree::verify_raw(&siblings[..95], 0, 3, [0; 32]),
+            Err(MerkleError::InvalidBytes)
+        );
+        assert_eq!(
+            MerkleTree::verify_raw(&siblings, 0b1000, 3, [0; 32]),
+            Err(MerkleError::InvalidIndex)
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Leaf Payloads
+//!
+//! A `MerkleTree<L>` with `L: AsRef<[u8]> + Clone` keeps the original payload of every leaf
+//! written through the typed accessors next to the leaf hash computed from it with the tree's
+//! hasher, `hasher.digest(payload.as_ref())`. `leaf` hands the payload back, while nodes, proofs
+//! and roots only ever see the hash, so payloads of any length, e.g. `Vec<u8>` or
+//! `bytes::Bytes`, work without converting to hex.
+//!
+//! A leaf written as a hash, with `set` and the other hex based writes, or restored by `undo` or
+//! `rollback`, has no payload until the next `set_leaf`.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{MerkleTree, ProofStep, DIGEST_BYTES};
+
+// the `0x` prefixed leaf hash of `payload`
+fn payload_leaf<H: MerkleHasher>(hasher: &H, payload: &[u8]) -> String {
+    format!("0x{}", hex::encode(hasher.digest(payload)))
+}
+
+impl<L: AsRef<[u8]> + Clone, H: MerkleHasher> MerkleTree<L, H> {
+    /// returns the `0x` prefixed leaf hash the tree stores for `payload`
+    pub fn hash_payload(&self, payload: &L) -> String {
+        payload_leaf(self.hasher(), payload.as_ref())
+    }
+
+    /// Returns the payload stored at `leaf_index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to read.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the payload, `None` if the leaf was not written as a payload, or
+    ///   `MerkleError::InvalidIndex`.
+    pub fn leaf(&self, leaf_index: usize) -> Result<Option<&L>, MerkleError> {
+        if leaf_index >= self.num_leaves() {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Ok(self.payloads_ref().get(leaf_index).and_then(Option::as_ref))
+    }
+
+    /// Sets the leaf at `leaf_index` to the hash of `value`, keeps `value` as its payload and
+    /// re-calculates the merkle root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `value` - The new payload for the leaf.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success or error
+    pub fn set_leaf(&mut self, leaf_index: usize, value: &L) -> Result<(), MerkleError> {
+        self.set(leaf_index, &self.hash_payload(value))?;
+        let payloads = self.payloads_mut();
+        if payloads.len() <= leaf_index {
+            payloads.resize(leaf_index + 1, None);
+        }
+        payloads[leaf_index] = Some(value.clone());
+        Ok(())
+    }
+}
+
+impl<L: AsRef<[u8]> + Clone> MerkleTree<L> {
+    /// Given `depth` (one indexed) and `initial_leaf`, constructs a merkle tree whose leaves all
+    /// hold the payload `initial_leaf`. Typed counterpart of `MerkleTree::new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - payload assigned to every leaf.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn with_leaf(depth: usize, initial_leaf: &L) -> Result<Self, MerkleError> {
+        let leaf = payload_leaf(&Sha3_256Hasher::<DIGEST_BYTES>, initial_leaf.as_ref());
+        let mut tree: Self = MerkleTree::new(depth, &leaf)?.with_payload();
+        let leaf_count = tree.num_leaves();
+        *tree.payloads_mut() = vec![Some(initial_leaf.clone()); leaf_count];
+        Ok(tree)
+    }
+
+    /// Constructs a merkle tree whose leaves are the hashes of `leaves`, padded like
+    /// `MerkleTree::from_leaves`. Typed counterpart of `MerkleTree::from_leaves`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf payloads, in order.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree or Error.
+    pub fn from_payloads(leaves: &[L]) -> Result<Self, MerkleError> {
+        let hashes: Vec<String> = leaves
+            .iter()
+            .map(|leaf| payload_leaf(&Sha3_256Hasher::<DIGEST_BYTES>, leaf.as_ref()))
+            .collect();
+        let hashes: Vec<&str> = hashes.iter().map(String::as_str).collect();
+        let mut tree: Self = MerkleTree::from_leaves(&hashes)?.with_payload();
+        *tree.payloads_mut() = leaves.iter().cloned().map(Some).collect();
+        Ok(tree)
+    }
+
+    /// Given a `proof` and the payload of the proven leaf, calculates and returns the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - `Vec<ProofStep>` containing the proof steps to be verified.
+    /// * `leaf_value` - The payload of the leaf you want to verify proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root of the tree or Error.
+    pub fn verify_leaf(proof: &[ProofStep], leaf_value: &L) -> Result<String, MerkleError> {
+        let leaf = payload_leaf(&Sha3_256Hasher::<DIGEST_BYTES>, leaf_value.as_ref());
+        MerkleTree::verify(proof, leaf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_array_tree_round_trip() {
+        let mut tree: MerkleTree<[u8; 32]> = MerkleTree::with_leaf(3, &[0u8; 32]).unwrap();
+        tree.set_leaf(2, &[7u8; 32]).unwrap();
+        assert_eq!(tree.leaf(2).unwrap(), Some(&[7u8; 32]));
+        assert_eq!(tree.leaf(1).unwrap(), Some(&[0u8; 32]));
+        assert_eq!(tree.leaf(4), Err(MerkleError::InvalidIndex));
+
+        let proof = tree.proof(2).unwrap();
+        assert_eq!(
+            MerkleTree::verify_leaf(&proof, &[7u8; 32]).unwrap(),
+            tree.root()
+        );
+        assert_ne!(
+            MerkleTree::verify_leaf(&proof, &[0u8; 32]).unwrap(),
+            tree.root()
+        );
+    }
+
+    #[test]
+    fn test_payloads_of_any_length() {
+        let leaves = [b"a".to_vec(), vec![2u8; 100], Vec::new()];
+        let tree: MerkleTree<Vec<u8>> = MerkleTree::from_payloads(&leaves).unwrap();
+        assert_eq!(tree.num_leaves(), 4);
+        for (leaf_index, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.leaf(leaf_index).unwrap(), Some(leaf));
+            let proof = tree.proof(leaf_index).unwrap();
+            assert_eq!(MerkleTree::verify_leaf(&proof, leaf).unwrap(), tree.root());
+        }
+        // the padding leaf holds no payload
+        assert_eq!(tree.leaf(3).unwrap(), None);
+
+        // the stored hash is the same for every payload type with the same bytes
+        let arrays: MerkleTree<[u8; 1]> = MerkleTree::from_payloads(&[*b"a"]).unwrap();
+        let strings = MerkleTree::from_payloads(&["a".to_string()]).unwrap();
+        assert_eq!(arrays.root(), strings.root());
+        assert_eq!(arrays.get_leaf(0), Ok(tree.get_leaf(0).unwrap()));
+    }
+
+    #[test]
+    fn test_hash_writes_drop_payloads() {
+        let mut tree: MerkleTree<Vec<u8>> = MerkleTree::with_leaf(2, &vec![1, 2, 3]).unwrap();
+        tree.enable_undo(4);
+        tree.set_leaf(0, &vec![4; 40]).unwrap();
+        assert_eq!(tree.leaf(0).unwrap(), Some(&vec![4; 40]));
+
+        let leaf = format!("0x{:064x}", 5);
+        tree.set(1, &leaf).unwrap();
+        assert_eq!(tree.leaf(1).unwrap(), None);
+
+        // the restored hash no longer matches the overwritten payload
+        tree.undo().unwrap();
+        tree.undo().unwrap();
+        assert_eq!(tree.leaf(0).unwrap(), None);
+        assert_eq!(
+            tree.get_leaf(0),
+            Ok(tree.hash_payload(&vec![1, 2, 3]).as_str())
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes_payload() {
+        let mut tree: MerkleTree<bytes::Bytes> =
+            MerkleTree::with_leaf(2, &bytes::Bytes::from_static(b"")).unwrap();
+        let payload = bytes::Bytes::from_static(b"not a 32 byte leaf");
+        tree.set_leaf(1, &payload).unwrap();
+        assert_eq!(tree.leaf(1).unwrap(), Some(&payload));
+        let proof = tree.proof(1).unwrap();
+        assert_eq!(
+            MerkleTree::verify_leaf(&proof, &payload).unwrap(),
+            tree.root()
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Persistent Trees
+//!
+//! `PersistentMerkleTree` is an immutable tree whose nodes are individually `Arc` shared. `set`
+//! copies only the path from the root to the written leaf and returns a new version sharing every
+//! other node with its parent, so old versions stay usable and cloning a version is `O(1)`. This
+//! suits readers that keep serving proofs from a version while a writer derives newer ones.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{
+    hash_nodes, normalize_node, Direction, MerkleTree, ProofStep,
+};
+use std::sync::Arc;
+
+enum Node {
+    Leaf(String),
+    Branch {
+        hash: String,
+        left: Arc<Node>,
+        right: Arc<Node>,
+    },
+}
+
+impl Node {
+    fn hash(&self) -> &str {
+        match self {
+            Node::Leaf(hash) | Node::Branch { hash, .. } => hash,
+        }
+    }
+}
+
+/// Immutable, structurally shared merkle tree, see the module documentation.
+#[derive(Clone)]
+pub struct PersistentMerkleTree<H = Sha3_256Hasher> {
+    // `None` for a tree without leaves
+    root: Option<Arc<Node>>,
+    depth: usize,
+    next_leaf: usize,
+    hasher: H,
+}
+
+// builds the subtree rooted at breadth-first `index` of `nodes`
+fn build_node(nodes: &[String], index: usize) -> Arc<Node> {
+    let left = 2 * index + 1;
+    if left >= nodes.len() {
+        return Arc::new(Node::Leaf(nodes[index].clone()));
+    }
+    Arc::new(Node::Branch {
+        hash: nodes[index].clone(),
+        left: build_node(nodes, left),
+        right: build_node(nodes, left + 1),
+    })
+}
+
+// writes the subtree `node` into `nodes` starting at breadth-first `index`
+fn flatten_node(node: &Node, index: usize, nodes: &mut [String]) {
+    nodes[index] = node.hash().to_string();
+    if let Node::Branch { left, right, .. } = node {
+        flatten_node(left, 2 * index + 1, nodes);
+        flatten_node(right, 2 * index + 2, nodes);
+    }
+}
+
+impl<L, H: MerkleHasher + Clone> MerkleTree<L, H> {
+    /// returns a persistent copy of the tree, see `PersistentMerkleTree`
+    pub fn to_persistent(&self) -> PersistentMerkleTree<H> {
+        let (nodes, next_leaf, _) = self.state();
+        PersistentMerkleTree {
+            root: (!nodes.is_empty()).then(|| build_node(nodes, 0)),
+            depth: self.depth(),
+            next_leaf,
+            hasher: self.hasher().clone(),
+        }
+    }
+}
+
+impl<H: MerkleHasher + Clone> PersistentMerkleTree<H> {
+    /// returns the root of the tree, or the hash of the empty string if the tree has no leaves
+    pub fn root(&self) -> String {
+        match &self.root {
+            Some(root) => root.hash().to_string(),
+            None => format!("0x{}", hex::encode(self.hasher.digest(&[]))),
+        }
+    }
+
+    /// returns the depth (one indexed) of the tree, i.e. the number of levels including the root
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// returns the number of leaves in the tree
+    pub fn num_leaves(&self) -> usize {
+        match self.depth {
+            0 => 0,
+            depth => 1 << (depth - 1),
+        }
+    }
+
+    // returns the nodes on the path from the root down to `leaf_index`, root first
+    fn path(&self, leaf_index: usize) -> Result<Vec<&Arc<Node>>, MerkleError> {
+        let root = match &self.root {
+            Some(root) if leaf_index < self.num_leaves() => root,
+            _ => return Err(MerkleError::InvalidIndex),
+        };
+        let mut path = vec![root];
+        for height in (0..self.depth - 1).rev() {
+            let Node::Branch { left, right, .. } = &**path[path.len() - 1] else {
+                unreachable!("branches reach down to the leaf level");
+            };
+            path.push(if (leaf_index >> height) & 1 == 1 {
+                right
+            } else {
+                left
+            });
+        }
+        Ok(path)
+    }
+
+    /// Returns the value stored at `leaf_index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to read.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf or `MerkleError::InvalidIndex`.
+    pub fn leaf(&self, leaf_index: usize) -> Result<&str, MerkleError> {
+        Ok(self.path(leaf_index)?.last().unwrap().hash())
+    }
+
+    /// Constructs the proof for `leaf_index`, see `MerkleTree::proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps, leaf first, or `MerkleError::InvalidIndex`.
+    pub fn proof(&self, leaf_index: usize) -> Result<Vec<ProofStep>, MerkleError> {
+        let path = self.path(leaf_index)?;
+        let mut proof = Vec::with_capacity(path.len() - 1);
+        for (height, parent) in path.iter().rev().skip(1).enumerate() {
+            let Node::Branch { left, right, .. } = &***parent else {
+                unreachable!("every node above a leaf is a branch");
+            };
+            proof.push(if (leaf_index >> height) & 1 == 1 {
+                ProofStep::new(Direction::Right, left.hash().to_string())
+            } else {
+                ProofStep::new(Direction::Left, right.hash().to_string())
+            });
+        }
+        Ok(proof)
+    }
+
+    /// Returns a new version with the leaf at `leaf_index` set to `value`. The new version shares
+    /// every node off the written path with `self`, which is left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `value` - The new value for the leaf. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new version or Error.
+    pub fn set(&self, leaf_index: usize, value: &str) -> Result<Self, MerkleError> {
+        let value = normalize_node(value, H::DIGEST_BYTES)?;
+        let path = self.path(leaf_index)?;
+
+        // rebuild the path bottom up, reusing the sibling of every rebuilt node
+        let mut node = Arc::new(Node::Leaf(value.to_string()));
+        for (height, parent) in path.iter().rev().skip(1).enumerate() {
+            let Node::Branch { left, right, .. } = &***parent else {
+                unreachable!("every node above a leaf is a branch");
+            };
+            let (left, right) = if (leaf_index >> height) & 1 == 1 {
+                (Arc::clone(left), node)
+            } else {
+                (node, Arc::clone(right))
+            };
+            node = Arc::new(Node::Branch {
+                hash: hash_nodes(&self.hasher, left.hash(), right.hash())?,
+                left,
+                right,
+            });
+        }
+        Ok(PersistentMerkleTree {
+            root: Some(node),
+            depth: self.depth,
+            next_leaf: self.next_leaf,
+            hasher: self.hasher.clone(),
+        })
+    }
+
+    /// returns a mutable copy of this version
+    pub fn to_mutable(&self) -> MerkleTree<String, H> {
+        let mut nodes = vec![String::new(); (1 << self.depth) - 1];
+        if let Some(root) = &self.root {
+            flatten_node(root, 0, &mut nodes);
+        }
+        let mut tree = MerkleTree::with_nodes(Vec::new(), self.hasher.clone());
+        tree.restore_state(nodes, self.next_leaf, None);
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves() -> Vec<String> {
+        (1..=4).map(|i| format!("0x{:064x}", i)).collect()
+    }
+
+    #[test]
+    fn test_versions_share_untouched_nodes() {
+        let leaves = leaves();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let tree = MerkleTree::from_leaves(&leaves).unwrap();
+        let v1 = tree.to_persistent();
+        let v1_proof = v1.proof(0).unwrap();
+
+        let value = format!("0x{}", "ab".repeat(32));
+        let v2 = v1.set(3, &value).unwrap();
+
+        // v1 is untouched
+        assert_eq!(v1.root(), tree.root());
+        assert_eq!(v1.leaf(3).unwrap(), leaves[3]);
+        assert_eq!(v1.proof(0).unwrap(), v1_proof);
+
+        // v2 matches a mutable tree with the same write
+        let mut expected = tree.clone();
+        expected.set(3, &value).unwrap();
+        assert_eq!(v2.root(), expected.root());
+        assert_eq!(v2.leaf(3).unwrap(), value);
+        for i in 0..4 {
+            assert_eq!(v2.proof(i).unwrap(), expected.proof(i).unwrap());
+        }
+
+        // the left half is shared by both versions, the right half was copied
+        let children = |tree: &PersistentMerkleTree| match tree.root.as_deref() {
+            Some(Node::Branch { left, right, .. }) => (Arc::clone(left), Arc::clone(right)),
+            _ => unreachable!(),
+        };
+        let ((v1_left, v1_right), (v2_left, v2_right)) = (children(&v1), children(&v2));
+        assert!(Arc::ptr_eq(&v1_left, &v2_left));
+        assert!(!Arc::ptr_eq(&v1_right, &v2_right));
+        // held by the roots of v1 and v2 plus the two handles above
+        assert_eq!(Arc::strong_count(&v1_left), 4);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut tree = MerkleTree::new(3, &leaves()[0]).unwrap();
+        tree.append(&leaves()[1]).unwrap();
+        let restored = tree.to_persistent().to_mutable();
+        assert_eq!(restored.state(), tree.state());
+
+        let empty = MerkleTree::empty().to_persistent();
+        assert_eq!(empty.root(), MerkleTree::empty().root());
+        assert_eq!(empty.proof(0), Err(MerkleError::InvalidIndex));
+        assert_eq!(empty.to_mutable().num_leaves(), 0);
+    }
+
+    #[test]
+    fn test_set_invalid() {
+        let v1 = MerkleTree::new(2, &leaves()[0]).unwrap().to_persistent();
+        assert_eq!(
+            v1.set(2, &leaves()[1]).err(),
+            Some(MerkleError::InvalidIndex)
+        );
+        assert_eq!(v1.set(0, "0x00").err(), Some(MerkleError::InvalidBytes));
+    }
+}
@@ -0,0 +1,211 @@
+//! Proof Caching
+//!
+//! `MerkleTree::enable_proof_cache` keeps up to `capacity` recently generated proofs, so hot
+//! leaves proven many times between mutations are served without rebuilding their proofs.
+//! Entries are evicted oldest first once the cache is full.
+//!
+//! Invalidation is precise. A proof for leaf `j` holds the siblings of `j`'s path, and for any
+//! other leaf `k` one of those siblings lies on `k`'s path: the child of their lowest common
+//! ancestor on `k`'s side. So writing leaf `k` drops every cached proof except `k`'s own, whose
+//! siblings are untouched by the write. Operations that rewrite internal nodes or the shape of
+//! the tree (`append_growing` growing the tree, `repair`, `recompute_subtree`, `rollback`) clear
+//! the whole cache.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::{MerkleTree, ProofStep};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError};
+
+#[derive(Default)]
+struct CacheEntries {
+    proofs: HashMap<usize, Vec<ProofStep>>,
+    // cached leaf indices, oldest first
+    order: VecDeque<usize>,
+    hits: u64,
+    misses: u64,
+}
+
+pub(crate) struct ProofCache {
+    capacity: usize,
+    // `proof` fills the cache through `&self`; invalidation goes through `&mut self` and never
+    // locks
+    entries: Mutex<CacheEntries>,
+}
+
+impl ProofCache {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        ProofCache {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    // an empty cache of the same capacity, used when cloning a tree
+    pub(crate) fn empty_copy(&self) -> Self {
+        ProofCache::with_capacity(self.capacity)
+    }
+
+    fn entries(&mut self) -> &mut CacheEntries {
+        self.entries
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // returns the cached proof for `leaf_index`, counting the lookup as a hit or a miss
+    pub(crate) fn get(&self, leaf_index: usize) -> Option<Vec<ProofStep>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let proof = entries.proofs.get(&leaf_index).cloned();
+        match proof {
+            Some(_) => entries.hits += 1,
+            None => entries.misses += 1,
+        }
+        proof
+    }
+
+    pub(crate) fn insert(&self, leaf_index: usize, proof: &[ProofStep]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.proofs.contains_key(&leaf_index) {
+            return;
+        }
+        if entries.order.len() == self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.proofs.remove(&oldest);
+            }
+        }
+        entries.order.push_back(leaf_index);
+        entries.proofs.insert(leaf_index, proof.to_vec());
+    }
+
+    // keeps only the proof for `leaf_index`, the one proof a write to that leaf leaves valid
+    pub(crate) fn retain_leaf(&mut self, leaf_index: usize) {
+        let entries = self.entries();
+        entries.proofs.retain(|&index, _| index == leaf_index);
+        entries.order.retain(|&index| index == leaf_index);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let entries = self.entries();
+        entries.proofs.clear();
+        entries.order.clear();
+    }
+
+    // overwrites every cached proof before dropping it
+    #[cfg(feature = "zeroize")]
+    pub(crate) fn zeroize(&mut self) {
+        use zeroize::Zeroize;
+        let entries = self.entries();
+        for proof in entries.proofs.values_mut() {
+            proof.zeroize();
+        }
+        self.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .proofs
+            .len()
+    }
+
+    fn counters(&self) -> (u64, u64) {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        (entries.hits, entries.misses)
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Starts caching up to `capacity` proofs, see the module documentation. Replaces any
+    /// existing cache, dropping its proofs and counters.
+    pub fn enable_proof_cache(&mut self, capacity: usize) {
+        *self.proof_cache_mut() = Some(ProofCache::with_capacity(capacity));
+    }
+
+    /// Stops caching proofs and drops the cache.
+    pub fn disable_proof_cache(&mut self) {
+        *self.proof_cache_mut() = None;
+    }
+
+    /// returns the number of proofs currently cached
+    pub fn proof_cache_len(&self) -> usize {
+        self.proof_cache_ref().map_or(0, ProofCache::len)
+    }
+
+    /// returns how many `proof` calls were served from the cache since it was enabled
+    pub fn proof_cache_hits(&self) -> u64 {
+        self.proof_cache_ref().map_or(0, |cache| cache.counters().0)
+    }
+
+    /// returns how many `proof` calls missed the cache since it was enabled
+    pub fn proof_cache_misses(&self) -> u64 {
+        self.proof_cache_ref().map_or(0, |cache| cache.counters().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> MerkleTree {
+        let leaves: Vec<String> = (0..8).map(|i| format!("0x{:064x}", i)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let mut tree = MerkleTree::from_leaves(&leaves).unwrap();
+        tree.enable_proof_cache(4);
+        tree
+    }
+
+    #[test]
+    fn test_cache_hit_returns_equal_proof() {
+        let tree = tree();
+        let first = tree.proof(3).unwrap();
+        assert_eq!((tree.proof_cache_hits(), tree.proof_cache_misses()), (0, 1));
+        let second = tree.proof(3).unwrap();
+        assert_eq!((tree.proof_cache_hits(), tree.proof_cache_misses()), (1, 1));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_sibling_write_invalidates() {
+        let mut tree = tree();
+        tree.proof(3).unwrap();
+        tree.set(2, &format!("0x{:064x}", 42)).unwrap();
+        assert_eq!(tree.proof_cache_len(), 0);
+
+        let proof = tree.proof(3).unwrap();
+        assert_eq!(tree.proof_cache_misses(), 2);
+        assert_eq!(
+            MerkleTree::verify(&proof, tree.get_leaf(3).unwrap().to_string()).unwrap(),
+            tree.root()
+        );
+    }
+
+    #[test]
+    fn test_own_write_keeps_proof() {
+        let mut tree = tree();
+        tree.proof(3).unwrap();
+        tree.proof(5).unwrap();
+        let value = format!("0x{:064x}", 42);
+        tree.set(3, &value).unwrap();
+        assert_eq!(tree.proof_cache_len(), 1);
+
+        let cached = tree.proof(3).unwrap();
+        assert_eq!(tree.proof_cache_hits(), 1);
+        assert_eq!(MerkleTree::verify(&cached, value).unwrap(), tree.root());
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let mut tree = tree();
+        for i in 0..5 {
+            tree.proof(i).unwrap();
+        }
+        assert_eq!(tree.proof_cache_len(), 4);
+        tree.proof(0).unwrap();
+        assert_eq!(tree.proof_cache_hits(), 0);
+
+        tree.disable_proof_cache();
+        assert_eq!(tree.proof_cache_len(), 0);
+    }
+}
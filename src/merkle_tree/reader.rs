@@ -0,0 +1,86 @@
+//! Read-only tree views
+//!
+//! `TreeReader` borrows a `MerkleTree` and only exposes read operations, so a single tree can be
+//! shared with worker threads that serve proofs while nothing is able to mutate it.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{MerkleTree, ProofStep};
+
+/// Cheaply copyable, read-only view of a `MerkleTree` that can be sent across threads.
+pub struct TreeReader<'a, L = String, H = Sha3_256Hasher> {
+    tree: &'a MerkleTree<L, H>,
+}
+
+impl<L, H> Clone for TreeReader<'_, L, H> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<L, H> Copy for TreeReader<'_, L, H> {}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// returns a read-only view of the tree
+    pub fn as_reader(&self) -> TreeReader<'_, L, H> {
+        TreeReader { tree: self }
+    }
+}
+
+impl<'a, L, H: MerkleHasher> TreeReader<'a, L, H> {
+    /// returns the root of the tree
+    pub fn root(&self) -> String {
+        self.tree.root()
+    }
+
+    /// returns the number of leaves in the tree
+    pub fn num_leaves(&self) -> usize {
+        self.tree.num_leaves()
+    }
+
+    /// Returns the value stored at `leaf_index`, see `MerkleTree::get_leaf`.
+    pub fn get(&self, leaf_index: usize) -> Result<&'a str, MerkleError> {
+        self.tree.get_leaf(leaf_index)
+    }
+
+    /// Constructs the proof for `leaf_index`, see `MerkleTree::proof`.
+    pub fn proof(&self, leaf_index: usize) -> Result<Vec<ProofStep>, MerkleError> {
+        self.tree.proof(leaf_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::thread;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_reader_is_send_sync() {
+        assert_send_sync::<MerkleTree>();
+        assert_send_sync::<TreeReader<'_>>();
+    }
+
+    #[test]
+    fn test_concurrent_proofs() {
+        let tree = MerkleTree::random(5, &mut StdRng::seed_from_u64(5));
+        let reader = tree.as_reader();
+        let root = reader.root();
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..reader.num_leaves())
+                .map(|i| {
+                    scope.spawn(move || {
+                        let leaf = reader.get(i).unwrap().to_string();
+                        MerkleTree::verify(&reader.proof(i).unwrap(), leaf).unwrap()
+                    })
+                })
+                .collect();
+            for worker in workers {
+                assert_eq!(worker.join().unwrap(), root);
+            }
+        });
+    }
+}
@@ -0,0 +1,380 @@
+//! JSON Serialization
+//!
+//! `to_json` and `try_from_json` read and write a tree in a stable JSON format, documented by
+//! `schema/merkle_tree.schema.json`:
+//!
+//! ```json
+//! {"depth": 2, "nodes": ["0x<root>", "0x<leaf 0>", "0x<leaf 1>"]}
+//! ```
+//!
+//! `depth` is one indexed like `MerkleTree::new`, and `nodes` holds every node breadth-first,
+//! starting with the root.
+//!
+//! `proof_to_json` and `proof_from_json` do the same for proofs, one object per step from the
+//! leaf up with `left` true when the proven node is the left child:
+//!
+//! ```json
+//! [{"left": true, "sibling": "0x<sibling 0>"}, {"left": false, "sibling": "0x<sibling 1>"}]
+//! ```
+//!
+//! `proof_to_rpc_json` and `proof_from_rpc_json` use the envelope of Ethereum JSON-RPC proofs
+//! such as `eth_getProof`: the leaf index as a hex quantity and the siblings from the leaf up,
+//! with the directions implied by the bits of the index. Only the envelope is shared; the
+//! siblings are nodes of this crate's trees, not trie nodes.
+//!
+//! ```json
+//! {"index": "0x5", "siblings": ["0x<sibling 0>", "0x<sibling 1>", "0x<sibling 2>"]}
+//! ```
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::{
+    tree_size, validate_leaf, Direction, MerkleTree, ProofStep, DIGEST_BYTES, MAX_DEPTH,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TreeJson {
+    depth: usize,
+    nodes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProofStepJson {
+    left: bool,
+    sibling: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RpcProofJson {
+    index: String,
+    siblings: Vec<String>,
+}
+
+// parses a JSON-RPC hex quantity: `0x` followed by hex digits without leading zeros
+fn parse_quantity(quantity: &str) -> Result<u64, MerkleError> {
+    let invalid =
+        || MerkleError::SerializationError(format!("invalid hex quantity {:?}", quantity));
+    let digits = quantity.strip_prefix("0x").ok_or_else(invalid)?;
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) {
+        return Err(invalid());
+    }
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    u64::from_str_radix(digits, 16).map_err(|_| invalid())
+}
+
+// maps serde_json failures onto `MerkleError::SerializationError`
+fn serialization_error(e: serde_json::Error) -> MerkleError {
+    MerkleError::SerializationError(e.to_string())
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Serializes every node of the tree to JSON, see the module documentation for the format.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the JSON string or `MerkleError::SerializationError`.
+    pub fn to_json(&self) -> Result<String, MerkleError> {
+        let json = TreeJson {
+            depth: self.depth(),
+            nodes: self
+                .iter_nodes_bfs()
+                .map(|(_, _, node)| node.to_string())
+                .collect(),
+        };
+        serde_json::to_string(&json).map_err(serialization_error)
+    }
+}
+
+impl MerkleTree {
+    /// Serializes `proof` to JSON, see the module documentation for the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to serialize.
+    ///
+    /// # Returns
+    ///
+    /// * The JSON array of steps.
+    pub fn proof_to_json(proof: &[ProofStep]) -> String {
+        let steps: Vec<ProofStepJson> = proof
+            .iter()
+            .map(|step| ProofStepJson {
+                left: *step.direction() == Direction::Left,
+                sibling: step.sibling().to_string(),
+            })
+            .collect();
+        serde_json::to_string(&steps).expect("proof steps serialize to JSON")
+    }
+
+    /// Parses a proof written by `proof_to_json`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The JSON array of steps.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps, `MerkleError::SerializationError` for malformed JSON
+    ///   or `MerkleError::InvalidBytes` for a sibling that is not a `0x` prefixed 32 byte hex
+    ///   string.
+    pub fn proof_from_json(json: &str) -> Result<Vec<ProofStep>, MerkleError> {
+        let steps: Vec<ProofStepJson> = serde_json::from_str(json).map_err(serialization_error)?;
+        steps
+            .into_iter()
+            .map(|step| {
+                validate_leaf(&step.sibling, DIGEST_BYTES)?;
+                let direction = match step.left {
+                    true => Direction::Left,
+                    false => Direction::Right,
+                };
+                Ok(ProofStep::new(direction, step.sibling))
+            })
+            .collect()
+    }
+
+    /// Serializes `proof` in the JSON-RPC envelope, see the module documentation for the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps, as returned by `proof(leaf_index)`. Their directions are not
+    ///   written; a verifier derives them from `leaf_index`.
+    /// * `leaf_index` - The 0 indexed leaf the proof is for.
+    ///
+    /// # Returns
+    ///
+    /// * The JSON object with the index and siblings.
+    pub fn proof_to_rpc_json(proof: &[ProofStep], leaf_index: u64) -> Value {
+        let siblings: Vec<&str> = proof.iter().map(ProofStep::sibling).collect();
+        json!({
+            "index": format!("{:#x}", leaf_index),
+            "siblings": siblings,
+        })
+    }
+
+    /// Parses a proof written in the JSON-RPC envelope, deriving the direction of each step from
+    /// the bits of the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The JSON object with the index and siblings.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf index and the proof steps, `MerkleError::SerializationError`
+    ///   for a malformed envelope or index, `MerkleError::InvalidBytes` for a sibling that is not
+    ///   32 byte hex, or `MerkleError::InvalidIndex` if the index has bits beyond the number of
+    ///   siblings.
+    pub fn proof_from_rpc_json(value: &Value) -> Result<(u64, Vec<ProofStep>), MerkleError> {
+        let json = RpcProofJson::deserialize(value).map_err(serialization_error)?;
+        let leaf_index = parse_quantity(&json.index)?;
+        if json.siblings.len() < 64 && leaf_index >> json.siblings.len() != 0 {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let proof = json
+            .siblings
+            .into_iter()
+            .enumerate()
+            .map(|(level, sibling)| {
+                validate_leaf(&sibling, DIGEST_BYTES)?;
+                let direction = match leaf_index.checked_shr(level as u32).unwrap_or(0) & 1 {
+                    0 => Direction::Left,
+                    _ => Direction::Right,
+                };
+                Ok(ProofStep::new(direction, sibling))
+            })
+            .collect::<Result<Vec<ProofStep>, MerkleError>>()?;
+        Ok((leaf_index, proof))
+    }
+}
+
+impl<H: MerkleHasher + Default> MerkleTree<String, H> {
+    /// Deserializes a tree written by `to_json`. Every node is checked to be a valid node of the
+    /// tree's hasher and every parent to be the hash of its children, so a loaded tree always
+    /// has a consistent root. The append position is not part of the format; `append` on a
+    /// loaded tree starts at leaf 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The JSON produced by `to_json`.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the MerkleTree, `MerkleError::SerializationError` for malformed JSON
+    ///   or inconsistent nodes, or the error for the first invalid node.
+    pub fn try_from_json(json: &str) -> Result<Self, MerkleError> {
+        let json: TreeJson = serde_json::from_str(json).map_err(serialization_error)?;
+        let total_nodes = tree_size(json.depth, MAX_DEPTH)?;
+        if json.nodes.len() != total_nodes {
+            return Err(MerkleError::SerializationError(format!(
+                "depth {} tree must have {} nodes, found {}",
+                json.depth,
+                total_nodes,
+                json.nodes.len()
+            )));
+        }
+        MerkleTree::from_node_vec(json.nodes, H::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        tree.set(2, LEAF).unwrap();
+        let json = tree.to_json().unwrap();
+        assert!(json.starts_with(r#"{"depth":3,"nodes":[""#));
+
+        let loaded: MerkleTree = MerkleTree::try_from_json(&json).unwrap();
+        assert_eq!(loaded.root(), tree.root());
+        assert_eq!(loaded.get_leaf(2).unwrap(), LEAF);
+        assert_eq!(loaded.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn test_json_empty_tree() {
+        let json = MerkleTree::empty().to_json().unwrap();
+        assert_eq!(json, r#"{"depth":0,"nodes":[]}"#);
+        let loaded: MerkleTree = MerkleTree::try_from_json(&json).unwrap();
+        assert_eq!(loaded.num_leaves(), 0);
+    }
+
+    #[test]
+    fn test_proof_json_round_trip() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        tree.set(1, LEAF).unwrap();
+        let proof = tree.proof(1).unwrap();
+
+        let json = MerkleTree::proof_to_json(&proof);
+        assert_eq!(
+            json,
+            format!(
+                r#"[{{"left":false,"sibling":"{}"}},{{"left":true,"sibling":"{}"}}]"#,
+                proof[0].sibling(),
+                proof[1].sibling()
+            )
+        );
+
+        let parsed = MerkleTree::proof_from_json(&json).unwrap();
+        assert_eq!(
+            MerkleTree::verify(&parsed, LEAF.to_string()).unwrap(),
+            tree.root()
+        );
+        assert_eq!(MerkleTree::proof_to_json(&[]), "[]");
+
+        assert!(matches!(
+            MerkleTree::proof_from_json(r#"[{"left":"yes","sibling":"0x00"}]"#),
+            Err(MerkleError::SerializationError(_))
+        ));
+        for sibling in ["00", "0x", "0x12"] {
+            let json = format!(r#"[{{"left":true,"sibling":"{}"}}]"#, sibling);
+            assert_eq!(
+                MerkleTree::proof_from_json(&json).err(),
+                Some(MerkleError::InvalidBytes)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rpc_proof_json_round_trip() {
+        let leaves: Vec<String> = (1..=8).map(|i| format!("0x{:064x}", i)).collect();
+        let leaves: Vec<&str> = leaves.iter().map(String::as_str).collect();
+        let tree = MerkleTree::from_leaves(&leaves).unwrap();
+        let proof = tree.proof(5).unwrap();
+
+        let value = MerkleTree::proof_to_rpc_json(&proof, 5);
+        let fixture: Value =
+            serde_json::from_str(include_str!("../../vectors/rpc_proof.json")).unwrap();
+        assert_eq!(value, fixture);
+        assert_eq!(value["index"], "0x5");
+        assert_eq!(
+            MerkleTree::proof_from_rpc_json(&fixture),
+            Ok((5, proof.clone()))
+        );
+        assert_eq!(
+            MerkleTree::verify(&proof, leaves[5].to_string()).unwrap(),
+            tree.root()
+        );
+
+        let zero = MerkleTree::proof_to_rpc_json(&tree.proof(0).unwrap(), 0);
+        assert_eq!(zero["index"], "0x0");
+        assert_eq!(MerkleTree::proof_from_rpc_json(&zero).unwrap().0, 0);
+        let empty = json!({"index": "0x0", "siblings": []});
+        assert_eq!(MerkleTree::proof_from_rpc_json(&empty), Ok((0, vec![])));
+    }
+
+    #[test]
+    fn test_rpc_proof_json_is_strict() {
+        let sibling = format!("0x{:064x}", 1);
+        let parse = |value: Value| MerkleTree::proof_from_rpc_json(&value);
+        for index in ["5", "0x", "0x05", "0xg", "0x10000000000000000"] {
+            assert!(matches!(
+                parse(json!({"index": index, "siblings": [sibling]})),
+                Err(MerkleError::SerializationError(_))
+            ));
+        }
+        for envelope in [
+            json!({"index": 5, "siblings": [sibling]}),
+            json!({"siblings": [sibling]}),
+            json!({"index": "0x1", "siblings": sibling}),
+            json!({"index": "0x1", "siblings": [sibling], "key": "0x1"}),
+            json!([sibling]),
+        ] {
+            assert!(matches!(
+                parse(envelope),
+                Err(MerkleError::SerializationError(_))
+            ));
+        }
+        assert_eq!(
+            parse(json!({"index": "0x1", "siblings": ["0x1234"]})),
+            Err(MerkleError::InvalidBytes)
+        );
+        assert_eq!(
+            parse(json!({"index": "0x1", "siblings": [&sibling[2..]]})),
+            Err(MerkleError::InvalidBytes)
+        );
+        // two siblings cover leaves 0 to 3
+        assert_eq!(
+            parse(json!({"index": "0x4", "siblings": [sibling, sibling]})),
+            Err(MerkleError::InvalidIndex)
+        );
+        assert!(parse(json!({"index": "0xA", "siblings": vec![sibling.clone(); 4]})).is_ok());
+    }
+
+    #[test]
+    fn test_json_rejects_invalid_trees() {
+        assert!(matches!(
+            MerkleTree::<String>::try_from_json("not json"),
+            Err(MerkleError::SerializationError(_))
+        ));
+        assert!(matches!(
+            MerkleTree::<String>::try_from_json(r#"{"depth":2,"nodes":[]}"#),
+            Err(MerkleError::SerializationError(_))
+        ));
+
+        // a leaf that no longer matches the root
+        let tree = MerkleTree::new(2, ZERO_LEAF).unwrap();
+        let tampered = tree.to_json().unwrap().replacen(ZERO_LEAF, LEAF, 1);
+        assert!(matches!(
+            MerkleTree::<String>::try_from_json(&tampered),
+            Err(MerkleError::SerializationError(_))
+        ));
+
+        let short_leaf = tree.to_json().unwrap().replacen(ZERO_LEAF, "0x00", 1);
+        assert_eq!(
+            MerkleTree::<String>::try_from_json(&short_leaf).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+    }
+}
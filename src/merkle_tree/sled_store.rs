@@ -0,0 +1,155 @@
+//! sled Node Store
+//!
+//! `SledStore` persists the nodes of a `StoredMerkleTree` in a `sled` database. Nodes are kept
+//! in the default tree keyed by their big-endian `u64` array index, and the depth and initial
+//! leaf the database was created for are kept in a separate `metadata` tree. Path updates are
+//! applied as one `sled::Batch`, so a crash leaves either the old or the new path. `flush` waits
+//! for everything written so far to reach disk.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use crate::merkle_tree::store::{NodeStore, StoredMerkleTree};
+use std::path::Path;
+
+const METADATA_TREE: &str = "metadata";
+const DEPTH_KEY: &[u8] = b"depth";
+const INITIAL_LEAF_KEY: &[u8] = b"initial_leaf";
+
+fn storage_error(e: impl std::fmt::Display) -> MerkleError {
+    MerkleError::Storage(e.to_string())
+}
+
+fn node_key(index: usize) -> [u8; 8] {
+    (index as u64).to_be_bytes()
+}
+
+// decodes a stored value, which is the node's `0x` prefixed hex string
+fn node_value(value: &[u8]) -> Result<String, MerkleError> {
+    String::from_utf8(value.to_vec()).map_err(storage_error)
+}
+
+/// `NodeStore` backed by a `sled` database, see the module documentation.
+pub struct SledStore {
+    db: sled::Db,
+    metadata: sled::Tree,
+}
+
+impl SledStore {
+    /// Opens the database at `path`, creating it if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory holding the database.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the store or `MerkleError::Storage`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MerkleError> {
+        let db = sled::open(path).map_err(storage_error)?;
+        let metadata = db.open_tree(METADATA_TREE).map_err(storage_error)?;
+        Ok(SledStore { db, metadata })
+    }
+}
+
+impl NodeStore for SledStore {
+    fn get(&self, index: usize) -> Result<Option<String>, MerkleError> {
+        match self.db.get(node_key(index)).map_err(storage_error)? {
+            Some(value) => Ok(Some(node_value(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn write_batch(&mut self, nodes: &[(usize, String)]) -> Result<(), MerkleError> {
+        let mut batch = sled::Batch::default();
+        for (index, node) in nodes {
+            batch.insert(&node_key(*index), node.as_bytes());
+        }
+        self.db.apply_batch(batch).map_err(storage_error)
+    }
+
+    fn metadata(&self) -> Result<Option<(usize, String)>, MerkleError> {
+        let depth = self.metadata.get(DEPTH_KEY).map_err(storage_error)?;
+        let initial_leaf = self.metadata.get(INITIAL_LEAF_KEY).map_err(storage_error)?;
+        match (depth, initial_leaf) {
+            (Some(depth), Some(initial_leaf)) => {
+                let depth: [u8; 8] = depth
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| storage_error("stored depth is not a u64"))?;
+                Ok(Some((
+                    u64::from_be_bytes(depth) as usize,
+                    node_value(&initial_leaf)?,
+                )))
+            }
+            (None, None) => Ok(None),
+            _ => Err(storage_error("database holds partial tree metadata")),
+        }
+    }
+
+    fn set_metadata(&mut self, depth: usize, initial_leaf: &str) -> Result<(), MerkleError> {
+        let mut batch = sled::Batch::default();
+        batch.insert(DEPTH_KEY, &(depth as u64).to_be_bytes());
+        batch.insert(INITIAL_LEAF_KEY, initial_leaf.as_bytes());
+        self.metadata.apply_batch(batch).map_err(storage_error)
+    }
+
+    fn flush(&mut self) -> Result<(), MerkleError> {
+        self.db.flush().map_err(storage_error)?;
+        Ok(())
+    }
+}
+
+impl MerkleTree {
+    /// Opens the sled backed tree at `path`, creating the database for a tree of `depth` whose
+    /// leaves are all `initial_leaf` if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory holding the database.
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf of a new tree. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the tree, `MerkleError::StoreMismatch` if the database was created
+    ///   for another depth or initial leaf, `MerkleError::Storage` or Error.
+    pub fn open_sled(
+        path: impl AsRef<Path>,
+        depth: usize,
+        initial_leaf: &str,
+    ) -> Result<StoredMerkleTree<SledStore>, MerkleError> {
+        StoredMerkleTree::open(SledStore::open(path)?, depth, initial_leaf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_reopen_sled_tree() {
+        let path = std::env::temp_dir().join(format!("merkle-sled-{}", std::process::id()));
+        let mut expected = MerkleTree::new(5, LEAF).unwrap();
+        {
+            let mut tree = MerkleTree::open_sled(&path, 5, LEAF).unwrap();
+            for (leaf_index, value) in [(3, 1), (12, 2), (3, 3)] {
+                let value = format!("0x{:064x}", value);
+                tree.set(leaf_index, &value).unwrap();
+                expected.set(leaf_index, &value).unwrap();
+            }
+            tree.flush().unwrap();
+        }
+
+        let tree = MerkleTree::open_sled(&path, 5, LEAF).unwrap();
+        assert_eq!(tree.root().unwrap(), expected.root());
+        assert_eq!(tree.proof(12).unwrap(), expected.proof(12).unwrap());
+        drop(tree);
+
+        assert_eq!(
+            MerkleTree::open_sled(&path, 6, LEAF).err(),
+            Some(MerkleError::StoreMismatch)
+        );
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
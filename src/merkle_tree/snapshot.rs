@@ -0,0 +1,108 @@
+//! Snapshots
+//!
+//! `MerkleTree::snapshot` copies the state of a tree so it can later be restored with
+//! `rollback`, e.g. to undo a speculative batch of updates.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+
+/// Copy of a tree's nodes and append position at the time `MerkleTree::snapshot` was called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleSnapshot {
+    nodes: Vec<String>,
+    next_leaf: usize,
+    sorted_len: Option<usize>,
+}
+
+impl MerkleSnapshot {
+    /// returns the root the tree had when the snapshot was taken
+    pub fn root(&self) -> Option<&str> {
+        self.nodes.first().map(String::as_str)
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// returns a snapshot of the tree's current state, see `rollback`
+    pub fn snapshot(&self) -> MerkleSnapshot {
+        let (nodes, next_leaf, sorted_len) = self.state();
+        MerkleSnapshot {
+            nodes: nodes.to_vec(),
+            next_leaf,
+            sorted_len,
+        }
+    }
+
+    /// Restores the tree to the state captured by `snapshot`, notifying root observers if the
+    /// root changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - A snapshot taken from this tree with `snapshot`.
+    pub fn rollback(&mut self, snapshot: &MerkleSnapshot) {
+        self.restore_state(
+            snapshot.nodes.clone(),
+            snapshot.next_leaf,
+            snapshot.sorted_len,
+        );
+    }
+
+    /// Compares the tree's nodes with the nodes stored in `snapshot` and returns the array
+    /// indices (positions in the breadth-first node layout, not leaf indices) of every node that
+    /// differs. If the tree's size changed since the snapshot, indices only present on one side
+    /// count as modified.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot to compare against.
+    ///
+    /// # Returns
+    ///
+    /// * The modified node indices in ascending order.
+    pub fn nodes_modified_since_snapshot(&self, snapshot: &MerkleSnapshot) -> Vec<usize> {
+        let (nodes, _, _) = self.state();
+        (0..nodes.len().max(snapshot.nodes.len()))
+            .filter(|index| nodes.get(*index) != snapshot.nodes.get(*index))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_nodes_modified_since_snapshot() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        let snapshot = tree.snapshot();
+        assert!(tree.nodes_modified_since_snapshot(&snapshot).is_empty());
+
+        // leaf 2 is node 5, its parent node 2 and the root node 0
+        tree.set(2, LEAF).unwrap();
+        assert_eq!(tree.nodes_modified_since_snapshot(&snapshot), vec![0, 2, 5]);
+
+        tree.rollback(&snapshot);
+        assert!(tree.nodes_modified_since_snapshot(&snapshot).is_empty());
+        assert_eq!(Some(tree.root().as_str()), snapshot.root());
+    }
+
+    #[test]
+    fn test_rollback_restores_append_position() {
+        let mut tree = MerkleTree::new(2, ZERO_LEAF).unwrap();
+        tree.append(LEAF).unwrap();
+        let snapshot = tree.snapshot();
+        tree.append(LEAF).unwrap();
+        tree.rollback(&snapshot);
+        assert_eq!(tree.append(LEAF).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_nodes_modified_after_growth() {
+        let mut tree = MerkleTree::new(1, ZERO_LEAF).unwrap();
+        tree.append(LEAF).unwrap();
+        let snapshot = tree.snapshot();
+        tree.append_growing(LEAF).unwrap();
+        assert_eq!(tree.nodes_modified_since_snapshot(&snapshot), vec![0, 1, 2]);
+    }
+}
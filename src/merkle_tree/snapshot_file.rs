@@ -0,0 +1,280 @@
+//! Snapshot Files
+//!
+//! `MerkleTree::to_snapshot_bytes` writes a tree into a versioned binary container and
+//! `MerkleTree::from_snapshot_bytes` reads it back. Every integer is big-endian. The container
+//! starts with a fixed header:
+//!
+//! | offset | size | field                                                              |
+//! |--------|------|--------------------------------------------------------------------|
+//! | 0      | 8    | `SNAPSHOT_MAGIC`                                                   |
+//! | 8      | 2    | format version                                                     |
+//! | 10     | 1    | hash algorithm id, see `hash_algorithm_byte`                       |
+//! | 11     | 1    | flags, `SNAPSHOT_FLAG_SPARSE` and `SNAPSHOT_FLAG_DOMAIN_SEPARATED` |
+//! | 12     | 4    | depth (one indexed, 0 for a tree without leaves)                   |
+//! | 16     | 8    | leaf count, the number of leaves filled by `append`                |
+//!
+//! followed by the payload of the version. Version 1 only writes dense payloads: every node of
+//! the tree breadth-first, starting with the root, as `H::DIGEST_BYTES` raw bytes each.
+//!
+//! The reader checks the magic and then dispatches on the version before looking at anything
+//! else, so a newer layout may change every later field. Versions it has no reader for fail with
+//! `MerkleError::UnsupportedSnapshotVersion`.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::{tree_size, MerkleTree, MAX_DEPTH};
+
+/// the first eight bytes of every snapshot
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"MRKLSNAP";
+/// the newest format version `from_snapshot_bytes` reads, and the one `to_snapshot_bytes` writes
+pub const SNAPSHOT_VERSION: u16 = 1;
+/// flag of a payload holding only the nodes that differ from the default subtree roots
+pub const SNAPSHOT_FLAG_SPARSE: u8 = 0b01;
+/// flag of a tree whose leaves and internal nodes are hashed with distinct domain prefixes
+pub const SNAPSHOT_FLAG_DOMAIN_SEPARATED: u8 = 0b10;
+
+// magic, version, hash algorithm, flags, depth and leaf count
+const HEADER_BYTES: usize = 8 + 2 + 1 + 1 + 4 + 8;
+
+fn snapshot_error(message: impl Into<String>) -> MerkleError {
+    MerkleError::SerializationError(message.into())
+}
+
+/// Returns the byte identifying the hasher `algorithm_id` in a snapshot header, or `None` for a
+/// hasher without one.
+pub fn hash_algorithm_byte(algorithm_id: &str) -> Option<u8> {
+    match algorithm_id {
+        "sha3-256" => Some(1),
+        "sha256" => Some(2),
+        "double-sha256" => Some(3),
+        "blake2b-256" => Some(4),
+        _ => None,
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Writes the tree as a version `SNAPSHOT_VERSION` snapshot, see the module documentation.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the snapshot bytes, or `MerkleError::SerializationError` if the tree's
+    ///   hasher has no `hash_algorithm_byte`.
+    pub fn to_snapshot_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let algorithm_id = self.hasher().algorithm_id();
+        let algorithm = hash_algorithm_byte(algorithm_id).ok_or_else(|| {
+            snapshot_error(format!(
+                "hash {} has no snapshot algorithm id",
+                algorithm_id
+            ))
+        })?;
+        let (nodes, next_leaf, _) = self.state();
+        let mut bytes = Vec::with_capacity(HEADER_BYTES + nodes.len() * H::DIGEST_BYTES);
+        bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+        bytes.push(algorithm);
+        // version 1 trees are dense and hash without domain separation
+        bytes.push(0);
+        bytes.extend_from_slice(&(self.depth() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(next_leaf as u64).to_be_bytes());
+        for node in nodes {
+            // every write validates the node, so all of them are hex
+            bytes.extend_from_slice(&hex::decode(&node[2..]).expect("nodes are hex"));
+        }
+        Ok(bytes)
+    }
+}
+
+impl<H: MerkleHasher + Default> MerkleTree<String, H> {
+    /// Reads a snapshot written by `to_snapshot_bytes`, checking every parent is the hash of its
+    /// children.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The snapshot, see the module documentation.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the MerkleTree, `MerkleError::UnsupportedSnapshotVersion` for a format
+    ///   version this build cannot read, `MerkleError::MaxDepthExceeded`, or
+    ///   `MerkleError::SerializationError` for any other malformed or inconsistent snapshot.
+    pub fn from_snapshot_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        if bytes.len() < SNAPSHOT_MAGIC.len() + 2 || bytes[..8] != SNAPSHOT_MAGIC {
+            return Err(snapshot_error("input is not a merkle tree snapshot"));
+        }
+        match u16::from_be_bytes([bytes[8], bytes[9]]) {
+            1 => MerkleTree::read_v1(bytes),
+            found => Err(MerkleError::UnsupportedSnapshotVersion {
+                found,
+                supported: SNAPSHOT_VERSION,
+            }),
+        }
+    }
+
+    fn read_v1(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let (header, payload) = bytes
+            .split_at_checked(HEADER_BYTES)
+            .ok_or_else(|| snapshot_error("snapshot header is truncated"))?;
+        let hasher = H::default();
+        if hash_algorithm_byte(hasher.algorithm_id()) != Some(header[10]) {
+            return Err(snapshot_error(format!(
+                "snapshot was written with hash algorithm {}, not {}",
+                header[10],
+                hasher.algorithm_id()
+            )));
+        }
+        match header[11] {
+            0 => {}
+            SNAPSHOT_FLAG_SPARSE => {
+                return Err(snapshot_error("version 1 snapshots are dense"));
+            }
+            flags => {
+                return Err(snapshot_error(format!(
+                    "unsupported snapshot flags {:#04x}",
+                    flags
+                )))
+            }
+        }
+        let depth = u32::from_be_bytes(header[12..16].try_into().expect("4 bytes"));
+        let next_leaf = u64::from_be_bytes(header[16..24].try_into().expect("8 bytes"));
+        let depth = usize::try_from(depth).unwrap_or(usize::MAX);
+        let total_nodes = tree_size(depth, MAX_DEPTH)?;
+        if payload.len() != total_nodes * H::DIGEST_BYTES {
+            return Err(snapshot_error(format!(
+                "depth {} snapshot must have {} payload bytes, found {}",
+                depth,
+                total_nodes * H::DIGEST_BYTES,
+                payload.len()
+            )));
+        }
+        let nodes: Vec<String> = payload
+            .chunks_exact(H::DIGEST_BYTES)
+            .map(|node| format!("0x{}", hex::encode(node)))
+            .collect();
+        let leaf_count = total_nodes.div_ceil(2);
+        let next_leaf = usize::try_from(next_leaf)
+            .ok()
+            .filter(|next_leaf| *next_leaf <= leaf_count)
+            .ok_or_else(|| {
+                snapshot_error(format!(
+                    "leaf count {} exceeds the {} leaves of the tree",
+                    next_leaf, leaf_count
+                ))
+            })?;
+        let mut tree = MerkleTree::with_nodes(Vec::new(), hasher);
+        tree.restore_state(nodes, next_leaf, None);
+        tree.validate()
+            .map_err(|e| MerkleError::SerializationError(e.to_string()))?;
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::hasher::Sha256Hasher;
+    use std::fs;
+    use std::path::Path;
+
+    // `vectors/snapshot_v1.bin` holds the depth 3 tree of these leaves
+    const GOLDEN_LEAVES: [&str; 3] = [
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
+        "0x0000000000000000000000000000000000000000000000000000000000000002",
+        "0x0000000000000000000000000000000000000000000000000000000000000003",
+    ];
+
+    fn golden() -> Vec<u8> {
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("vectors/snapshot_v1.bin")).unwrap()
+    }
+
+    fn read(bytes: &[u8]) -> Result<MerkleTree, MerkleError> {
+        MerkleTree::from_snapshot_bytes(bytes)
+    }
+
+    fn header(version: u16) -> Vec<u8> {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_be_bytes());
+        bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_snapshot_v1_golden_file() {
+        let tree = MerkleTree::from_leaves(&GOLDEN_LEAVES).unwrap();
+        let golden = golden();
+        assert_eq!(tree.to_snapshot_bytes().unwrap(), golden);
+        assert_eq!(golden.len(), HEADER_BYTES + 7 * 32);
+        assert_eq!(
+            &golden[8..24],
+            &[0, 1, 1, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 3]
+        );
+
+        let mut tree = read(&golden).unwrap();
+        assert_eq!(
+            tree.root(),
+            MerkleTree::from_leaves(&GOLDEN_LEAVES).unwrap().root()
+        );
+        assert_eq!(tree.get_leaf(2), Ok(GOLDEN_LEAVES[2]));
+        // the append position survives the round trip
+        assert_eq!(tree.append(GOLDEN_LEAVES[0]), Ok(3));
+        assert_eq!(tree.append(GOLDEN_LEAVES[0]), Err(MerkleError::TreeFull));
+
+        let empty = MerkleTree::empty().to_snapshot_bytes().unwrap();
+        assert_eq!(empty.len(), HEADER_BYTES);
+        assert_eq!(read(&empty).unwrap().depth(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_unknown_version() {
+        assert_eq!(
+            read(&header(99)).err(),
+            Some(MerkleError::UnsupportedSnapshotVersion {
+                found: 99,
+                supported: SNAPSHOT_VERSION,
+            })
+        );
+        assert_eq!(
+            read(&header(0)).err(),
+            Some(MerkleError::UnsupportedSnapshotVersion {
+                found: 0,
+                supported: SNAPSHOT_VERSION,
+            })
+        );
+        assert!(read(&header(1)).is_ok());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_malformed_input() {
+        let golden = golden();
+        let mut corrupted_node = golden.clone();
+        corrupted_node[HEADER_BYTES + 40] ^= 1;
+        let mut sparse = golden.clone();
+        sparse[11] = SNAPSHOT_FLAG_SPARSE;
+        let mut overfilled = golden.clone();
+        overfilled[23] = 5;
+        for malformed in [
+            &b"MRKL"[..],
+            &b"{\"depth\": 3}"[..],
+            &golden[..HEADER_BYTES - 1],
+            &golden[..golden.len() - 1],
+            &corrupted_node,
+            &sparse,
+            &overfilled,
+        ] {
+            assert!(matches!(
+                read(malformed),
+                Err(MerkleError::SerializationError(_))
+            ));
+        }
+
+        let mut too_deep = header(1);
+        too_deep[15] = 31;
+        assert!(matches!(
+            read(&too_deep),
+            Err(MerkleError::MaxDepthExceeded { .. })
+        ));
+        // the hash algorithm must be the reader's
+        assert!(matches!(
+            MerkleTree::<String, Sha256Hasher>::from_snapshot_bytes(&golden),
+            Err(MerkleError::SerializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+//! Sorted Trees
+//!
+//! Trees whose leaves are strictly ascending by byte value, as used for allowlist commitments.
+//! Keeping the leaves ordered is what makes it possible to prove that a value is absent.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{
+    normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES, DIGEST_HEX,
+};
+use std::cmp::Ordering;
+
+/// Proof that a value is absent from a sorted tree: the two adjacent leaves that bracket it, each
+/// with its own inclusion proof.
+#[derive(Clone, Debug)]
+pub struct NonMembershipProof {
+    /// 0 indexed position of `lower_leaf`; `upper_leaf` is at `lower_index + 1`
+    pub lower_index: usize,
+    /// the largest leaf smaller than the value
+    pub lower_leaf: String,
+    /// inclusion proof for `lower_leaf`
+    pub lower_proof: Vec<ProofStep>,
+    /// the smallest leaf greater than the value
+    pub upper_leaf: String,
+    /// inclusion proof for `upper_leaf`
+    pub upper_proof: Vec<ProofStep>,
+}
+
+// decodes a `0x` prefixed 32 byte hex leaf so leaves can be compared by byte value
+fn leaf_bytes(leaf: &str) -> Result<Vec<u8>, MerkleError> {
+    match leaf.strip_prefix("0x") {
+        Some(digits) if digits.len() == DIGEST_HEX => Ok(hex::decode(digits)?),
+        _ => Err(MerkleError::InvalidBytes),
+    }
+}
+
+// recovers the leaf index a proof was generated for from its directions, or `None` if the proof
+// is too long for any tree
+fn proof_leaf_index(proof: &[ProofStep]) -> Option<usize> {
+    if proof.len() > usize::BITS as usize {
+        return None;
+    }
+    Some(
+        proof
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| *step.direction() == Direction::Right)
+            .map(|(level, _)| 1 << level)
+            .sum(),
+    )
+}
+
+// checks `leaves` are strictly ascending by byte value, returning the index of the first that is
+// not in `MerkleError::UnsortedLeaves` or `MerkleError::DuplicateLeaf`
+fn check_sorted<'a>(leaves: impl IntoIterator<Item = &'a str>) -> Result<(), MerkleError> {
+    let mut previous: Option<Vec<u8>> = None;
+    for (index, leaf) in leaves.into_iter().enumerate() {
+        let bytes = leaf_bytes(leaf)?;
+        if let Some(previous) = &previous {
+            match previous.cmp(&bytes) {
+                Ordering::Less => {}
+                Ordering::Equal => return Err(MerkleError::DuplicateLeaf { index }),
+                Ordering::Greater => return Err(MerkleError::UnsortedLeaves { index }),
+            }
+        }
+        previous = Some(bytes);
+    }
+    Ok(())
+}
+
+impl MerkleTree {
+    /// Constructs a merkle tree from `leaves` after checking they are strictly ascending by byte
+    /// value. Like `from_leaves`, the leaf count is padded to a power of two with `ZERO_LEAF`;
+    /// the padding is not part of the sorted set.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The sorted leaf values. Each must be 32 bit hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the new MerkleTree, or `MerkleError::UnsortedLeaves` /
+    ///   `MerkleError::DuplicateLeaf` with the index of the first offending leaf.
+    pub fn from_sorted_leaves(leaves: &[&str]) -> Result<Self, MerkleError> {
+        check_sorted(leaves.iter().copied())?;
+        let mut tree = MerkleTree::from_leaves(leaves)?;
+        tree.set_sorted_len(Some(leaves.len()));
+        Ok(tree)
+    }
+
+    /// Checks that the leaves filled so far, by `from_sorted_leaves`, `from_leaves` or `append`,
+    /// are still strictly ascending by byte value, as `non_membership_proof` relies on. The
+    /// padding after them is not checked. Unlike `non_membership_proof`, which refuses any tree
+    /// written with `set`, this looks at the leaves themselves.
+    ///
+    /// # Returns
+    ///
+    /// * Result indicating success, or `MerkleError::UnsortedLeaves` /
+    ///   `MerkleError::DuplicateLeaf` with the index of the first offending leaf.
+    pub fn verify_sorted_invariant(&self) -> Result<(), MerkleError> {
+        let (nodes, next_leaf, _) = self.state();
+        let first_leaf = nodes.len() - self.num_leaves();
+        check_sorted(
+            nodes[first_leaf..first_leaf + next_leaf]
+                .iter()
+                .map(String::as_str),
+        )
+    }
+
+    /// Proves that `value` is not a leaf of a tree built by `from_sorted_leaves`, by returning the
+    /// two adjacent leaves it falls between along with their inclusion proofs.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The absent value. Must be 32 bit hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the `NonMembershipProof`, `MerkleError::NotSorted` for unsorted trees,
+    ///   `MerkleError::LeafPresent` if `value` is a leaf, or `MerkleError::ValueOutOfRange` if it
+    ///   is below the first or above the last sorted leaf.
+    pub fn non_membership_proof(&self, value: &str) -> Result<NonMembershipProof, MerkleError> {
+        let sorted_len = self.sorted_len().ok_or(MerkleError::NotSorted)?;
+        let target = leaf_bytes(value)?;
+
+        // binary search for the first leaf that is not smaller than `value`
+        let (mut low, mut high) = (0, sorted_len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match leaf_bytes(self.get_leaf(mid)?)?.cmp(&target) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Equal => return Err(MerkleError::LeafPresent { index: mid }),
+                Ordering::Greater => high = mid,
+            }
+        }
+        if low == 0 || low == sorted_len {
+            return Err(MerkleError::ValueOutOfRange);
+        }
+
+        Ok(NonMembershipProof {
+            lower_index: low - 1,
+            lower_leaf: self.get_leaf(low - 1)?.to_string(),
+            lower_proof: self.proof(low - 1)?,
+            upper_leaf: self.get_leaf(low)?.to_string(),
+            upper_proof: self.proof(low)?,
+        })
+    }
+
+    /// Checks that `proof` shows `value` is absent from the sorted tree with root `root`: both
+    /// bracketing leaves are included under `root`, they are adjacent, and `value` lies strictly
+    /// between them. The roots are compared ignoring case and surrounding whitespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof returned by `non_membership_proof`.
+    /// * `value` - The value claimed to be absent.
+    /// * `root` - The expected root of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing whether the proof is valid or Error.
+    pub fn verify_non_membership(
+        proof: &NonMembershipProof,
+        value: &str,
+        root: &str,
+    ) -> Result<bool, MerkleError> {
+        let target = leaf_bytes(value)?;
+        if leaf_bytes(&proof.lower_leaf)? >= target || leaf_bytes(&proof.upper_leaf)? <= target {
+            return Ok(false);
+        }
+        if proof.lower_proof.len() != proof.upper_proof.len()
+            || proof_leaf_index(&proof.lower_proof) != Some(proof.lower_index)
+            || proof_leaf_index(&proof.upper_proof) != proof.lower_index.checked_add(1)
+        {
+            return Ok(false);
+        }
+        let root = normalize_node(root, DIGEST_BYTES)?;
+        Ok(
+            MerkleTree::verify(&proof.lower_proof, proof.lower_leaf.clone())?
+                .eq_ignore_ascii_case(root)
+                && MerkleTree::verify(&proof.upper_proof, proof.upper_leaf.clone())?
+                    .eq_ignore_ascii_case(root),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF_1: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const LEAF_2: &str = "0x2222222222222222222222222222222222222222222222222222222222222222";
+    const LEAF_3: &str = "0x3333333333333333333333333333333333333333333333333333333333333333";
+    const LEAF_4: &str = "0x4444444444444444444444444444444444444444444444444444444444444444";
+    const LEAF_5: &str = "0x5555555555555555555555555555555555555555555555555555555555555555";
+
+    #[test]
+    fn test_from_sorted_leaves() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_3]).unwrap();
+        let unchecked = MerkleTree::from_leaves(&[LEAF_1, LEAF_2, LEAF_3]).unwrap();
+        assert_eq!(tree.root(), unchecked.root());
+    }
+
+    #[test]
+    fn test_from_sorted_leaves_compares_bytes_not_case() {
+        let upper = "0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        let lower = "0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        assert!(MerkleTree::from_sorted_leaves(&[upper, lower]).is_ok());
+    }
+
+    #[test]
+    fn test_from_sorted_leaves_out_of_order() {
+        assert_eq!(
+            MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_3, LEAF_2]).err(),
+            Some(MerkleError::UnsortedLeaves { index: 2 })
+        );
+    }
+
+    #[test]
+    fn test_from_sorted_leaves_duplicate() {
+        assert_eq!(
+            MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_2]).err(),
+            Some(MerkleError::DuplicateLeaf { index: 2 })
+        );
+    }
+
+    #[test]
+    fn test_verify_sorted_invariant() {
+        let mut tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_4]).unwrap();
+        assert_eq!(tree.verify_sorted_invariant(), Ok(()));
+        tree.set(1, LEAF_3).unwrap();
+        assert_eq!(tree.verify_sorted_invariant(), Ok(()));
+
+        tree.set(1, LEAF_5).unwrap();
+        assert_eq!(
+            tree.verify_sorted_invariant(),
+            Err(MerkleError::UnsortedLeaves { index: 2 })
+        );
+        tree.set(1, LEAF_1).unwrap();
+        assert_eq!(
+            tree.verify_sorted_invariant(),
+            Err(MerkleError::DuplicateLeaf { index: 1 })
+        );
+        assert_eq!(MerkleTree::empty().verify_sorted_invariant(), Ok(()));
+    }
+
+    #[test]
+    fn test_non_membership_proof() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_3, LEAF_5]).unwrap();
+        let proof = tree.non_membership_proof(LEAF_4).unwrap();
+        assert_eq!(proof.lower_index, 2);
+        assert_eq!(proof.lower_leaf, LEAF_3);
+        assert_eq!(proof.upper_leaf, LEAF_5);
+        assert!(MerkleTree::verify_non_membership(&proof, LEAF_4, &tree.root()).unwrap());
+        let shouted = format!(" 0x{} ", tree.root()[2..].to_uppercase());
+        assert!(MerkleTree::verify_non_membership(&proof, LEAF_4, &shouted).unwrap());
+
+        // the proof does not carry over to a different root or value
+        let other = MerkleTree::from_leaves(&[LEAF_1, LEAF_2, LEAF_3, LEAF_4]).unwrap();
+        assert!(!MerkleTree::verify_non_membership(&proof, LEAF_4, &other.root()).unwrap());
+        assert!(!MerkleTree::verify_non_membership(&proof, LEAF_2, &tree.root()).unwrap());
+    }
+
+    #[test]
+    fn test_non_membership_proof_rejects_present_value() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_3, LEAF_5]).unwrap();
+        assert_eq!(
+            tree.non_membership_proof(LEAF_3).err(),
+            Some(MerkleError::LeafPresent { index: 2 })
+        );
+        assert_eq!(
+            tree.non_membership_proof(LEAF_1).err(),
+            Some(MerkleError::LeafPresent { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_non_membership_proof_errors() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_2, LEAF_3, LEAF_4]).unwrap();
+        assert_eq!(
+            tree.non_membership_proof(LEAF_1).err(),
+            Some(MerkleError::ValueOutOfRange)
+        );
+        // the zero padding after the last leaf is not part of the sorted set
+        assert_eq!(
+            tree.non_membership_proof(LEAF_5).err(),
+            Some(MerkleError::ValueOutOfRange)
+        );
+
+        let mut modified = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_3]).unwrap();
+        modified.set(0, LEAF_5).unwrap();
+        assert_eq!(
+            modified.non_membership_proof(LEAF_2).err(),
+            Some(MerkleError::NotSorted)
+        );
+
+        let unsorted = MerkleTree::from_leaves(&[LEAF_1, LEAF_3]).unwrap();
+        assert_eq!(
+            unsorted.non_membership_proof(LEAF_2).err(),
+            Some(MerkleError::NotSorted)
+        );
+    }
+
+    #[test]
+    fn test_verify_non_membership_rejects_non_adjacent_leaves() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_3, LEAF_5]).unwrap();
+        let proof = NonMembershipProof {
+            lower_index: 1,
+            lower_leaf: LEAF_2.to_string(),
+            lower_proof: tree.proof(1).unwrap(),
+            upper_leaf: LEAF_5.to_string(),
+            upper_proof: tree.proof(3).unwrap(),
+        };
+        assert!(!MerkleTree::verify_non_membership(&proof, LEAF_4, &tree.root()).unwrap());
+    }
+
+    #[test]
+    fn test_tampered_non_membership_proof() {
+        let tree = MerkleTree::from_sorted_leaves(&[LEAF_1, LEAF_2, LEAF_3, LEAF_5]).unwrap();
+        let root = tree.root();
+        let proof = tree.non_membership_proof(LEAF_4).unwrap();
+
+        // proofs too long for any tree, and indices at the end of the range, are rejected
+        let mut long = proof.clone();
+        let step = ProofStep::new(Direction::Right, LEAF_1.to_string());
+        long.lower_proof = vec![step.clone(); usize::BITS as usize + 1];
+        long.upper_proof = vec![step; usize::BITS as usize + 1];
+        assert!(!MerkleTree::verify_non_membership(&long, LEAF_4, &root).unwrap());
+        let mut last = proof.clone();
+        last.lower_index = usize::MAX;
+        assert!(!MerkleTree::verify_non_membership(&last, LEAF_4, &root).unwrap());
+
+        let mut truncated = proof.clone();
+        truncated.upper_proof.pop();
+        assert!(!MerkleTree::verify_non_membership(&truncated, LEAF_4, &root).unwrap());
+        let mut malformed = proof;
+        malformed.upper_leaf = "0x".to_string();
+        assert_eq!(
+            MerkleTree::verify_non_membership(&malformed, LEAF_4, &root),
+            Err(MerkleError::InvalidBytes)
+        );
+    }
+}
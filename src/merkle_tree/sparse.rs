@@ -0,0 +1,271 @@
+//! Sparse Proofs
+//!
+//! In a mostly empty tree most siblings of a proof are roots of subtrees whose leaves are all
+//! `ZERO_LEAF`, which are `ZERO_HASHES[level]` and need not be sent. `SparseProof` keeps only the
+//! other siblings and marks the levels whose sibling is the default, so its size grows with the
+//! number of populated subtrees along the path rather than with the depth.
+//!
+//! A `SparseProof` proves any number of leaves at once. Siblings that another proven path
+//! already provides are not sent either: the needed siblings are listed level by level from the
+//! leaves up, and left to right within a level, which is the order the verifier consumes them.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::Sha3_256Hasher;
+use crate::merkle_tree::merkle_tree::{
+    hash_nodes, normalize_node, validate_leaf, MerkleTree, DIGEST_BYTES, ZERO_HASHES,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A proof of one or more leaves with the default and shared siblings left out, see the module
+/// documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseProof {
+    /// per proven leaf in order, and per level from the leaf up, true when the leaf's ancestor
+    /// at that level is the right child
+    pub direction_bits: Vec<bool>,
+    /// the needed siblings not marked in `is_default`, in the order of `is_default`
+    pub non_default_siblings: Vec<String>,
+    /// per needed sibling, true when the sibling is `ZERO_HASHES[level]`
+    pub is_default: Vec<bool>,
+}
+
+impl SparseProof {
+    // the next needed sibling at `level`
+    fn next_sibling<'a>(
+        level: usize,
+        defaults: &mut impl Iterator<Item = &'a bool>,
+        siblings: &mut impl Iterator<Item = &'a String>,
+    ) -> Result<String, MerkleError> {
+        let sibling = match defaults.next().ok_or(MerkleError::InvalidFormat)? {
+            true => ZERO_HASHES.get(level).ok_or(MerkleError::InvalidFormat)?,
+            false => siblings.next().ok_or(MerkleError::InvalidFormat)?,
+        };
+        validate_leaf(sibling, DIGEST_BYTES)?;
+        Ok(sibling.clone())
+    }
+
+    /// Given the proven leaves, calculates and returns the root, like `MerkleTree::verify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_values` - The values of the proven leaves, in the order they were passed to
+    ///   `MerkleTree::sparse_proof`. Must be 32 bit hex strings with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the root of the tree, `MerkleError::InvalidFormat` if the direction
+    ///   bits, defaults and siblings do not add up for that many leaves or a leaf repeats with a
+    ///   different value, or `MerkleError::InvalidBytes` for a malformed leaf or sibling.
+    pub fn verify(&self, leaf_values: &[String]) -> Result<String, MerkleError> {
+        if leaf_values.is_empty() || !self.direction_bits.len().is_multiple_of(leaf_values.len()) {
+            return Err(MerkleError::InvalidFormat);
+        }
+        let levels = self.direction_bits.len() / leaf_values.len();
+        if levels >= usize::BITS as usize {
+            return Err(MerkleError::InvalidFormat);
+        }
+        let mut known = BTreeMap::new();
+        for (leaf_index, leaf) in leaf_values.iter().enumerate() {
+            let bits = &self.direction_bits[leaf_index * levels..(leaf_index + 1) * levels];
+            let offset = bits.iter().enumerate().fold(0, |offset, (level, right)| {
+                offset | (usize::from(*right) << level)
+            });
+            let leaf = normalize_node(leaf, DIGEST_BYTES)?.to_string();
+            if let Some(other) = known.insert(offset, leaf.clone()) {
+                if !other.eq_ignore_ascii_case(&leaf) {
+                    return Err(MerkleError::InvalidFormat);
+                }
+            }
+        }
+
+        let mut defaults = self.is_default.iter();
+        let mut siblings = self.non_default_siblings.iter();
+        let hasher = Sha3_256Hasher::<DIGEST_BYTES>;
+        for level in 0..levels {
+            let mut parents = BTreeMap::new();
+            let mut nodes = known.into_iter().peekable();
+            while let Some((offset, node)) = nodes.next() {
+                let (left, right) = if offset % 2 == 1 {
+                    let left = SparseProof::next_sibling(level, &mut defaults, &mut siblings)?;
+                    (left, node)
+                } else if let Some((_, right)) = nodes.next_if(|(next, _)| *next == offset + 1) {
+                    (node, right)
+                } else {
+                    let right = SparseProof::next_sibling(level, &mut defaults, &mut siblings)?;
+                    (node, right)
+                };
+                parents.insert(offset / 2, hash_nodes(&hasher, &left, &right)?);
+            }
+            known = parents;
+        }
+        if defaults.next().is_some() || siblings.next().is_some() {
+            return Err(MerkleError::InvalidFormat);
+        }
+        let (_, root) = known.pop_first().expect("one node per level is known");
+        Ok(root)
+    }
+}
+
+impl MerkleTree {
+    /// Constructs a proof of the leaves at `leaf_indices` with their default and shared siblings
+    /// left out, see the module documentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_indices` - 0 indexed leaves you want to construct a proof for. Repeated indices
+    ///   are proven once each time they appear.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the `SparseProof`, or `MerkleError::InvalidIndex` if `leaf_indices`
+    ///   is empty or an index is out of range.
+    pub fn sparse_proof(&self, leaf_indices: &[usize]) -> Result<SparseProof, MerkleError> {
+        let leaf_count = self.num_leaves();
+        if leaf_indices.is_empty() || leaf_indices.iter().any(|&i| i >= leaf_count) {
+            return Err(MerkleError::InvalidIndex);
+        }
+        let levels = self.proof_len();
+        let mut sparse = SparseProof {
+            direction_bits: Vec::with_capacity(leaf_indices.len() * levels),
+            non_default_siblings: Vec::new(),
+            is_default: Vec::new(),
+        };
+        for &leaf_index in leaf_indices {
+            sparse
+                .direction_bits
+                .extend((0..levels).map(|level| (leaf_index >> level) & 1 == 1));
+        }
+
+        let mut known: BTreeSet<usize> = leaf_indices.iter().copied().collect();
+        for level in 0..levels {
+            let mut parents = BTreeSet::new();
+            let mut offsets = known.into_iter().peekable();
+            while let Some(offset) = offsets.next() {
+                let shared = offset % 2 == 0 && offsets.next_if_eq(&(offset + 1)).is_some();
+                if !shared {
+                    // the sibling at `level` above the leaves sits at depth `levels - level`
+                    let sibling = self.node(levels - level, offset ^ 1)?;
+                    let default = ZERO_HASHES
+                        .get(level)
+                        .is_some_and(|zero| zero.eq_ignore_ascii_case(sibling));
+                    sparse.is_default.push(default);
+                    if !default {
+                        sparse.non_default_siblings.push(sibling.to_string());
+                    }
+                }
+                parents.insert(offset / 2);
+            }
+            known = parents;
+        }
+        Ok(sparse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    #[test]
+    fn test_sparse_proof_omits_default_siblings() {
+        let mut tree = MerkleTree::new_with_precomputed_zeros(12).unwrap();
+        let leaf = format!("0x{:064x}", 7);
+        tree.set(1000, &leaf).unwrap();
+        tree.set(1001, &leaf).unwrap();
+        tree.set(10, &leaf).unwrap();
+
+        let sparse = tree.sparse_proof(&[1000]).unwrap();
+        assert_eq!(sparse.is_default.len(), 11);
+        // the neighbouring leaf and the subtree holding leaf 10
+        assert_eq!(sparse.non_default_siblings.len(), 2);
+        assert_eq!(
+            sparse.verify(std::slice::from_ref(&leaf)).unwrap(),
+            tree.root()
+        );
+        let bits: Vec<bool> = (0..11).map(|level| (1000 >> level) & 1 == 1).collect();
+        assert_eq!(sparse.direction_bits, bits);
+
+        // in the empty half of the tree only the other half is not a default sibling
+        let empty = tree.sparse_proof(&[1500]).unwrap();
+        assert_eq!(empty.non_default_siblings.len(), 1);
+        assert_eq!(empty.verify(&[ZERO_LEAF.to_string()]).unwrap(), tree.root());
+        assert_eq!(tree.sparse_proof(&[2048]), Err(MerkleError::InvalidIndex));
+        assert_eq!(tree.sparse_proof(&[]), Err(MerkleError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_sparse_proof_shares_siblings_between_leaves() {
+        let mut tree = MerkleTree::new_with_precomputed_zeros(12).unwrap();
+        let leaf = format!("0x{:064x}", 7);
+        tree.set(1000, &leaf).unwrap();
+        tree.set(1001, &leaf).unwrap();
+        tree.set(10, &leaf).unwrap();
+
+        let leaves = [1001, 10, 1000];
+        let sparse = tree.sparse_proof(&leaves).unwrap();
+        assert_eq!(sparse.direction_bits.len(), 3 * 11);
+        // leaves 1000 and 1001 are each other's siblings and the two paths meet below the root,
+        // so every sibling that is left is a default
+        assert!(sparse.non_default_siblings.is_empty());
+        let leaf_values = [leaf.clone(), leaf.clone(), leaf.clone()];
+        assert_eq!(sparse.verify(&leaf_values).unwrap(), tree.root());
+        assert_ne!(
+            sparse.verify(&[leaf.clone(), ZERO_LEAF.to_string(), leaf.clone()]),
+            Ok(tree.root())
+        );
+
+        let separate: usize = leaves
+            .iter()
+            .map(|&i| tree.sparse_proof(&[i]).unwrap().is_default.len())
+            .sum();
+        assert!(sparse.is_default.len() < separate);
+
+        let repeated = tree.sparse_proof(&[10, 10]).unwrap();
+        assert_eq!(
+            repeated.verify(&[leaf.clone(), leaf.clone()]).unwrap(),
+            tree.root()
+        );
+        assert_eq!(
+            repeated.verify(&[leaf.clone(), ZERO_LEAF.to_string()]),
+            Err(MerkleError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_sparse_proof_rejects_malformed_proofs() {
+        let tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        let sparse = tree.sparse_proof(&[2]).unwrap();
+        assert!(sparse.non_default_siblings.is_empty());
+
+        let mut extra_sibling = sparse.clone();
+        extra_sibling
+            .non_default_siblings
+            .push(ZERO_LEAF.to_string());
+        let mut missing_default = sparse.clone();
+        missing_default.is_default.pop();
+        let too_deep = SparseProof {
+            direction_bits: vec![false; ZERO_HASHES.len() + 1],
+            non_default_siblings: Vec::new(),
+            is_default: vec![true; ZERO_HASHES.len() + 1],
+        };
+        for malformed in [extra_sibling, missing_default, too_deep] {
+            assert_eq!(
+                malformed.verify(&[ZERO_LEAF.to_string()]),
+                Err(MerkleError::InvalidFormat)
+            );
+        }
+        // three direction bits do not split into two leaves
+        assert_eq!(
+            sparse.verify(&[ZERO_LEAF.to_string(), ZERO_LEAF.to_string()]),
+            Err(MerkleError::InvalidFormat)
+        );
+        assert_eq!(sparse.verify(&[]), Err(MerkleError::InvalidFormat));
+
+        let mut short_sibling = sparse.clone();
+        short_sibling.is_default[1] = false;
+        short_sibling.non_default_siblings.push("0x12".to_string());
+        assert_eq!(
+            short_sibling.verify(&[ZERO_LEAF.to_string()]),
+            Err(MerkleError::InvalidBytes)
+        );
+    }
+}
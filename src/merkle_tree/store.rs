@@ -0,0 +1,412 @@
+//! Node Stores
+//!
+//! `StoredMerkleTree` keeps its nodes in a `NodeStore` instead of memory, so trees can outgrow
+//! RAM or persist across restarts. Nodes are addressed by their array index in the usual
+//! breadth-first layout, and only nodes that differ from the tree's initial state are stored:
+//! an unwritten node is the initial leaf hashed up to its level. Each `set` writes the leaf and
+//! its path in one `NodeStore::write_batch`, so a store that applies batches atomically never
+//! holds a torn path.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::config::TreeConfig;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{
+    check_depth, hash_nodes, normalize_node, Direction, ProofStep,
+};
+use crate::utils::index::{index_to_depth_offset, parent_index};
+use std::collections::HashMap;
+
+/// Storage backend of a `StoredMerkleTree`.
+pub trait NodeStore {
+    /// Returns the node stored at `index`, or `None` if it was never written.
+    fn get(&self, index: usize) -> Result<Option<String>, MerkleError>;
+
+    /// Writes every `(index, node)` pair. Backends should apply the batch atomically.
+    fn write_batch(&mut self, nodes: &[(usize, String)]) -> Result<(), MerkleError>;
+
+    /// Returns the `(depth, initial_leaf)` the store was created for, or `None` for a new store.
+    fn metadata(&self) -> Result<Option<(usize, String)>, MerkleError>;
+
+    /// Records the `(depth, initial_leaf)` a new store is created for.
+    fn set_metadata(&mut self, depth: usize, initial_leaf: &str) -> Result<(), MerkleError>;
+
+    /// Makes every write so far durable. Stores without durability have nothing to do.
+    fn flush(&mut self) -> Result<(), MerkleError> {
+        Ok(())
+    }
+}
+
+/// `NodeStore` holding its nodes in a `HashMap`, for tests and short lived trees.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    nodes: HashMap<usize, String>,
+    metadata: Option<(usize, String)>,
+}
+
+impl MemoryStore {
+    /// returns an empty store
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// returns the number of stored nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// returns true if no node has been stored
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl NodeStore for MemoryStore {
+    fn get(&self, index: usize) -> Result<Option<String>, MerkleError> {
+        Ok(self.nodes.get(&index).cloned())
+    }
+
+    fn write_batch(&mut self, nodes: &[(usize, String)]) -> Result<(), MerkleError> {
+        self.nodes.extend(nodes.iter().cloned());
+        Ok(())
+    }
+
+    fn metadata(&self) -> Result<Option<(usize, String)>, MerkleError> {
+        Ok(self.metadata.clone())
+    }
+
+    fn set_metadata(&mut self, depth: usize, initial_leaf: &str) -> Result<(), MerkleError> {
+        self.metadata = Some((depth, initial_leaf.to_string()));
+        Ok(())
+    }
+}
+
+/// Merkle tree whose nodes live in a `NodeStore`, see the module documentation.
+pub struct StoredMerkleTree<S, H = Sha3_256Hasher> {
+    store: S,
+    // value of an unwritten node at each zero indexed depth, root first
+    defaults: Vec<String>,
+    hasher: H,
+}
+
+impl<S: NodeStore> StoredMerkleTree<S> {
+    /// Opens the tree held by `store`, or creates it if the store is new.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The backend holding the nodes.
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf of a new tree. must be 32 bit hex string starting with '0x'
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the tree, `MerkleError::StoreMismatch` if the store holds a tree of
+    ///   another depth or initial leaf, or Error.
+    pub fn open(store: S, depth: usize, initial_leaf: &str) -> Result<Self, MerkleError> {
+        StoredMerkleTree::open_with_hasher(store, depth, initial_leaf, Sha3_256Hasher)
+    }
+
+    /// Opens the tree held by `store`, or creates it if the store is new, with its depth limited
+    /// by `config` instead of `MAX_DEPTH`. Only written nodes are stored, so a raised limit lets
+    /// mostly unwritten trees go deeper than any in memory tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The backend holding the nodes.
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf of a new tree. must be 32 bit hex string starting with '0x'
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the tree, `MerkleError::MaxDepthExceeded` beyond the configured depth
+    ///   limit, `MerkleError::StoreMismatch` if the store holds a tree of another depth or
+    ///   initial leaf, or Error.
+    pub fn open_with_config(
+        store: S,
+        depth: usize,
+        initial_leaf: &str,
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        StoredMerkleTree::open_with_hasher_and_config(
+            store,
+            depth,
+            initial_leaf,
+            Sha3_256Hasher,
+            config,
+        )
+    }
+}
+
+impl<S: NodeStore, H: MerkleHasher> StoredMerkleTree<S, H> {
+    /// Opens the tree held by `store`, combining nodes with `hasher`, or creates it if the store
+    /// is new.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The backend holding the nodes.
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf of a new tree. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    /// * `hasher` - hash function the tree combines nodes with.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the tree, `MerkleError::StoreMismatch` if the store holds a tree of
+    ///   another depth or initial leaf, or Error.
+    pub fn open_with_hasher(
+        store: S,
+        depth: usize,
+        initial_leaf: &str,
+        hasher: H,
+    ) -> Result<Self, MerkleError> {
+        StoredMerkleTree::open_with_hasher_and_config(
+            store,
+            depth,
+            initial_leaf,
+            hasher,
+            TreeConfig::default(),
+        )
+    }
+
+    /// Opens the tree held by `store`, combining nodes with `hasher`, or creates it if the store
+    /// is new, with its depth limited by `config` instead of `MAX_DEPTH`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The backend holding the nodes.
+    /// * `depth` - The depth of the tree. Ex: depth 20 creates tree with level 0 to level 19.
+    /// * `initial_leaf` - value of every leaf of a new tree. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    /// * `hasher` - hash function the tree combines nodes with.
+    /// * `config` - The options of the tree.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the tree, `MerkleError::MaxDepthExceeded` beyond the configured depth
+    ///   limit, `MerkleError::StoreMismatch` if the store holds a tree of another depth or
+    ///   initial leaf, or Error.
+    pub fn open_with_hasher_and_config(
+        mut store: S,
+        depth: usize,
+        initial_leaf: &str,
+        hasher: H,
+        config: TreeConfig,
+    ) -> Result<Self, MerkleError> {
+        check_depth(depth, config.depth_limit())?;
+        let initial_leaf = normalize_node(initial_leaf, H::DIGEST_BYTES)?;
+        match store.metadata()? {
+            Some((stored_depth, stored_leaf))
+                if stored_depth == depth && stored_leaf == initial_leaf => {}
+            Some(_) => return Err(MerkleError::StoreMismatch),
+            None => store.set_metadata(depth, initial_leaf)?,
+        }
+
+        let mut defaults = Vec::with_capacity(depth);
+        if depth > 0 {
+            defaults.push(initial_leaf.to_string());
+        }
+        for _ in 1..depth {
+            let below = defaults.last().unwrap();
+            defaults.push(hash_nodes(&hasher, below, below)?);
+        }
+        defaults.reverse();
+        Ok(StoredMerkleTree {
+            store,
+            defaults,
+            hasher,
+        })
+    }
+
+    /// returns the depth (one indexed) of the tree, i.e. the number of levels including the root
+    pub fn depth(&self) -> usize {
+        self.defaults.len()
+    }
+
+    /// returns the number of leaves in the tree
+    pub fn num_leaves(&self) -> usize {
+        match self.depth() {
+            0 => 0,
+            depth => 1 << (depth - 1),
+        }
+    }
+
+    // reads the node at `index`, falling back to its level's default
+    fn node(&self, index: usize) -> Result<String, MerkleError> {
+        match self.store.get(index)? {
+            Some(node) => Ok(node),
+            None => Ok(self.defaults[index_to_depth_offset(index).0].clone()),
+        }
+    }
+
+    // returns the array index of a leaf
+    fn leaf_position(&self, leaf_index: usize) -> Result<usize, MerkleError> {
+        if leaf_index >= self.num_leaves() {
+            return Err(MerkleError::InvalidIndex);
+        }
+        Ok(self.num_leaves() - 1 + leaf_index)
+    }
+
+    /// returns the root of the tree, or the hash of the empty string if the tree has no leaves
+    pub fn root(&self) -> Result<String, MerkleError> {
+        match self.depth() {
+            0 => Ok(format!("0x{}", hex::encode(self.hasher.digest(&[])))),
+            _ => self.node(0),
+        }
+    }
+
+    /// Returns the value stored at `leaf_index`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to read.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the leaf, `MerkleError::InvalidIndex` or the store's error.
+    pub fn get_leaf(&self, leaf_index: usize) -> Result<String, MerkleError> {
+        self.node(self.leaf_position(leaf_index)?)
+    }
+
+    /// Sets the leaf at `leaf_index` to `value`, writing the leaf and every node on its path to
+    /// the root in a single batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - The 0 indexed leaf to set.
+    /// * `value` - The new leaf value. Must be `H::DIGEST_BYTES` hex string starting with `0x`
+    ///
+    /// # Returns
+    ///
+    /// * Result containing nothing, or Error.
+    pub fn set(&mut self, leaf_index: usize, value: &str) -> Result<(), MerkleError> {
+        let mut index = self.leaf_position(leaf_index)?;
+        let value = normalize_node(value, H::DIGEST_BYTES)?;
+
+        let mut writes = vec![(index, value.to_string())];
+        while let Some(parent) = parent_index(index) {
+            let node = &writes.last().unwrap().1;
+            let hash = if index % 2 == 1 {
+                hash_nodes(&self.hasher, node, &self.node(index + 1)?)?
+            } else {
+                hash_nodes(&self.hasher, &self.node(index - 1)?, node)?
+            };
+            writes.push((parent, hash));
+            index = parent;
+        }
+        self.store.write_batch(&writes)
+    }
+
+    /// Constructs the proof for `leaf_index`, see `MerkleTree::proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index` - 0 indexed leaf you want to construct a proof for.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the proof steps, `MerkleError::InvalidIndex` or the store's error.
+    pub fn proof(&self, leaf_index: usize) -> Result<Vec<ProofStep>, MerkleError> {
+        let mut index = self.leaf_position(leaf_index)?;
+        let mut proof = Vec::with_capacity(self.depth() - 1);
+        while let Some(parent) = parent_index(index) {
+            proof.push(if index % 2 == 1 {
+                ProofStep::new(Direction::Left, self.node(index + 1)?)
+            } else {
+                ProofStep::new(Direction::Right, self.node(index - 1)?)
+            });
+            index = parent;
+        }
+        Ok(proof)
+    }
+
+    /// makes every write so far durable, see `NodeStore::flush`
+    pub fn flush(&mut self) -> Result<(), MerkleError> {
+        self.store.flush()
+    }
+
+    /// returns the backend holding the nodes
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// consumes the tree, returning its backend
+    pub fn into_store(self) -> S {
+        self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::{MerkleTree, MAX_DEPTH};
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_stored_tree_matches_merkle_tree() {
+        let mut tree = MerkleTree::new(4, LEAF).unwrap();
+        let mut stored = StoredMerkleTree::open(MemoryStore::new(), 4, LEAF).unwrap();
+        assert_eq!(stored.root().unwrap(), tree.root());
+        assert!(stored.store().is_empty());
+
+        for (leaf_index, value) in [(5, 7), (0, 1), (5, 9)] {
+            let value = format!("0x{:064x}", value);
+            tree.set(leaf_index, &value).unwrap();
+            stored.set(leaf_index, &value).unwrap();
+        }
+        assert_eq!(stored.root().unwrap(), tree.root());
+        assert_eq!(stored.proof(5).unwrap(), tree.proof(5).unwrap());
+        assert_eq!(stored.proof(3).unwrap(), tree.proof(3).unwrap());
+        assert_eq!(stored.get_leaf(5).unwrap(), tree.get_leaf(5).unwrap());
+        // two leaves share only the root, so two paths of four nodes hold seven distinct nodes
+        assert_eq!(stored.store().len(), 7);
+
+        assert_eq!(stored.set(8, LEAF), Err(MerkleError::InvalidIndex));
+        assert_eq!(stored.set(0, "0x00"), Err(MerkleError::InvalidBytes));
+        assert_eq!(stored.proof(8), Err(MerkleError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_reopen_checks_metadata() {
+        let mut stored = StoredMerkleTree::open(MemoryStore::new(), 3, LEAF).unwrap();
+        stored.set(2, &format!("0x{:064x}", 7)).unwrap();
+        let root = stored.root().unwrap();
+
+        let store = stored.into_store();
+        let reopened = StoredMerkleTree::open(store.clone(), 3, LEAF).unwrap();
+        assert_eq!(reopened.root().unwrap(), root);
+        assert_eq!(
+            StoredMerkleTree::open(store.clone(), 4, LEAF).err(),
+            Some(MerkleError::StoreMismatch)
+        );
+        let other_leaf = format!("0x{:064x}", 1);
+        assert_eq!(
+            StoredMerkleTree::open(store, 3, &other_leaf).err(),
+            Some(MerkleError::StoreMismatch)
+        );
+    }
+
+    #[test]
+    fn test_depth_limit_is_configurable() {
+        assert_eq!(
+            StoredMerkleTree::open(MemoryStore::new(), 32, LEAF).err(),
+            Some(MerkleError::MaxDepthExceeded {
+                requested: 32,
+                permitted: MAX_DEPTH
+            })
+        );
+
+        // only the written path is stored, so a depth 32 tree stays small
+        let config = TreeConfig::default().max_depth(32).unwrap();
+        let mut stored =
+            StoredMerkleTree::open_with_config(MemoryStore::new(), 32, LEAF, config).unwrap();
+        assert_eq!(stored.num_leaves(), 1 << 31);
+        let value = format!("0x{:064x}", 7);
+        let last_leaf = stored.num_leaves() - 1;
+        stored.set(last_leaf, &value).unwrap();
+        let proof = stored.proof(last_leaf).unwrap();
+        assert_eq!(proof.len(), 31);
+        assert_eq!(
+            MerkleTree::verify(&proof, value).unwrap(),
+            stored.root().unwrap()
+        );
+        assert_eq!(stored.store().len(), 32);
+    }
+}
@@ -0,0 +1,262 @@
+//! Proof Traces
+//!
+//! `MerkleTree::explain` verifies a proof like `MerkleTree::verify`, through the same fold, and
+//! records the inputs and output of every hash on the way to the root. Its `Display` prints one
+//! line per level with hashes shortened to their first and last four hex digits, for walking
+//! someone through why a proof does or does not reach a root:
+//!
+//! ```text
+//! level 0: hash(0x2222…2222 || 0x3333…3333) = 0x777d…3f64
+//! ...
+//! level 3: hash(0xc4ba…23f1 || 0xe7e1…6858) = 0x5705…eff4
+//! root = 0x5705…eff4
+//! ```
+//!
+//! `MerkleTree::explain_verify` does the same against a tree, comparing each hash with the node
+//! the tree actually stores on the proven leaf's path, and reports the first one that differs.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::{MerkleHasher, Sha3_256Hasher};
+use crate::merkle_tree::merkle_tree::{
+    check_proof_length, fold_proof_traced, Direction, MerkleTree, ProofStep, DIGEST_BYTES,
+};
+use std::fmt;
+
+/// One hash of a `ProofTrace`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceStep {
+    /// the level of the proof step, 0 being the leaf's
+    pub level: usize,
+    /// the left input of the hash
+    pub left: String,
+    /// the right input of the hash
+    pub right: String,
+    /// the hash of `left` and `right`
+    pub output: String,
+}
+
+/// The hashes `MerkleTree::explain` computed, leaf first, and the root they lead to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofTrace {
+    /// one step per proof step, leaf first
+    pub steps: Vec<TraceStep>,
+    /// the output of the last step, or the leaf for an empty proof
+    pub root: String,
+}
+
+/// The first node on a proof's path that differs from the tree, see `VerifyReport`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    /// the height of the node above the leaves: 0 for the leaf itself, `k` for the output of
+    /// the proof step at level `k - 1`
+    pub level: usize,
+    /// the node the tree stores there
+    pub expected: String,
+    /// the node the proof computed
+    pub got: String,
+}
+
+/// What `MerkleTree::explain_verify` found walking a proof up the tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifyReport {
+    /// the root the proof leads to
+    pub root: String,
+    /// the lowest node that differs from the tree, `None` if the proof matches it all the way up
+    pub divergence: Option<Divergence>,
+}
+
+impl VerifyReport {
+    /// returns true if every node the proof computed matches the tree
+    pub fn is_valid(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+// shortens a `0x` prefixed node to its first and last four hex digits
+fn truncate(node: &str) -> String {
+    match node.get(2..6).zip(node.get(node.len().saturating_sub(4)..)) {
+        Some((head, tail)) if node.len() > 14 => format!("0x{}…{}", head, tail),
+        _ => node.to_string(),
+    }
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "level {}: hash({} || {}) = {}",
+            self.level,
+            truncate(&self.left),
+            truncate(&self.right),
+            truncate(&self.output)
+        )
+    }
+}
+
+impl fmt::Display for ProofTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        write!(f, "root = {}", truncate(&self.root))
+    }
+}
+
+impl MerkleTree {
+    /// Verifies `proof` like `verify`, recording every hash, see the module documentation.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be 32 bit hex string with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the trace, whose root is the one `verify` returns, or the errors of
+    ///   `verify`.
+    pub fn explain(proof: &[ProofStep], leaf_value: &str) -> Result<ProofTrace, MerkleError> {
+        let mut steps = Vec::with_capacity(proof.len());
+        let root = fold_proof_traced(
+            &Sha3_256Hasher::<DIGEST_BYTES>,
+            proof,
+            leaf_value.to_string(),
+            |level, left, right, output| {
+                steps.push(TraceStep {
+                    level,
+                    left: left.to_string(),
+                    right: right.to_string(),
+                    output: output.to_string(),
+                })
+            },
+        )?;
+        Ok(ProofTrace { steps, root })
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Verifies `proof` against this tree, comparing the leaf and every node the proof computes
+    /// with the node stored on the path of the leaf the proof's directions point to. Unlike
+    /// `verify_against_root` this says where a bad proof went wrong, e.g. which sibling was
+    /// tampered with.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - The proof steps to be verified.
+    /// * `leaf_value` - The value of the leaf you want to verify proof for. Must be `H::DIGEST_BYTES` hex string with `0x` prefix.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the report, `MerkleError::ProofLengthMismatch` if the proof is not
+    ///   `proof_len` steps long, `MerkleError::InvalidIndex` for a tree without leaves, or the
+    ///   errors of `verify`.
+    pub fn explain_verify(
+        &self,
+        proof: &[ProofStep],
+        leaf_value: &str,
+    ) -> Result<VerifyReport, MerkleError> {
+        check_proof_length(proof, self.depth())?;
+        let leaf_index = proof
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| *step.direction() == Direction::Right)
+            .fold(0, |leaf_index, (level, _)| leaf_index | 1 << level);
+        let mut computed = vec![leaf_value.to_string()];
+        let root = fold_proof_traced(
+            self.hasher(),
+            proof,
+            leaf_value.to_string(),
+            |_, _, _, output| computed.push(output.to_string()),
+        )?;
+
+        let mut divergence = None;
+        for (level, got) in computed.into_iter().enumerate() {
+            let expected = self.node(self.proof_len() - level, leaf_index >> level)?;
+            if !expected.eq_ignore_ascii_case(&got) {
+                divergence = Some(Divergence {
+                    level,
+                    expected: expected.to_string(),
+                    got,
+                });
+                break;
+            }
+        }
+        Ok(VerifyReport { root, divergence })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tree of `test_proof`, whose leaf `i` repeats the hex digit `i`
+    fn tree() -> MerkleTree {
+        let leaves: Vec<String> = (0..16)
+            .map(|i| format!("0x{}", format!("{:x}", i).repeat(64)))
+            .collect();
+        MerkleTree::from_leaves(&leaves.iter().map(String::as_str).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_explain_proof() {
+        let tree = tree();
+        let leaf = tree.get_leaf(3).unwrap();
+        let proof = tree.proof(3).unwrap();
+        let trace = MerkleTree::explain(&proof, leaf).unwrap();
+        assert_eq!(trace.root, tree.root());
+        assert_eq!(
+            Ok(trace.root.clone()),
+            MerkleTree::verify(&proof, leaf.to_string())
+        );
+        assert_eq!(
+            trace.to_string(),
+            "level 0: hash(0x2222…2222 || 0x3333…3333) = 0x777d…3f64\n\
+             level 1: hash(0x35e7…9e10 || 0x777d…3f64) = 0xdabd…de90\n\
+             level 2: hash(0xdabd…de90 || 0x26fc…d7c0) = 0xc4ba…23f1\n\
+             level 3: hash(0xc4ba…23f1 || 0xe7e1…6858) = 0x5705…eff4\n\
+             root = 0x5705…eff4"
+        );
+    }
+
+    #[test]
+    fn test_explain_verify_pinpoints_tampered_sibling() {
+        let tree = tree();
+        let leaf = tree.get_leaf(3).unwrap();
+        let proof = tree.proof(3).unwrap();
+        let report = tree.explain_verify(&proof, leaf).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.root, tree.root());
+
+        let mut tampered = proof.clone();
+        tampered[2] = ProofStep::new(*proof[2].direction(), format!("0x{:064x}", 9));
+        let report = tree.explain_verify(&tampered, leaf).unwrap();
+        let divergence = report.divergence.unwrap();
+        // the output of step 2 is the first node above the tampered sibling
+        assert_eq!(divergence.level, 3);
+        assert_eq!(divergence.expected, tree.node(1, 0).unwrap());
+        assert_ne!(divergence.got, divergence.expected);
+        assert_ne!(report.root, tree.root());
+
+        let other_leaf = tree.get_leaf(4).unwrap();
+        let report = tree.explain_verify(&proof, other_leaf).unwrap();
+        assert_eq!(report.divergence.unwrap().level, 0);
+        assert_eq!(
+            tree.explain_verify(&proof[1..], leaf),
+            Err(MerkleError::ProofLengthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain_rejects_what_verify_rejects() {
+        let proof = vec![ProofStep::new(Direction::Left, "0x12".to_string())];
+        let leaf = format!("0x{:064x}", 1);
+        assert_eq!(
+            MerkleTree::explain(&proof, &leaf),
+            Err(MerkleError::InvalidBytes)
+        );
+        let trace = MerkleTree::explain(&[], &leaf).unwrap();
+        assert!(trace.steps.is_empty());
+        assert_eq!(trace.to_string(), "root = 0x0000…0001");
+    }
+}
@@ -0,0 +1,191 @@
+//! Undo History
+//!
+//! Once `MerkleTree::enable_undo` is called, every `set`, `set_batch` and `append` (and the
+//! methods built on them) records the previous values of the nodes on the paths it writes, so
+//! `undo` can revert it in `O(depth)` without rebuilding the tree. A batch is recorded, and undone,
+//! as a single unit. The log keeps the `capacity` most recent mutations and drops the oldest.
+//!
+//! Operations that rewrite the tree in ways a path can't capture (`append_growing` growing the
+//! tree, `repair`, `recompute_subtree` and `rollback`) clear the log.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use std::collections::VecDeque;
+
+/// Everything needed to revert one mutation.
+#[derive(Clone, Debug)]
+pub(crate) struct UndoEntry {
+    // length of the node vector before the mutation, which is shorter only if it appended to an
+    // empty tree
+    pub(crate) node_count: usize,
+    // breadth-first index and previous value of every written node
+    pub(crate) nodes: Vec<(usize, String)>,
+    pub(crate) next_leaf: usize,
+    pub(crate) sorted_len: Option<usize>,
+}
+
+/// Bounded log of recorded mutations, oldest first.
+#[derive(Clone, Debug)]
+pub(crate) struct UndoLog {
+    capacity: usize,
+    entries: VecDeque<UndoEntry>,
+}
+
+impl UndoLog {
+    fn with_capacity(capacity: usize) -> Self {
+        UndoLog {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: UndoEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // overwrites every recorded node value before dropping the history
+    #[cfg(feature = "zeroize")]
+    pub(crate) fn zeroize(&mut self) {
+        use zeroize::Zeroize;
+        for entry in self.entries.iter_mut() {
+            for (_, node) in entry.nodes.iter_mut() {
+                node.zeroize();
+            }
+        }
+        self.clear();
+    }
+}
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Starts recording up to `capacity` mutations for `undo`, see the module documentation.
+    /// Replaces any existing history.
+    pub fn enable_undo(&mut self, capacity: usize) {
+        *self.undo_log_mut() = Some(UndoLog::with_capacity(capacity));
+    }
+
+    /// returns the number of mutations `undo` can currently revert
+    pub fn undo_len(&self) -> usize {
+        self.undo_log_ref().map_or(0, |undo| undo.entries.len())
+    }
+
+    /// Reverts the most recent recorded mutation.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the restored root or `MerkleError::NothingToUndo`.
+    pub fn undo(&mut self) -> Result<String, MerkleError> {
+        let entry = self
+            .undo_log_mut()
+            .as_mut()
+            .and_then(|undo| undo.entries.pop_back())
+            .ok_or(MerkleError::NothingToUndo)?;
+        self.apply_undo(entry);
+        Ok(self.root())
+    }
+
+    /// Reverts the `count` most recent recorded mutations, newest first. Nothing is reverted if
+    /// fewer than `count` are recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of mutations to revert.
+    ///
+    /// # Returns
+    ///
+    /// * Result containing the restored root or `MerkleError::NothingToUndo`.
+    pub fn undo_n(&mut self, count: usize) -> Result<String, MerkleError> {
+        if count > self.undo_len() {
+            return Err(MerkleError::NothingToUndo);
+        }
+        for _ in 0..count {
+            self.undo()?;
+        }
+        Ok(self.root())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    fn value(i: u64) -> String {
+        format!("0x{:064x}", i)
+    }
+
+    #[test]
+    fn test_set_then_undo() {
+        let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        tree.enable_undo(8);
+        let original = tree.clone();
+
+        tree.set(5, &value(1)).unwrap();
+        assert_ne!(tree.root(), original.root());
+        assert_eq!(tree.undo().unwrap(), original.root());
+        assert_eq!(tree.state(), original.state());
+        assert_eq!(tree.undo(), Err(MerkleError::NothingToUndo));
+    }
+
+    #[test]
+    fn test_undo_in_reverse_order() {
+        let mut tree = MerkleTree::new(4, ZERO_LEAF).unwrap();
+        tree.enable_undo(8);
+        let original = tree.root();
+
+        let mut roots = Vec::new();
+        for i in 0..3 {
+            roots.push(tree.root());
+            tree.set(i, &value(i as u64 + 1)).unwrap();
+        }
+        assert_eq!(tree.undo().unwrap(), roots[2]);
+        assert_eq!(tree.undo_n(3), Err(MerkleError::NothingToUndo));
+        assert_eq!(tree.undo_n(2).unwrap(), original);
+    }
+
+    #[test]
+    fn test_batch_and_append_undo() {
+        let mut tree = MerkleTree::empty();
+        tree.enable_undo(8);
+        tree.append(&value(1)).unwrap();
+        tree.append_growing(&value(2)).unwrap();
+        // growing cleared the history, leaving only the append that followed it
+        assert_eq!(tree.undo_len(), 1);
+
+        let before = tree.clone();
+        tree.set_batch(&[(0, &value(3)), (1, &value(4))]).unwrap();
+        tree.set_batch(&[]).unwrap();
+        assert_eq!(tree.undo_len(), 2);
+        tree.undo().unwrap();
+        assert_eq!(tree.state(), before.state());
+
+        let mut empty = MerkleTree::empty();
+        empty.enable_undo(1);
+        empty.append(&value(1)).unwrap();
+        assert_eq!(empty.undo().unwrap(), MerkleTree::empty().root());
+        assert_eq!(empty.num_leaves(), 0);
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        tree.enable_undo(2);
+        for i in 0..3 {
+            tree.set(i, &value(i as u64 + 1)).unwrap();
+        }
+        assert_eq!(tree.undo_len(), 2);
+        tree.undo_n(2).unwrap();
+        assert_eq!(tree.get_leaf(0).unwrap(), value(1));
+        assert_eq!(tree.get_leaf(1).unwrap(), ZERO_LEAF);
+        assert_eq!(tree.undo(), Err(MerkleError::NothingToUndo));
+    }
+}
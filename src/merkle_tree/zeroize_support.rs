@@ -0,0 +1,55 @@
+//! Zeroizing
+//!
+//! `MerkleTree::zeroize` wipes a tree holding secret leaves once it is no longer needed. Every
+//! node string is overwritten with zeros before it is freed, along with the copies of nodes
+//! kept in the proof cache, the undo history and the leaf index, and the tree is left empty.
+//! `ProofStep` implements `Zeroize` too, for proofs handed out earlier. Clones and snapshots are
+//! independent copies and have to be wiped separately.
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::MerkleTree;
+use zeroize::Zeroize;
+
+impl<L, H: MerkleHasher> MerkleTree<L, H> {
+    /// Overwrites every node, cached proof, undo record and indexed leaf value with zeros and
+    /// empties the tree, see the module documentation. Root observers are notified of the empty
+    /// tree's root.
+    pub fn zeroize(&mut self) {
+        if let Some(cache) = self.proof_cache_mut() {
+            cache.zeroize();
+        }
+        if let Some(undo) = self.undo_log_mut() {
+            undo.zeroize();
+        }
+        if let Some(lookup) = self.leaf_lookup_mut() {
+            lookup.zeroize();
+        }
+        self.restore_state(Vec::new(), 0, None).zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ZERO_LEAF;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[test]
+    fn test_zeroize_empties_tree_and_caches() {
+        let mut tree = MerkleTree::new(3, ZERO_LEAF).unwrap();
+        tree.enable_proof_cache(4);
+        tree.enable_undo(4);
+        tree.set(1, LEAF).unwrap();
+        tree.proof(1).unwrap();
+        assert_eq!((tree.proof_cache_len(), tree.undo_len()), (1, 1));
+
+        tree.zeroize();
+        assert_eq!(tree.num_leaves(), 0);
+        assert_eq!(tree.root(), MerkleTree::empty().root());
+        assert_eq!((tree.proof_cache_len(), tree.undo_len()), (0, 0));
+
+        let mut proof = MerkleTree::new(2, LEAF).unwrap().proof(0).unwrap();
+        proof.zeroize();
+        assert!(proof.is_empty());
+    }
+}
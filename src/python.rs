@@ -0,0 +1,242 @@
+//! Python Bindings
+//!
+//! A `merkle_trees` extension module built with PyO3 against the stable ABI, see
+//! `python/pyproject.toml`. It exposes a `MerkleTree` class wrapping the default `MerkleTree`.
+//! Proofs are lists of `(direction, sibling)` tuples with `direction` either `"left"` or
+//! `"right"`. Every `MerkleError` variant is raised as its own exception class, all deriving
+//! from `merkle_trees.MerkleTreeError`.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{
+    normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES,
+};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+create_exception!(merkle_trees, MerkleTreeError, PyException);
+create_exception!(merkle_trees, EncodeError, MerkleTreeError);
+create_exception!(merkle_trees, InvalidBytesError, MerkleTreeError);
+create_exception!(merkle_trees, MaxDepthExceededError, MerkleTreeError);
+create_exception!(merkle_trees, InvalidIndexError, MerkleTreeError);
+create_exception!(merkle_trees, TreeFullError, MerkleTreeError);
+create_exception!(merkle_trees, ProofLengthMismatchError, MerkleTreeError);
+create_exception!(merkle_trees, UnsortedLeavesError, MerkleTreeError);
+create_exception!(merkle_trees, DuplicateLeafError, MerkleTreeError);
+create_exception!(merkle_trees, NotSortedError, MerkleTreeError);
+create_exception!(merkle_trees, LeafPresentError, MerkleTreeError);
+create_exception!(merkle_trees, ValueOutOfRangeError, MerkleTreeError);
+create_exception!(merkle_trees, SerializationError, MerkleTreeError);
+create_exception!(merkle_trees, IntegrityError, MerkleTreeError);
+create_exception!(merkle_trees, NothingToUndoError, MerkleTreeError);
+create_exception!(merkle_trees, StorageError, MerkleTreeError);
+create_exception!(merkle_trees, StoreMismatchError, MerkleTreeError);
+create_exception!(merkle_trees, RootMismatchError, MerkleTreeError);
+create_exception!(merkle_trees, HashFunctionMismatchError, MerkleTreeError);
+create_exception!(merkle_trees, InvalidFormatError, MerkleTreeError);
+create_exception!(
+    merkle_trees,
+    UnsupportedSnapshotVersionError,
+    MerkleTreeError
+);
+
+/// Converts `error` into the Python exception of its variant, carrying its message.
+pub fn to_py_err(error: MerkleError) -> PyErr {
+    let message = error.to_string();
+    match error {
+        MerkleError::EncodeError(_) => EncodeError::new_err(message),
+        MerkleError::InvalidBytes => InvalidBytesError::new_err(message),
+        MerkleError::MaxDepthExceeded { .. } => MaxDepthExceededError::new_err(message),
+        MerkleError::InvalidIndex => InvalidIndexError::new_err(message),
+        MerkleError::TreeFull => TreeFullError::new_err(message),
+        MerkleError::ProofLengthMismatch { .. } => ProofLengthMismatchError::new_err(message),
+        MerkleError::UnsortedLeaves { .. } => UnsortedLeavesError::new_err(message),
+        MerkleError::DuplicateLeaf { .. } => DuplicateLeafError::new_err(message),
+        MerkleError::NotSorted => NotSortedError::new_err(message),
+        MerkleError::LeafPresent { .. } => LeafPresentError::new_err(message),
+        MerkleError::ValueOutOfRange => ValueOutOfRangeError::new_err(message),
+        MerkleError::SerializationError(_) => SerializationError::new_err(message),
+        MerkleError::Integrity(_) => IntegrityError::new_err(message),
+        MerkleError::NothingToUndo => NothingToUndoError::new_err(message),
+        MerkleError::Storage(_) => StorageError::new_err(message),
+        MerkleError::StoreMismatch => StoreMismatchError::new_err(message),
+        MerkleError::RootMismatch { .. } => RootMismatchError::new_err(message),
+        MerkleError::HashFunctionMismatch => HashFunctionMismatchError::new_err(message),
+        MerkleError::InvalidFormat => InvalidFormatError::new_err(message),
+        MerkleError::UnsupportedSnapshotVersion { .. } => {
+            UnsupportedSnapshotVersionError::new_err(message)
+        }
+    }
+}
+
+// converts a proof into the `(direction, sibling)` tuples handed to Python
+fn proof_to_tuples(proof: &[ProofStep]) -> Vec<(&'static str, String)> {
+    proof
+        .iter()
+        .map(|step| {
+            let direction = match step.direction() {
+                Direction::Left => "left",
+                Direction::Right => "right",
+            };
+            (direction, step.sibling().to_string())
+        })
+        .collect()
+}
+
+// converts `(direction, sibling)` tuples from Python back into a proof
+fn proof_from_tuples(proof: Vec<(String, String)>) -> Result<Vec<ProofStep>, MerkleError> {
+    proof
+        .into_iter()
+        .map(|(direction, sibling)| {
+            let direction = match direction.as_str() {
+                "left" => Direction::Left,
+                "right" => Direction::Right,
+                other => {
+                    return Err(MerkleError::SerializationError(format!(
+                        "unknown proof direction {:?}, expected \"left\" or \"right\"",
+                        other
+                    )))
+                }
+            };
+            Ok(ProofStep::new(direction, sibling))
+        })
+        .collect()
+}
+
+/// Python `MerkleTree` class.
+#[pyclass(name = "MerkleTree", module = "merkle_trees")]
+pub struct PyMerkleTree {
+    tree: MerkleTree,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    /// Creates a tree of `depth` whose leaves are all `initial_leaf`, releasing the GIL while
+    /// the tree is built.
+    #[new]
+    fn new(py: Python<'_>, depth: usize, initial_leaf: &str) -> PyResult<Self> {
+        let tree = py
+            .allow_threads(|| MerkleTree::new(depth, initial_leaf))
+            .map_err(to_py_err)?;
+        Ok(PyMerkleTree { tree })
+    }
+
+    /// Sets the leaf at `leaf_index` to `value`.
+    fn set(&mut self, leaf_index: usize, value: &str) -> PyResult<()> {
+        self.tree.set(leaf_index, value).map_err(to_py_err)
+    }
+
+    /// returns the `0x` prefixed root of the tree
+    fn root(&self) -> String {
+        self.tree.root()
+    }
+
+    /// Returns the proof for `leaf_index` as a list of `(direction, sibling)` tuples.
+    fn proof(&self, leaf_index: usize) -> PyResult<Vec<(&'static str, String)>> {
+        let proof = self.tree.proof(leaf_index).map_err(to_py_err)?;
+        Ok(proof_to_tuples(&proof))
+    }
+
+    /// Checks that `proof` leads from `leaf` to `root`. The roots are compared ignoring case and
+    /// surrounding whitespace.
+    #[staticmethod]
+    fn verify(proof: Vec<(String, String)>, leaf: &str, root: &str) -> PyResult<bool> {
+        let proof = proof_from_tuples(proof).map_err(to_py_err)?;
+        let root = normalize_node(root, DIGEST_BYTES).map_err(to_py_err)?;
+        let computed = MerkleTree::verify(&proof, leaf.to_string()).map_err(to_py_err)?;
+        Ok(computed.eq_ignore_ascii_case(root))
+    }
+}
+
+#[pymodule]
+fn merkle_trees(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyMerkleTree>()?;
+    m.add("MerkleTreeError", py.get_type::<MerkleTreeError>())?;
+    m.add("EncodeError", py.get_type::<EncodeError>())?;
+    m.add("InvalidBytesError", py.get_type::<InvalidBytesError>())?;
+    m.add(
+        "MaxDepthExceededError",
+        py.get_type::<MaxDepthExceededError>(),
+    )?;
+    m.add("InvalidIndexError", py.get_type::<InvalidIndexError>())?;
+    m.add("TreeFullError", py.get_type::<TreeFullError>())?;
+    m.add(
+        "ProofLengthMismatchError",
+        py.get_type::<ProofLengthMismatchError>(),
+    )?;
+    m.add("UnsortedLeavesError", py.get_type::<UnsortedLeavesError>())?;
+    m.add("DuplicateLeafError", py.get_type::<DuplicateLeafError>())?;
+    m.add("NotSortedError", py.get_type::<NotSortedError>())?;
+    m.add("LeafPresentError", py.get_type::<LeafPresentError>())?;
+    m.add(
+        "ValueOutOfRangeError",
+        py.get_type::<ValueOutOfRangeError>(),
+    )?;
+    m.add("SerializationError", py.get_type::<SerializationError>())?;
+    m.add("IntegrityError", py.get_type::<IntegrityError>())?;
+    m.add("NothingToUndoError", py.get_type::<NothingToUndoError>())?;
+    m.add("StorageError", py.get_type::<StorageError>())?;
+    m.add("StoreMismatchError", py.get_type::<StoreMismatchError>())?;
+    m.add("RootMismatchError", py.get_type::<RootMismatchError>())?;
+    m.add(
+        "HashFunctionMismatchError",
+        py.get_type::<HashFunctionMismatchError>(),
+    )?;
+    m.add("InvalidFormatError", py.get_type::<InvalidFormatError>())?;
+    m.add(
+        "UnsupportedSnapshotVersionError",
+        py.get_type::<UnsupportedSnapshotVersionError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_tuples_round_trip() {
+        let leaf = "0xabababababababababababababababababababababababababababababababab";
+        let mut tree = MerkleTree::new(3, leaf).unwrap();
+        tree.set(1, &format!("0x{:064x}", 7)).unwrap();
+        let proof = tree.proof(1).unwrap();
+
+        let tuples = proof_to_tuples(&proof);
+        assert_eq!(tuples[0].0, "right");
+        assert_eq!(tuples[1].0, "left");
+        let owned = tuples
+            .into_iter()
+            .map(|(direction, sibling)| (direction.to_string(), sibling))
+            .collect();
+        assert_eq!(proof_from_tuples(owned).unwrap(), proof);
+    }
+
+    #[test]
+    fn test_errors_map_to_distinct_exceptions() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let error = to_py_err(MerkleError::InvalidIndex);
+            assert!(error.is_instance_of::<InvalidIndexError>(py));
+            assert!(error.is_instance_of::<MerkleTreeError>(py));
+            assert!(!error.is_instance_of::<TreeFullError>(py));
+            assert_eq!(
+                error.value(py).to_string(),
+                MerkleError::InvalidIndex.to_string()
+            );
+
+            let error = to_py_err(MerkleError::ProofLengthMismatch {
+                expected: 2,
+                actual: 3,
+            });
+            assert!(error.is_instance_of::<ProofLengthMismatchError>(py));
+        });
+    }
+
+    #[test]
+    fn test_unknown_direction() {
+        assert!(matches!(
+            proof_from_tuples(vec![("up".to_string(), "0x00".to_string())]),
+            Err(MerkleError::SerializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,199 @@
+//! Known-Answer Test Vectors
+//!
+//! Deterministic trees with their roots and proofs, for checking an independent implementation
+//! of the tree against this crate. `generate` fills the leaves of each tree with SplitMix64
+//! output from `seed`, so every vector can be regenerated from `(depth, seed, count)` alone, and
+//! the vectors serialize to JSON with proof steps in the format of `MerkleTree::proof_to_json`.
+//! `vectors/test_vectors.json` holds `generate(4, 42, 2)`.
+//!
+//! `UNIFORM_VECTORS` lists the roots of trees whose leaves all hold one value that this crate's
+//! own tests pin down.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{
+    check_depth, Direction, MerkleTree, DIGEST_BYTES, EMPTY_ROOT, MAX_DEPTH,
+};
+use serde::{Deserialize, Serialize};
+
+/// A tree of `depth` whose leaves all hold `initial_leaf`, and its root.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniformVector {
+    pub depth: usize,
+    pub initial_leaf: &'static str,
+    pub root: &'static str,
+}
+
+/// Roots of `MerkleTree::new(depth, initial_leaf)`.
+pub const UNIFORM_VECTORS: &[UniformVector] = &[
+    UniformVector {
+        depth: 0,
+        initial_leaf: "0xabababababababababababababababababababababababababababababababab",
+        root: EMPTY_ROOT,
+    },
+    UniformVector {
+        depth: 20,
+        initial_leaf: "0xabababababababababababababababababababababababababababababababab",
+        root: "0xd4490f4d374ca8a44685fe9471c5b8dbe58cdffd13d30d9aba15dd29efb92930",
+    },
+];
+
+/// One step of a `ProofVector`, `left` being true when the proven node is the left child.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProofStepVector {
+    pub left: bool,
+    pub sibling: String,
+}
+
+/// A leaf of a `TestVector` with its proof.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProofVector {
+    pub leaf_index: usize,
+    pub leaf: String,
+    pub proof: Vec<ProofStepVector>,
+}
+
+/// A tree built from `leaves` with its root and the proofs of some of its leaves.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestVector {
+    pub depth: usize,
+    pub leaves: Vec<String>,
+    pub root: String,
+    pub proofs: Vec<ProofVector>,
+}
+
+// SplitMix64, the seeding generator of xoshiro, which needs no dependency to reproduce
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Generates `count` trees of `depth` with pseudo-random leaves, see the module documentation.
+/// Each vector proves its first and last leaf and one leaf picked by the generator.
+///
+/// # Arguments
+///
+/// * `depth` - The depth (one indexed) of every tree. A tree of depth `d` has `2^(d - 1)` leaves,
+///   all of which are part of the vector, so keep it small.
+/// * `seed` - The seed of the leaf generator.
+/// * `count` - The number of vectors.
+///
+/// # Returns
+///
+/// * Result containing the vectors, or `MerkleError::MaxDepthExceeded`.
+pub fn generate(depth: usize, seed: u64, count: usize) -> Result<Vec<TestVector>, MerkleError> {
+    check_depth(depth, MAX_DEPTH)?;
+    let leaf_count = match depth {
+        0 => 0,
+        depth => 1 << (depth - 1),
+    };
+    let mut state = seed;
+    let mut vectors = Vec::with_capacity(count);
+    for _ in 0..count {
+        let leaves: Vec<String> = (0..leaf_count)
+            .map(|_| {
+                let words: Vec<String> = (0..DIGEST_BYTES / 8)
+                    .map(|_| format!("{:016x}", splitmix64(&mut state)))
+                    .collect();
+                format!("0x{}", words.concat())
+            })
+            .collect();
+        let tree = MerkleTree::from_leaves(&leaves.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+        let mut indices = Vec::new();
+        if leaf_count > 0 {
+            let picked = (splitmix64(&mut state) % leaf_count as u64) as usize;
+            indices = vec![0, picked, leaf_count - 1];
+            indices.sort_unstable();
+            indices.dedup();
+        }
+        let proofs = indices
+            .into_iter()
+            .map(|leaf_index| {
+                let proof = tree
+                    .proof(leaf_index)?
+                    .iter()
+                    .map(|step| ProofStepVector {
+                        left: *step.direction() == Direction::Left,
+                        sibling: step.sibling().to_string(),
+                    })
+                    .collect();
+                Ok(ProofVector {
+                    leaf_index,
+                    leaf: leaves[leaf_index].clone(),
+                    proof,
+                })
+            })
+            .collect::<Result<Vec<ProofVector>, MerkleError>>()?;
+        vectors.push(TestVector {
+            depth,
+            root: tree.root(),
+            leaves,
+            proofs,
+        });
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::ProofStep;
+
+    #[test]
+    fn test_splitmix64_reference_output() {
+        let mut state = 0;
+        assert_eq!(splitmix64(&mut state), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(splitmix64(&mut state), 0x6e78_9e6a_a1b9_65f4);
+    }
+
+    #[test]
+    fn test_uniform_vectors() {
+        for vector in UNIFORM_VECTORS {
+            let tree = MerkleTree::new(vector.depth, vector.initial_leaf).unwrap();
+            assert_eq!(tree.root(), vector.root);
+        }
+    }
+
+    #[test]
+    fn test_generate_matches_fixture() {
+        let fixture: Vec<TestVector> =
+            serde_json::from_str(include_str!("../vectors/test_vectors.json")).unwrap();
+        assert_eq!(generate(4, 42, 2).unwrap(), fixture);
+
+        for vector in &fixture {
+            assert_eq!(vector.leaves.len(), 8);
+            for proof in &vector.proofs {
+                let steps: Vec<ProofStep> = proof
+                    .proof
+                    .iter()
+                    .map(|step| {
+                        ProofStep::new(
+                            if step.left {
+                                Direction::Left
+                            } else {
+                                Direction::Right
+                            },
+                            step.sibling.clone(),
+                        )
+                    })
+                    .collect();
+                assert_eq!(
+                    MerkleTree::verify(&steps, proof.leaf.clone()).unwrap(),
+                    vector.root
+                );
+            }
+        }
+        assert_ne!(fixture[0].leaves, fixture[1].leaves);
+        assert_ne!(generate(4, 43, 1).unwrap()[0], fixture[0]);
+        assert!(generate(0, 42, 1).unwrap()[0].proofs.is_empty());
+        assert!(matches!(
+            generate(MAX_DEPTH + 1, 42, 1),
+            Err(MerkleError::MaxDepthExceeded { .. })
+        ));
+    }
+}
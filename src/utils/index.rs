@@ -1,4 +1,5 @@
 use crate::errors::errors::ValidationError;
+use std::ops::Range;
 
 /// Given a `(depth, offset)`, calculates and returns the corresponding index.
 ///
@@ -9,13 +10,70 @@ use crate::errors::errors::ValidationError;
 ///
 /// # Returns
 ///
-/// * usize containing the calculated index.
+/// * usize containing the calculated index, or `ValidationError::Overflow` if it does not fit in
+///   a usize.
 pub fn depth_offset_to_index(depth: usize, offset: usize) -> Result<usize, ValidationError> {
-    let base = (1 << depth) - 1;
+    let base = u32::try_from(depth)
+        .ok()
+        .and_then(|depth| 1usize.checked_shl(depth))
+        .and_then(|nodes| nodes.checked_sub(1))
+        .ok_or(ValidationError::Overflow)?;
     if offset > base {
         return Err(ValidationError::Invalid);
     }
-    Ok(base + offset)
+    base.checked_add(offset).ok_or(ValidationError::Overflow)
+}
+
+/// Given the depth (one indexed) of a tree, returns its number of leaves.
+///
+/// # Arguments
+///
+/// * `depth` - The depth of the tree, i.e. the number of levels including the root.
+///
+/// # Returns
+///
+/// * usize containing `2^(depth - 1)`, 0 for depth 0, or `ValidationError::Overflow` if it does not
+///   fit in a usize.
+pub fn leaf_count(depth: usize) -> Result<usize, ValidationError> {
+    match depth.checked_sub(1) {
+        None => Ok(0),
+        Some(height) => u32::try_from(height)
+            .ok()
+            .and_then(|height| 1usize.checked_shl(height))
+            .ok_or(ValidationError::Overflow),
+    }
+}
+
+/// Given the depth (one indexed) of a tree, returns its number of nodes.
+///
+/// # Arguments
+///
+/// * `depth` - The depth of the tree, i.e. the number of levels including the root.
+///
+/// # Returns
+///
+/// * usize containing `2^depth - 1`, or `ValidationError::Overflow` if `2^depth` does not fit in a
+///   usize.
+pub fn node_count(depth: usize) -> Result<usize, ValidationError> {
+    leaf_count(depth)?
+        .checked_mul(2)
+        .map(|nodes| nodes.saturating_sub(1))
+        .ok_or(ValidationError::Overflow)
+}
+
+/// Given a level of a tree, returns the array indices of its nodes in the breadth-first layout.
+///
+/// # Arguments
+///
+/// * `depth` - The level, 0 being the root.
+///
+/// # Returns
+///
+/// * The half-open range `(2^depth - 1, 2^(depth + 1) - 1)` of indices, or
+///   `ValidationError::Overflow` if its end does not fit in a usize.
+pub fn level_index_range(depth: usize) -> Result<(usize, usize), ValidationError> {
+    let end = node_count(depth.checked_add(1).ok_or(ValidationError::Overflow)?)?;
+    Ok((node_count(depth)?, end))
 }
 
 /// Given an index, returns its `(depth, offset)`.
@@ -28,18 +86,15 @@ pub fn depth_offset_to_index(depth: usize, offset: usize) -> Result<usize, Valid
 ///
 /// * A tuple `(depth, offset)`.
 pub fn index_to_depth_offset(index: usize) -> (usize, usize) {
-    let mut depth = 0;
-    let mut nodes_at_current_depth = 1;
+    // the nodes at `depth` start at index `2^depth - 1`, so `usize::MAX` is the first node at
+    // depth `usize::BITS`
+    let depth = match index.checked_add(1) {
+        Some(position) => position.ilog2(),
+        None => usize::BITS,
+    };
+    let base = usize::MAX.checked_shr(usize::BITS - depth).unwrap_or(0);
 
-    while index >= nodes_at_current_depth {
-        depth += 1;
-        nodes_at_current_depth += 1 << depth;
-    }
-
-    let base = if depth == 0 { 0 } else { (1 << depth) - 1 };
-    let offset = index - base;
-
-    (depth, offset)
+    (depth as usize, index - base)
 }
 
 /// Given an index, returns the index of its parent.
@@ -52,11 +107,7 @@ pub fn index_to_depth_offset(index: usize) -> (usize, usize) {
 ///
 /// * An integer representing the index of the parent node.
 pub fn parent_index(index: usize) -> Option<usize> {
-    // TODO: Implement the function.
-    if index == 0 {
-        return None;
-    }
-    return Some((index - 1) / 2);
+    index.checked_sub(1).map(|index| index / 2)
 }
 
 /// Given an index, returns the index of its left-most child.
@@ -67,9 +118,120 @@ pub fn parent_index(index: usize) -> Option<usize> {
 ///
 /// # Returns
 ///
-/// * An integer representing the index of the left-most child node.
-pub fn left_child_index(index: usize) -> usize {
-    return (index * 2) + 1;
+/// * An integer representing the index of the left-most child node, or
+///   `ValidationError::Overflow` if it does not fit in a usize.
+pub fn left_child_index(index: usize) -> Result<usize, ValidationError> {
+    index
+        .checked_mul(2)
+        .and_then(|index| index.checked_add(1))
+        .ok_or(ValidationError::Overflow)
+}
+
+/// Given a node and one of its ancestors, returns how many levels above the node the ancestor
+/// is. A node counts as its own ancestor, at distance 0.
+///
+/// # Arguments
+///
+/// * `node` - The index of the node.
+/// * `ancestor` - The index of the candidate ancestor.
+///
+/// # Returns
+///
+/// * The number of levels between them, or `None` if `ancestor` is not an ancestor of `node`.
+pub fn distance_to_ancestor(node: usize, ancestor: usize) -> Option<usize> {
+    let (node_depth, _) = index_to_depth_offset(node);
+    let (ancestor_depth, _) = index_to_depth_offset(ancestor);
+    let distance = node_depth.checked_sub(ancestor_depth)?;
+    (ancestor_at_distance(node, distance) == ancestor).then_some(distance)
+}
+
+/// Given two nodes, returns whether the first is an ancestor of the second. A node counts as
+/// its own ancestor.
+///
+/// # Arguments
+///
+/// * `a` - The index of the candidate ancestor.
+/// * `b` - The index of the node.
+///
+/// # Returns
+///
+/// * true if `a` lies on the path from `b` to the root.
+pub fn is_ancestor(a: usize, b: usize) -> bool {
+    distance_to_ancestor(b, a).is_some()
+}
+
+/// Given two nodes, returns their lowest common ancestor, which is one of them if it is an
+/// ancestor of the other.
+///
+/// # Arguments
+///
+/// * `a` - The index of the first node.
+/// * `b` - The index of the second node.
+///
+/// # Returns
+///
+/// * The index of the deepest node that is an ancestor of both.
+pub fn lowest_common_ancestor(a: usize, b: usize) -> usize {
+    let (a_depth, _) = index_to_depth_offset(a);
+    let (b_depth, _) = index_to_depth_offset(b);
+    // bring the deeper node up to the other's depth, then drop the differing low bits of their
+    // one indexed heap positions, which are equal from the common ancestor up
+    let a = position(ancestor_at_distance(a, a_depth.saturating_sub(b_depth)));
+    let b = position(ancestor_at_distance(b, b_depth.saturating_sub(a_depth)));
+    let differing = u128::BITS - (a ^ b).leading_zeros();
+    ((a >> differing) - 1) as usize
+}
+
+/// Given a node of a tree of `tree_depth` levels, returns the leaves beneath it.
+///
+/// # Arguments
+///
+/// * `index` - The index of the node.
+/// * `tree_depth` - The depth (one indexed) of the tree, i.e. the number of levels including the root.
+///
+/// # Returns
+///
+/// * The half-open range of leaf indices under the node, `ValidationError::Invalid` if the tree
+///   has no node at `index`, or `ValidationError::Overflow` if the range does not fit in a usize.
+pub fn leaf_range_under(index: usize, tree_depth: usize) -> Result<Range<usize>, ValidationError> {
+    let (depth, offset) = index_to_depth_offset(index);
+    if depth >= tree_depth {
+        return Err(ValidationError::Invalid);
+    }
+    let height = u32::try_from(tree_depth - 1 - depth).map_err(|_| ValidationError::Overflow)?;
+    let start = offset
+        .checked_shl(height)
+        .filter(|start| start >> height == offset)
+        .ok_or(ValidationError::Overflow)?;
+    let count = 1usize
+        .checked_shl(height)
+        .ok_or(ValidationError::Overflow)?;
+    let end = start.checked_add(count).ok_or(ValidationError::Overflow)?;
+    Ok(start..end)
+}
+
+/// Given a node of a tree of `tree_depth` levels, returns the number of leaves beneath it.
+///
+/// # Arguments
+///
+/// * `index` - The index of the node.
+/// * `tree_depth` - The depth (one indexed) of the tree, i.e. the number of levels including the root.
+///
+/// # Returns
+///
+/// * The number of leaves under the node, or the errors of `leaf_range_under`.
+pub fn subtree_leaf_count(index: usize, tree_depth: usize) -> Result<usize, ValidationError> {
+    leaf_range_under(index, tree_depth).map(|leaves| leaves.len())
+}
+
+// the one indexed heap position of a node, in which each level up drops the lowest bit
+fn position(index: usize) -> u128 {
+    index as u128 + 1
+}
+
+// returns the ancestor `distance` levels above `index`, which must be at least that deep
+fn ancestor_at_distance(index: usize, distance: usize) -> usize {
+    ((position(index) >> distance) - 1) as usize
 }
 
 #[cfg(test)]
@@ -89,6 +251,21 @@ mod tests {
         // edge cases
         assert_eq!(depth_offset_to_index(2, 5), Err(ValidationError::Invalid));
         assert_eq!(depth_offset_to_index(1, 2), Err(ValidationError::Invalid));
+        // overflow
+        let bits = usize::BITS as usize;
+        assert_eq!(depth_offset_to_index(bits - 1, 0), Ok(usize::MAX / 2));
+        assert_eq!(
+            depth_offset_to_index(bits - 1, usize::MAX / 2 + 1),
+            Err(ValidationError::Invalid)
+        );
+        assert_eq!(
+            depth_offset_to_index(bits, 0),
+            Err(ValidationError::Overflow)
+        );
+        assert_eq!(
+            depth_offset_to_index(usize::MAX, 0),
+            Err(ValidationError::Overflow)
+        );
     }
 
     #[test]
@@ -101,6 +278,12 @@ mod tests {
         assert_eq!(index_to_depth_offset(14), (3, 7)); // Last leaf node.
         assert_eq!(index_to_depth_offset(15), (4, 0)); // Just after the last leaf node.
         assert_eq!(index_to_depth_offset(16), (4, 1)); // The index after the previous.
+        let bits = usize::BITS as usize;
+        assert_eq!(
+            index_to_depth_offset(usize::MAX - 1),
+            (bits - 1, usize::MAX / 2)
+        );
+        assert_eq!(index_to_depth_offset(usize::MAX), (bits, 0));
     }
 
     #[test]
@@ -112,12 +295,150 @@ mod tests {
         assert_eq!(parent_index(3), Some(1));
         assert_eq!(parent_index(7), Some(3));
         assert_eq!(parent_index(14), Some(6));
+        assert_eq!(parent_index(usize::MAX), Some(usize::MAX / 2));
     }
 
     #[test]
     fn test_left_child_index() {
-        assert_eq!(left_child_index(0), 1);
-        assert_eq!(left_child_index(1), 3);
-        assert_eq!(left_child_index(5), 11);
+        assert_eq!(left_child_index(0), Ok(1));
+        assert_eq!(left_child_index(1), Ok(3));
+        assert_eq!(left_child_index(5), Ok(11));
+        assert_eq!(left_child_index(usize::MAX / 2), Ok(usize::MAX));
+        assert_eq!(
+            left_child_index(usize::MAX / 2 + 1),
+            Err(ValidationError::Overflow)
+        );
+    }
+
+    // walks up from `node` one parent at a time, returning the path including `node`
+    fn path_to_root(node: usize) -> Vec<usize> {
+        std::iter::successors(Some(node), |&index| parent_index(index)).collect()
+    }
+
+    #[test]
+    fn test_ancestry() {
+        // (a, b, lowest common ancestor, distance from b to a if a is an ancestor of b)
+        let cases = [
+            (0, 0, 0, Some(0)),
+            (0, 30, 0, Some(4)),
+            (30, 0, 0, None),
+            (7, 7, 7, Some(0)),
+            (1, 9, 1, Some(2)),
+            (2, 9, 0, None),
+            (15, 16, 7, None),
+            (15, 18, 3, None),
+            (3, 22, 1, None),
+            (12, 27, 2, None),
+            (13, 27, 13, Some(1)),
+            (6, 27, 6, Some(2)),
+            (14, 29, 14, Some(1)),
+        ];
+        for (a, b, lca, distance) in cases {
+            assert_eq!(lowest_common_ancestor(a, b), lca, "lca of {} and {}", a, b);
+            assert_eq!(lowest_common_ancestor(b, a), lca, "lca of {} and {}", b, a);
+            assert_eq!(distance_to_ancestor(b, a), distance, "{} above {}", a, b);
+            assert_eq!(is_ancestor(a, b), distance.is_some(), "{} above {}", a, b);
+        }
+
+        // every pair of the 31 nodes of a depth 5 tree agrees with walking to the root
+        for a in 0..31 {
+            for b in 0..31 {
+                let a_path = path_to_root(a);
+                let b_path = path_to_root(b);
+                let expected = b_path.iter().position(|&node| node == a);
+                assert_eq!(distance_to_ancestor(b, a), expected);
+                let lca = *a_path.iter().find(|node| b_path.contains(node)).unwrap();
+                assert_eq!(lowest_common_ancestor(a, b), lca);
+            }
+        }
+        assert_eq!(
+            lowest_common_ancestor(usize::MAX - 1, usize::MAX - 2),
+            parent_index(usize::MAX - 1).unwrap()
+        );
+        assert!(is_ancestor(0, usize::MAX));
+    }
+
+    #[test]
+    fn test_leaf_ranges_tile_leaves() {
+        assert_eq!(leaf_range_under(0, 4), Ok(0..8));
+        assert_eq!(leaf_range_under(2, 4), Ok(4..8));
+        assert_eq!(leaf_range_under(5, 4), Ok(4..6));
+        assert_eq!(leaf_range_under(9, 4), Ok(2..3));
+        assert_eq!(subtree_leaf_count(1, 4), Ok(4));
+
+        for depth in 0..4 {
+            // the nodes of each level cover every leaf exactly once, in order
+            let mut next_leaf = 0;
+            for offset in 0..1 << depth {
+                let index = depth_offset_to_index(depth, offset).unwrap();
+                let leaves = leaf_range_under(index, 4).unwrap();
+                assert_eq!(leaves.start, next_leaf);
+                assert_eq!(subtree_leaf_count(index, 4), Ok(8 >> depth));
+                next_leaf = leaves.end;
+
+                // and each covers exactly the leaves it is an ancestor of
+                for leaf in 0..8 {
+                    let leaf_node = depth_offset_to_index(3, leaf).unwrap();
+                    assert_eq!(leaves.contains(&leaf), is_ancestor(index, leaf_node));
+                }
+            }
+            assert_eq!(next_leaf, 8);
+        }
+
+        assert_eq!(leaf_range_under(15, 4), Err(ValidationError::Invalid));
+        assert_eq!(leaf_range_under(0, 0), Err(ValidationError::Invalid));
+        assert_eq!(
+            subtree_leaf_count(usize::MAX, 4),
+            Err(ValidationError::Invalid)
+        );
+        assert_eq!(
+            leaf_range_under(0, usize::BITS as usize + 1),
+            Err(ValidationError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_tree_sizes() {
+        assert_eq!((leaf_count(0), node_count(0)), (Ok(0), Ok(0)));
+        assert_eq!((leaf_count(1), node_count(1)), (Ok(1), Ok(1)));
+        assert_eq!(
+            (leaf_count(30), node_count(30)),
+            (Ok(1 << 29), Ok((1 << 30) - 1))
+        );
+
+        // depths whose sizes need more than 32 bits only fit on 64 bit targets
+        let wide = usize::BITS > 32;
+        assert_eq!(node_count(32).is_ok(), wide);
+        assert_eq!(leaf_count(33).is_ok(), wide);
+        assert_eq!(leaf_count(usize::BITS as usize), Ok(1 << (usize::BITS - 1)));
+        assert_eq!(
+            node_count(usize::BITS as usize),
+            Err(ValidationError::Overflow)
+        );
+        assert_eq!(
+            leaf_count(usize::BITS as usize + 1),
+            Err(ValidationError::Overflow)
+        );
+        assert_eq!(node_count(usize::MAX), Err(ValidationError::Overflow));
+    }
+
+    #[test]
+    fn test_level_index_range() {
+        assert_eq!(level_index_range(0), Ok((0, 1)));
+        assert_eq!(level_index_range(1), Ok((1, 3)));
+        assert_eq!(level_index_range(2), Ok((3, 7)));
+        // the last level of a tree ends at its node count
+        assert_eq!(level_index_range(29).map(|(_, end)| end), node_count(30));
+
+        let bits = usize::BITS as usize;
+        assert_eq!(
+            level_index_range(bits - 2),
+            Ok(((1 << (bits - 2)) - 1, (1 << (bits - 1)) - 1))
+        );
+        assert_eq!(level_index_range(bits - 1), Err(ValidationError::Overflow));
+        assert_eq!(
+            level_index_range(usize::MAX),
+            Err(ValidationError::Overflow)
+        );
     }
 }
@@ -1,2 +1,9 @@
-//! Index calculations
+//! Index calculations and leaf helpers
 pub mod index;
+#[cfg(any(test, feature = "rand"))]
+mod random;
+mod validation;
+
+#[cfg(any(test, feature = "rand"))]
+pub use random::{random_leaf, random_updates};
+pub use validation::validate_hex_slice;
@@ -0,0 +1,128 @@
+//! Random Trees
+//!
+//! Helpers for tests that need arbitrary leaves, trees and writes. They draw everything from the
+//! `rand::Rng` passed in, so a seeded generator such as `rand::rngs::StdRng::seed_from_u64`
+//! reproduces the same trees on every run.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::hasher::MerkleHasher;
+use crate::merkle_tree::merkle_tree::{check_depth, MerkleTree, DIGEST_BYTES, MAX_DEPTH};
+use rand::Rng;
+
+/// returns a uniformly random `0x` prefixed 32 byte leaf
+pub fn random_leaf(rng: &mut impl Rng) -> String {
+    let mut bytes = [0u8; DIGEST_BYTES];
+    rng.fill(&mut bytes[..]);
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Sets `count` leaves picked uniformly at random, possibly repeating, to random values.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to write to.
+/// * `count` - The number of writes.
+/// * `rng` - The generator the leaf indices and values are drawn from.
+///
+/// # Returns
+///
+/// * Result containing the `(leaf_index, value)` of each write in the order they were applied,
+///   or `MerkleError::InvalidIndex` if `count` is not zero and the tree has no leaves.
+pub fn random_updates<L, H: MerkleHasher>(
+    tree: &mut MerkleTree<L, H>,
+    count: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<(usize, String)>, MerkleError> {
+    let num_leaves = tree.num_leaves();
+    if count > 0 && num_leaves == 0 {
+        return Err(MerkleError::InvalidIndex);
+    }
+    let mut updates = Vec::with_capacity(count);
+    for _ in 0..count {
+        let leaf_index = rng.gen_range(0..num_leaves);
+        let value = random_leaf(rng);
+        tree.set(leaf_index, &value)?;
+        updates.push((leaf_index, value));
+    }
+    Ok(updates)
+}
+
+impl MerkleTree {
+    /// Constructs a merkle tree of `depth` whose leaves all hold `random_leaf` values.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The depth (one indexed) of the tree.
+    /// * `rng` - The generator the leaves are drawn from.
+    ///
+    /// # Returns
+    ///
+    /// * A new MerkleTree with `2^(depth - 1)` random leaves, or an empty tree for depth 0.
+    ///
+    /// # Panics
+    ///
+    /// * If `depth` exceeds `MAX_DEPTH`.
+    pub fn random(depth: usize, rng: &mut impl Rng) -> Self {
+        check_depth(depth, MAX_DEPTH).expect("depth within MAX_DEPTH");
+        let leaf_count = match depth {
+            0 => 0,
+            depth => 1 << (depth - 1),
+        };
+        let leaves: Vec<String> = (0..leaf_count).map(|_| random_leaf(rng)).collect();
+        MerkleTree::from_leaves(&leaves.iter().map(String::as_str).collect::<Vec<_>>())
+            .expect("random leaves are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_random_trees_are_deterministic() {
+        let tree = MerkleTree::random(5, &mut StdRng::seed_from_u64(7));
+        assert_eq!(tree.depth(), 5);
+        assert_eq!(
+            tree.root(),
+            MerkleTree::random(5, &mut StdRng::seed_from_u64(7)).root()
+        );
+        assert_ne!(
+            tree.root(),
+            MerkleTree::random(5, &mut StdRng::seed_from_u64(8)).root()
+        );
+        assert_eq!(
+            MerkleTree::random(0, &mut StdRng::seed_from_u64(7)).num_leaves(),
+            0
+        );
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let leaf = random_leaf(&mut rng);
+        assert_eq!(leaf.len(), 2 + 2 * DIGEST_BYTES);
+        assert_ne!(leaf, random_leaf(&mut rng));
+    }
+
+    #[test]
+    fn test_random_updates() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut tree = MerkleTree::random(4, &mut rng);
+        let updates = random_updates(&mut tree, 20, &mut rng).unwrap();
+        assert_eq!(updates.len(), 20);
+        for leaf_index in 0..tree.num_leaves() {
+            // the last write to each leaf is the one that sticks
+            if let Some((_, value)) = updates.iter().rev().find(|(i, _)| *i == leaf_index) {
+                assert_eq!(tree.get_leaf(leaf_index).unwrap(), value);
+            }
+        }
+        assert!(tree.validate().is_ok());
+
+        assert_eq!(
+            random_updates(&mut MerkleTree::empty(), 1, &mut rng),
+            Err(MerkleError::InvalidIndex)
+        );
+        assert_eq!(
+            random_updates(&mut MerkleTree::empty(), 0, &mut rng),
+            Ok(vec![])
+        );
+    }
+}
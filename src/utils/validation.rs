@@ -0,0 +1,48 @@
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{validate_leaf, DIGEST_BYTES};
+
+/// Checks every value is a leaf `MerkleTree::from_leaves` accepts, so a large batch can be
+/// rejected before any hashing starts.
+///
+/// # Arguments
+///
+/// * `values` - The leaf values. Each must be 32 byte hex string starting with `0x`
+///
+/// # Returns
+///
+/// * Ok if every value is valid, or the index and error of the first one that is not.
+pub fn validate_hex_slice(values: &[&str]) -> Result<(), (usize, MerkleError)> {
+    for (index, value) in values.iter().enumerate() {
+        validate_leaf(value, DIGEST_BYTES).map_err(|error| (index, error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::merkle_tree::{MerkleTree, ZERO_LEAF};
+
+    #[test]
+    fn test_validate_hex_slice() {
+        let leaf = format!("0x{:064x}", 1);
+        assert_eq!(validate_hex_slice(&[]), Ok(()));
+        assert_eq!(validate_hex_slice(&[ZERO_LEAF, &leaf]), Ok(()));
+        assert!(MerkleTree::from_leaves(&[ZERO_LEAF, &leaf]).is_ok());
+
+        let short = &leaf[..40];
+        let values = [ZERO_LEAF, &leaf, short, "0xzz"];
+        assert_eq!(
+            validate_hex_slice(&values),
+            Err((2, MerkleError::InvalidBytes))
+        );
+        assert_eq!(
+            MerkleTree::from_leaves(&values).err(),
+            Some(MerkleError::InvalidBytes)
+        );
+        assert_eq!(
+            validate_hex_slice(&[&leaf[2..]]),
+            Err((0, MerkleError::InvalidBytes))
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! WebAssembly Bindings
+//!
+//! `wasm-bindgen` exports for browser and Node clients. `JsMerkleTree` wraps the default
+//! `MerkleTree`, and `proof` returns an array of plain `{direction, sibling}` objects with
+//! `direction` either `"left"` or `"right"`, so `JSON.stringify(tree.proof(i))` is exactly what
+//! the standalone `verify` accepts. Errors are thrown as JS `Error`s carrying the `MerkleError`
+//! message.
+use crate::errors::errors::MerkleError;
+use crate::merkle_tree::merkle_tree::{
+    normalize_node, Direction, MerkleTree, ProofStep, DIGEST_BYTES,
+};
+use js_sys::{Array, Object, Reflect};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: MerkleError) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsDirection {
+    Left,
+    Right,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsProofStep {
+    direction: JsDirection,
+    sibling: String,
+}
+
+// builds the `{direction, sibling}` object for one proof step
+fn step_object(step: &ProofStep) -> Object {
+    let direction = match step.direction() {
+        Direction::Left => "left",
+        Direction::Right => "right",
+    };
+    let object = Object::new();
+    for (key, value) in [("direction", direction), ("sibling", step.sibling())] {
+        Reflect::set(&object, &key.into(), &value.into())
+            .expect("plain objects accept new properties");
+    }
+    object
+}
+
+/// JS class wrapping a `MerkleTree`.
+#[wasm_bindgen]
+pub struct JsMerkleTree {
+    tree: MerkleTree,
+}
+
+#[wasm_bindgen]
+impl JsMerkleTree {
+    /// Creates a tree of `depth` whose leaves are all `initial_leaf`, see `MerkleTree::new`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(depth: usize, initial_leaf: &str) -> Result<JsMerkleTree, JsError> {
+        let tree = MerkleTree::new(depth, initial_leaf).map_err(js_error)?;
+        Ok(JsMerkleTree { tree })
+    }
+
+    /// Sets the leaf at `index` to `value`, see `MerkleTree::set`.
+    pub fn set(&mut self, index: usize, value: &str) -> Result<(), JsError> {
+        self.tree.set(index, value).map_err(js_error)
+    }
+
+    /// returns the `0x` prefixed root of the tree
+    pub fn root(&self) -> String {
+        self.tree.root()
+    }
+
+    /// Returns the proof for the leaf at `index` as an array of `{direction, sibling}` objects.
+    pub fn proof(&self, index: usize) -> Result<Array, JsError> {
+        let proof = self.tree.proof(index).map_err(js_error)?;
+        Ok(proof.iter().map(step_object).collect())
+    }
+}
+
+/// Checks that the JSON encoded proof leads from `leaf` to `root`. The roots are compared ignoring
+/// case and surrounding whitespace.
+#[wasm_bindgen]
+pub fn verify(proof_json: &str, leaf: &str, root: &str) -> Result<bool, JsError> {
+    let steps: Vec<JsProofStep> = serde_json::from_str(proof_json)
+        .map_err(|e| js_error(MerkleError::SerializationError(e.to_string())))?;
+    let proof: Vec<ProofStep> = steps
+        .into_iter()
+        .map(|step| {
+            let direction = match step.direction {
+                JsDirection::Left => Direction::Left,
+                JsDirection::Right => Direction::Right,
+            };
+            ProofStep::new(direction, step.sibling)
+        })
+        .collect();
+    let root = normalize_node(root, DIGEST_BYTES).map_err(js_error)?;
+    Ok(MerkleTree::verify(&proof, leaf.to_string())
+        .map_err(js_error)?
+        .eq_ignore_ascii_case(root))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+    #[wasm_bindgen_test]
+    fn test_construct_set_proof_verify() {
+        let value = format!("0x{:064x}", 7);
+        let mut tree = JsMerkleTree::new(3, LEAF).unwrap();
+        tree.set(1, &value).unwrap();
+
+        let proof = tree.proof(1).unwrap();
+        assert_eq!(proof.length(), 2);
+        let proof_json: String = js_sys::JSON::stringify(&proof).unwrap().into();
+        assert!(proof_json.starts_with(r#"[{"direction":"right","sibling":"0x"#));
+
+        assert!(verify(&proof_json, &value, &tree.root()).unwrap());
+        let shouted = format!(" 0x{} ", tree.root()[2..].to_uppercase());
+        assert!(verify(&proof_json, &value, &shouted).unwrap());
+        assert!(!verify(&proof_json, LEAF, &tree.root()).unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_errors_are_thrown() {
+        assert!(JsMerkleTree::new(31, LEAF).is_err());
+        let mut tree = JsMerkleTree::new(2, LEAF).unwrap();
+        assert!(tree.set(2, LEAF).is_err());
+        assert!(tree.proof(2).is_err());
+        assert!(verify("not json", LEAF, LEAF).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Drives the `merkle-cli` binary on temporary snapshot and proof files.
+#![cfg(feature = "cli")]
+use merkle_trees_th::merkle_tree::merkle_tree::MerkleTree;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const LEAF: &str = "0xabababababababababababababababababababababababababababababababab";
+
+fn merkle_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_merkle-cli"))
+        .args(args)
+        .output()
+        .expect("merkle-cli runs")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone())
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+// a path in the temp directory unique to this test process and `name`
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("merkle-cli-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_build_set_proof_verify() {
+    let snapshot = temp_path("tree.bin");
+    let snapshot = snapshot.to_str().unwrap();
+    let value = format!("0x{:064x}", 7);
+
+    let output = merkle_cli(&["build", "--depth", "4", "--leaf", LEAF, "--out", snapshot]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), MerkleTree::new(4, LEAF).unwrap().root());
+
+    let output = merkle_cli(&[
+        "set",
+        "--snapshot",
+        snapshot,
+        "--index",
+        "5",
+        "--value",
+        &value,
+    ]);
+    assert!(output.status.success());
+    let mut expected = MerkleTree::new(4, LEAF).unwrap();
+    expected.set(5, &value).unwrap();
+    let root = stdout(&output);
+    assert_eq!(root, expected.root());
+
+    for format in ["json", "text"] {
+        let output = merkle_cli(&[
+            "proof",
+            "--snapshot",
+            snapshot,
+            "--index",
+            "5",
+            "--format",
+            format,
+        ]);
+        assert!(output.status.success());
+        let proof_file = temp_path(&format!("proof.{}", format));
+        fs::write(&proof_file, stdout(&output)).unwrap();
+        let proof_file = proof_file.to_str().unwrap();
+
+        let output = merkle_cli(&[
+            "verify", "--root", &root, "--leaf", &value, "--proof", proof_file,
+        ]);
+        assert_eq!(output.status.code(), Some(0));
+        let shouted = format!(" 0x{} ", root[2..].to_uppercase());
+        let output = merkle_cli(&[
+            "verify", "--root", &shouted, "--leaf", &value, "--proof", proof_file,
+        ]);
+        assert_eq!(output.status.code(), Some(0));
+        let output = merkle_cli(&[
+            "verify", "--root", &root, "--leaf", LEAF, "--proof", proof_file,
+        ]);
+        assert_eq!(output.status.code(), Some(1));
+        fs::remove_file(proof_file).unwrap();
+    }
+    let output = merkle_cli(&[
+        "proof",
+        "--snapshot",
+        snapshot,
+        "--index",
+        "5",
+        "--format",
+        "text",
+    ]);
+    assert!(stdout(&output).starts_with("R:0x"));
+    fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn test_json_snapshots_are_rewritten_as_containers() {
+    let snapshot = temp_path("tree.json");
+    let tree = MerkleTree::new(3, LEAF).unwrap();
+    fs::write(&snapshot, tree.to_json().unwrap()).unwrap();
+    let snapshot = snapshot.to_str().unwrap();
+
+    let value = format!("0x{:064x}", 7);
+    let output = merkle_cli(&[
+        "set",
+        "--snapshot",
+        snapshot,
+        "--index",
+        "1",
+        "--value",
+        &value,
+    ]);
+    assert!(output.status.success());
+    let rewritten = MerkleTree::from_snapshot_bytes(&fs::read(snapshot).unwrap());
+    let rewritten: MerkleTree = rewritten.unwrap();
+    assert_eq!(rewritten.root(), stdout(&output));
+    assert_eq!(rewritten.get_leaf(1), Ok(value.as_str()));
+    fs::remove_file(snapshot).unwrap();
+}
+
+#[test]
+fn test_errors_exit_with_usage_code() {
+    assert_eq!(merkle_cli(&[]).status.code(), Some(2));
+    assert_eq!(
+        merkle_cli(&["build", "--depth", "4"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(
+        merkle_cli(&["build", "--depth", "31", "--leaf", LEAF])
+            .status
+            .code(),
+        Some(2)
+    );
+    let missing = temp_path("missing.bin");
+    let output = merkle_cli(&[
+        "proof",
+        "--snapshot",
+        missing.to_str().unwrap(),
+        "--index",
+        "0",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot read"));
+}